@@ -0,0 +1,698 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use clap::{Args, Parser, Subcommand};
+use colored::Colorize;
+use log_query_service::models::ai_agent::{AiAgentRequest, AiAgentResponse, AiAgentStopReason};
+use log_query_service::models::query::{
+    AiAnalyzeRequest, AiAnalyzeResponse, AiQueryRequest, AiQueryResponse, AiSearchAnalyzeRequest,
+    AiSearchAnalyzeResponse, LogHit, SearchRequest, SearchResponse,
+};
+use log_query_service::models::time_range::parse_duration;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::process::ExitCode;
+
+/// Search quick-log from the terminal
+#[derive(Parser)]
+#[command(name = "qlog", version)]
+struct Cli {
+    /// query-service base URL
+    #[arg(
+        long,
+        env = "QLOG_SERVER",
+        default_value = "http://localhost:8080",
+        global = true
+    )]
+    server: String,
+
+    /// Print raw JSON instead of formatted output
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Search logs with a Lucene query
+    Search {
+        /// Query string, e.g. 'level:ERROR AND message:timeout'
+        #[arg(default_value = "*")]
+        query: String,
+
+        #[command(flatten)]
+        range: TimeRange,
+
+        #[command(flatten)]
+        filters: Filters,
+
+        /// Number of hits to return
+        #[arg(short = 'n', long, default_value_t = 50)]
+        limit: usize,
+
+        /// Page number (1-based)
+        #[arg(long, default_value_t = 1)]
+        page: usize,
+
+        /// Oldest first
+        #[arg(long)]
+        asc: bool,
+    },
+    /// Follow new logs matching a query
+    Tail {
+        #[arg(default_value = "*")]
+        query: String,
+
+        #[command(flatten)]
+        filters: Filters,
+
+        /// How far back to start, e.g. 5m
+        #[arg(long, default_value = "1m", value_parser = parse_duration)]
+        since: Duration,
+
+        /// Poll interval, e.g. 2s
+        #[arg(long, default_value = "2s", value_parser = parse_duration)]
+        interval: Duration,
+    },
+    /// Show all logs of a trace in chronological order
+    Trace {
+        trace_id: String,
+
+        #[command(flatten)]
+        range: TraceRange,
+    },
+    /// List services seen in the last 24 hours
+    Services,
+    /// Ask the AI analyzer to explain the errors of a trace
+    Analyze {
+        trace_id: String,
+
+        /// Ignore a cached analysis and ask the AI again
+        #[arg(long)]
+        refresh: bool,
+
+        /// Prompt template, see GET /api/v1/ai/templates
+        #[arg(long)]
+        template: Option<String>,
+
+        /// Answer language, e.g. en, zh
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// Turn a question into a search query, e.g. 'payment timeouts in prod since 10am'
+    Ask {
+        question: String,
+
+        /// IANA time zone the question refers to, e.g. Asia/Shanghai
+        #[arg(long, env = "QLOG_TZ")]
+        tz: Option<String>,
+
+        /// Run the generated query instead of only printing it
+        #[arg(long)]
+        run: bool,
+    },
+    /// Let the AI run its own log searches to investigate a question
+    Investigate {
+        question: String,
+
+        /// Start the investigation from this trace
+        #[arg(long)]
+        trace: Option<String>,
+
+        /// IANA time zone the question refers to, e.g. Asia/Shanghai
+        #[arg(long, env = "QLOG_TZ")]
+        tz: Option<String>,
+
+        /// Answer language, e.g. en, zh
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// Ask the AI analyzer to summarize the logs matching a query
+    Summarize {
+        /// Query string, e.g. 'service:checkout AND status:>=500'
+        query: String,
+
+        #[command(flatten)]
+        range: TimeRange,
+
+        #[command(flatten)]
+        filters: Filters,
+
+        /// Number of logs to sample, spread across the time range
+        #[arg(long, default_value_t = 500)]
+        sample: usize,
+
+        /// Ignore a cached analysis and ask the AI again
+        #[arg(long)]
+        refresh: bool,
+
+        /// Prompt template, see GET /api/v1/ai/templates
+        #[arg(long)]
+        template: Option<String>,
+
+        /// Answer language, e.g. en, zh
+        #[arg(long)]
+        language: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct TimeRange {
+    /// Relative start, e.g. 15m, 2h, 1d, 2w
+    #[arg(long, default_value = "15m", value_parser = parse_duration, conflicts_with = "from")]
+    since: Duration,
+
+    /// Absolute start (RFC 3339)
+    #[arg(long, requires = "to")]
+    from: Option<DateTime<Utc>>,
+
+    /// Absolute end (RFC 3339)
+    #[arg(long, requires = "from")]
+    to: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    fn resolve(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        resolve_range(self.since, self.from, self.to)
+    }
+}
+
+/// trace 的时间范围，默认比普通查询宽，覆盖较早发生的请求
+#[derive(Args)]
+struct TraceRange {
+    /// Relative start, e.g. 15m, 2h, 1d, 2w
+    #[arg(long, default_value = "24h", value_parser = parse_duration, conflicts_with = "from")]
+    since: Duration,
+
+    /// Absolute start (RFC 3339)
+    #[arg(long, requires = "to")]
+    from: Option<DateTime<Utc>>,
+
+    /// Absolute end (RFC 3339)
+    #[arg(long, requires = "from")]
+    to: Option<DateTime<Utc>>,
+}
+
+impl TraceRange {
+    fn resolve(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        resolve_range(self.since, self.from, self.to)
+    }
+}
+
+fn resolve_range(
+    since: Duration,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    match (from, to) {
+        (Some(from), Some(to)) => (from, to),
+        _ => {
+            let now = Utc::now();
+            (now - since, now)
+        }
+    }
+}
+
+#[derive(Args)]
+struct Filters {
+    /// Field filter, repeatable: -f service=api -f env=prod
+    #[arg(short = 'f', long = "filter", value_parser = parse_filter)]
+    filters: Vec<(String, String)>,
+}
+
+impl Filters {
+    fn to_map(&self) -> HashMap<String, String> {
+        self.filters.iter().cloned().collect()
+    }
+}
+
+fn parse_filter(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((field, value)) if !field.is_empty() && !value.is_empty() => {
+            Ok((field.to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected field=value, got '{}'", s)),
+    }
+}
+
+struct ApiClient {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl ApiClient {
+    fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Self::decode(response).await
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T, String> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, path))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Self::decode(response).await
+    }
+
+    async fn decode<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, String> {
+        let status = response.status();
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            // 代理等返回的错误可能不是 JSON，此时直接显示响应内容
+            let error = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|body| body["error"].as_str().map(str::to_string));
+            let message = match error {
+                Some(error) => error,
+                None if text.trim().is_empty() => "unknown error".to_string(),
+                None => text.trim().chars().take(200).collect(),
+            };
+            return Err(format!("{}: {}", status, message));
+        }
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    async fn search(&self, req: &SearchRequest) -> Result<SearchResponse, String> {
+        self.post("/api/v1/search", req).await
+    }
+}
+
+fn search_request(
+    query: &str,
+    filters: HashMap<String, String>,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    page: usize,
+    page_size: usize,
+    sort_desc: bool,
+) -> SearchRequest {
+    SearchRequest {
+        query: query.to_string(),
+        filters,
+        time_range_type: "absolute".to_string(),
+        relative_time_key: None,
+        relative_end_key: None,
+        time_zone: None,
+        start_time: Some(start),
+        end_time: Some(end),
+        page,
+        page_size,
+        sort_by: "timestamp".to_string(),
+        sort_desc,
+        sort: Vec::new(),
+        highlight: false,
+        frames: false,
+        no_cache: false,
+    }
+}
+
+#[actix_rt::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let api = ApiClient::new(&cli.server);
+
+    let result = match &cli.command {
+        Command::Search {
+            query,
+            range,
+            filters,
+            limit,
+            page,
+            asc,
+        } => {
+            let req = search_request(query, filters.to_map(), range.resolve(), *page, *limit, !asc);
+            run_search(&api, &req, cli.json).await
+        }
+        Command::Tail {
+            query,
+            filters,
+            since,
+            interval,
+        } => run_tail(&api, query, filters.to_map(), *since, *interval, cli.json).await,
+        Command::Trace { trace_id, range } => {
+            let req = search_request(
+                &format!("trace_id:{}", trace_id),
+                HashMap::new(),
+                range.resolve(),
+                1,
+                1000,
+                false,
+            );
+            run_search(&api, &req, cli.json).await
+        }
+        Command::Services => run_services(&api, cli.json).await,
+        Command::Analyze {
+            trace_id,
+            refresh,
+            template,
+            language,
+        } => {
+            let req = AiAnalyzeRequest {
+                trace_id: trace_id.clone(),
+                force_refresh: *refresh,
+                template: template.clone(),
+                language: language.clone(),
+            };
+            run_analyze(&api, &req, cli.json).await
+        }
+        Command::Ask { question, tz, run } => {
+            let req = AiQueryRequest {
+                question: question.clone(),
+                time_zone: tz.clone(),
+                language: None,
+            };
+            run_ask(&api, &req, *run, cli.json).await
+        }
+        Command::Investigate {
+            question,
+            trace,
+            tz,
+            language,
+        } => {
+            let req = AiAgentRequest {
+                question: question.clone(),
+                trace_id: trace.clone(),
+                time_zone: tz.clone(),
+                language: language.clone(),
+            };
+            run_investigate(&api, &req, cli.json).await
+        }
+        Command::Summarize {
+            query,
+            range,
+            filters,
+            sample,
+            refresh,
+            template,
+            language,
+        } => {
+            let req = AiSearchAnalyzeRequest {
+                search: search_request(query, filters.to_map(), range.resolve(), 1, 1, true),
+                sample_size: *sample,
+                force_refresh: *refresh,
+                template: template.clone(),
+                language: language.clone(),
+            };
+            run_summarize(&api, &req, cli.json).await
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {}", "error:".red().bold(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run_search(api: &ApiClient, req: &SearchRequest, json: bool) -> Result<(), String> {
+    let response = api.search(req).await?;
+
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    for hit in &response.hits {
+        print_hit(hit);
+    }
+    eprintln!(
+        "{}",
+        format!(
+            "{} of {} hits (page {}, {} ms)",
+            response.hits.len(),
+            response.total,
+            response.page,
+            response.took_ms
+        )
+        .dimmed()
+    );
+    Ok(())
+}
+
+async fn run_tail(
+    api: &ApiClient,
+    query: &str,
+    filters: HashMap<String, String>,
+    since: Duration,
+    interval: Duration,
+    json: bool,
+) -> Result<(), String> {
+    let page_size = 1000;
+    let mut cursor = Utc::now() - since;
+    // 每次从上一条日志的时间开始查询（含该时间，避免漏掉同一时刻稍后写入的日志），
+    // 与游标时间相同的日志会再次返回，需要去重
+    let mut seen_at_cursor: HashSet<String> = HashSet::new();
+    let poll = interval
+        .to_std()
+        .map_err(|_| "interval must be positive".to_string())?;
+
+    loop {
+        let end = Utc::now();
+        let mut page = 1;
+
+        loop {
+            let req = search_request(query, filters.clone(), (cursor, end), page, page_size, false);
+            let response = api.search(&req).await?;
+            let fetched = response.hits.len();
+
+            for hit in response.hits {
+                let timestamp = hit.timestamp.with_timezone(&Utc);
+                let key = format!("{}|{}|{}", timestamp, hit.service, hit.message);
+                if timestamp < cursor || seen_at_cursor.contains(&key) {
+                    continue;
+                }
+                if timestamp > cursor {
+                    cursor = timestamp;
+                    seen_at_cursor.clear();
+                }
+                seen_at_cursor.insert(key);
+
+                if json {
+                    println!("{}", serde_json::to_string(&hit).unwrap_or_default());
+                } else {
+                    print_hit(&hit);
+                }
+            }
+
+            if fetched < page_size {
+                break;
+            }
+            page += 1;
+        }
+
+        actix_rt::time::sleep(poll).await;
+    }
+}
+
+async fn run_services(api: &ApiClient, json: bool) -> Result<(), String> {
+    let response: Value = api.get("/api/v1/services").await?;
+
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    for service in response["services"].as_array().into_iter().flatten() {
+        if let Some(service) = service.as_str() {
+            println!("{}", service);
+        }
+    }
+    Ok(())
+}
+
+async fn run_analyze(api: &ApiClient, req: &AiAnalyzeRequest, json: bool) -> Result<(), String> {
+    eprintln!("{}", "Analyzing, this can take a minute...".dimmed());
+    let response: AiAnalyzeResponse = api.post("/api/v1/ai/analyze", req).await?;
+
+    if json {
+        print_json(&response);
+    } else {
+        println!("{} {}\n", "Trace".bold(), response.trace_id.cyan());
+        if let Some(cached_at) = response.cached_at {
+            let cached_at = cached_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+            eprintln!(
+                "{}\n",
+                format!("Cached analysis from {}, use --refresh to re-run", cached_at).dimmed()
+            );
+        }
+        println!("{}", response.analysis);
+    }
+    Ok(())
+}
+
+async fn run_ask(api: &ApiClient, req: &AiQueryRequest, run: bool, json: bool) -> Result<(), String> {
+    let response: AiQueryResponse = api.post("/api/v1/ai/query", req).await?;
+
+    if json && !run {
+        print_json(&response);
+        return Ok(());
+    }
+
+    let search = &response.search;
+    let time_range = match search.time_range_type.as_str() {
+        "relative" => format!(
+            "{} ~ {}",
+            search.relative_time_key.as_deref().unwrap_or_default(),
+            search.relative_end_key.as_deref().unwrap_or("now")
+        ),
+        _ => format!(
+            "{} ~ {}",
+            search.start_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            search.end_time.map(|t| t.to_rfc3339()).unwrap_or_default()
+        ),
+    };
+    let mut filters: Vec<String> = search
+        .filters
+        .iter()
+        .map(|(field, value)| format!("{}={}", field, value))
+        .collect();
+    filters.sort();
+
+    let mut summary = format!("{} {}\n", "Query".bold(), search.query.cyan());
+    if !filters.is_empty() {
+        summary.push_str(&format!("{} {}\n", "Filters".bold(), filters.join(" ")));
+    }
+    summary.push_str(&format!("{} {}\n", "Time".bold(), time_range));
+    if !response.explanation.is_empty() {
+        summary.push_str(&format!("{}\n", response.explanation.dimmed()));
+    }
+    for warning in &response.warnings {
+        summary.push_str(&format!("{} {}\n", "warning:".yellow().bold(), warning));
+    }
+
+    // 执行查询时说明输出到 stderr，stdout 只有搜索结果
+    if run {
+        eprintln!("{}", summary);
+        run_search(api, search, json).await
+    } else {
+        print!("{}", summary);
+        eprintln!("{}", "Use --run to execute it".dimmed());
+        Ok(())
+    }
+}
+
+async fn run_investigate(api: &ApiClient, req: &AiAgentRequest, json: bool) -> Result<(), String> {
+    eprintln!("{}", "Investigating, this can take a few minutes...".dimmed());
+    let response: AiAgentResponse = api.post("/api/v1/ai/agent", req).await?;
+
+    if json {
+        print_json(&response);
+        return Ok(());
+    }
+
+    // 执行过的查询输出到 stderr，stdout 只有结论
+    for step in &response.steps {
+        let result = match (&step.error, &step.summary) {
+            (Some(error), _) => format!("{} {}", "error:".red(), error),
+            (None, Some(summary)) => summary.dimmed().to_string(),
+            (None, None) => String::new(),
+        };
+        eprintln!("{} {} {}  {}", "→".dimmed(), step.tool.cyan(), step.arguments, result);
+    }
+    let stopped = match response.stop_reason {
+        AiAgentStopReason::Completed => "",
+        AiAgentStopReason::StepLimit => ", stopped at the step limit",
+        AiAgentStopReason::TokenLimit => ", stopped at the token budget",
+    };
+    eprintln!(
+        "{}\n",
+        format!(
+            "{} tool calls, {} AI calls, {} tokens{}",
+            response.steps.len(),
+            response.ai_calls,
+            response.usage.prompt_tokens + response.usage.completion_tokens,
+            stopped
+        )
+        .dimmed()
+    );
+    println!("{}", response.analysis);
+    Ok(())
+}
+
+async fn run_summarize(
+    api: &ApiClient,
+    req: &AiSearchAnalyzeRequest,
+    json: bool,
+) -> Result<(), String> {
+    eprintln!("{}", "Analyzing, this can take a minute...".dimmed());
+    let response: AiSearchAnalyzeResponse = api.post("/api/v1/ai/analyze/search", req).await?;
+
+    if json {
+        print_json(&response);
+    } else {
+        println!("{} {}", "Query".bold(), req.search.query.cyan());
+        println!(
+            "{}\n",
+            format!(
+                "{} matching logs, {} sampled, {} patterns",
+                response.total, response.sampled, response.patterns
+            )
+            .dimmed()
+        );
+        if let Some(cached_at) = response.cached_at {
+            let cached_at = cached_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+            eprintln!(
+                "{}\n",
+                format!("Cached analysis from {}, use --refresh to re-run", cached_at).dimmed()
+            );
+        }
+        println!("{}", response.analysis);
+    }
+    Ok(())
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+fn print_hit(hit: &LogHit) {
+    let timestamp = hit
+        .timestamp
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S%.3f")
+        .to_string();
+    let level = format!("{:<5}", hit.level);
+    let level = match hit.level.to_uppercase().as_str() {
+        "ERROR" | "FATAL" => level.red().bold(),
+        "WARN" | "WARNING" => level.yellow(),
+        "INFO" => level.green(),
+        "DEBUG" | "TRACE" => level.blue(),
+        _ => level.normal(),
+    };
+
+    print!(
+        "{} {} {} {}",
+        timestamp.dimmed(),
+        level,
+        format!("[{}]", hit.service).cyan(),
+        hit.message
+    );
+    if let Some(trace_id) = &hit.trace_id {
+        print!(" {}", format!("trace_id={}", trace_id).dimmed());
+    }
+    println!();
+
+    if let Some(stack_trace) = &hit.stack_trace {
+        for line in stack_trace.lines() {
+            println!("    {}", line.dimmed());
+        }
+    }
+}
@@ -1,11 +1,38 @@
+use crate::models::time_range::parse_duration;
 use config::{Config as ConfigBuilder, ConfigError, Environment, File};
+use log::info;
+use reqwest::Url;
 use serde::Deserialize;
 
+/// 配置文件路径（不含扩展名，由 config crate 自动识别格式）
+pub const CONFIG_FILE: &str = "config/config";
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub quickwit: QuickwitConfig,
     pub ai_analyzer: AiAnalyzerConfig,
+
+    #[serde(default)]
+    pub issues: IssuesConfig,
+
+    #[serde(default)]
+    pub stack_trace: StackTraceConfig,
+
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    #[serde(default)]
+    pub ai_cache: AiCacheConfig,
+
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+
+    #[serde(default)]
+    pub ai_sessions: AiSessionsConfig,
+
+    #[serde(default)]
+    pub ai_agent: AiAgentConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -14,26 +41,518 @@ pub struct ServerConfig {
     pub port: u16,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct QuickwitConfig {
     pub base_url: String,
     pub index_id: String,
+
+    /// 单次搜索请求的超时，如 30s
+    #[serde(default = "default_quickwit_timeout")]
+    pub timeout: String,
+
+    /// 连接失败或 5xx 时的最大重试次数（不含首次请求）
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// 首次重试前的退避时长，之后每次翻倍并加随机抖动，如 200ms
+    #[serde(default = "default_retry_backoff")]
+    pub retry_backoff: String,
+
+    /// 连续失败多少次后熔断
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub breaker_failure_threshold: u32,
+
+    /// 熔断持续时长，之后放行一个探测请求，如 30s
+    #[serde(default = "default_breaker_cooldown")]
+    pub breaker_cooldown: String,
+}
+
+fn default_quickwit_timeout() -> String {
+    "30s".to_string()
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff() -> String {
+    "200ms".to_string()
+}
+
+fn default_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_cooldown() -> String {
+    "30s".to_string()
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AiAnalyzerConfig {
+    /// 接口协议：openai（默认，含 OpenRouter 等兼容服务）、anthropic、ollama
+    #[serde(default)]
+    pub provider: AiProviderKind,
     pub base_url: String,
     pub api_key: Option<String>,
     pub model: String,
+
+    /// prompt 中日志部分的 token 预算（按模型粗略估算）
+    #[serde(default = "default_max_context_tokens")]
+    pub max_context_tokens: usize,
+
+    /// 提示词模板目录，文件名（不含扩展名）即模板名
+    #[serde(default = "default_prompt_templates_dir")]
+    pub prompt_templates_dir: String,
+
+    /// 请求未指定模板时使用的模板
+    #[serde(default = "default_template")]
+    pub default_template: String,
+
+    /// 请求未指定语言时的回答语言，如 zh、en
+    #[serde(default = "default_language")]
+    pub default_language: String,
+}
+
+fn default_max_context_tokens() -> usize {
+    6000
+}
+
+fn default_prompt_templates_dir() -> String {
+    "config/prompts".to_string()
+}
+
+fn default_template() -> String {
+    "default".to_string()
+}
+
+fn default_language() -> String {
+    "zh".to_string()
+}
+
+/// AI 服务的接口协议
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiProviderKind {
+    /// OpenAI Chat Completions 及兼容接口（OpenRouter、vLLM 等）
+    #[default]
+    #[serde(rename = "openai", alias = "openai-compatible")]
+    OpenAi,
+    /// Anthropic Messages API
+    Anthropic,
+    /// Ollama 原生接口（/api/chat）
+    Ollama,
+}
+
+impl AiProviderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AiProviderKind::OpenAi => "openai",
+            AiProviderKind::Anthropic => "anthropic",
+            AiProviderKind::Ollama => "ollama",
+        }
+    }
+}
+
+/// 错误聚合（issue 跟踪）配置
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct IssuesConfig {
+    /// 是否在后台定期扫描 ERROR 日志
+    pub enabled: bool,
+
+    /// issue 数据的 JSON 文件路径
+    pub store_path: String,
+
+    /// 扫描间隔，如 1m
+    pub scan_interval: String,
+
+    /// 首次扫描（没有扫描进度时）回溯的时长，如 1h
+    pub initial_lookback: String,
+}
+
+impl Default for IssuesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store_path: "data/issues.json".to_string(),
+            scan_interval: "1m".to_string(),
+            initial_lookback: "1h".to_string(),
+        }
+    }
+}
+
+/// 堆栈解析配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StackTraceConfig {
+    /// 业务代码的包名或路径前缀（如 com.example、/app/src），为空时排除常见框架、标准库与第三方依赖的帧
+    pub in_app_prefixes: Vec<String>,
+}
+
+/// Quickwit 查询结果缓存配置
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+
+    /// 最多缓存的查询数，超出时淘汰最久未使用的
+    pub max_entries: usize,
+
+    /// 时间窗口已完全过去的查询的缓存时长，如 10m
+    pub ttl: String,
+
+    /// 时间窗口接近当前时间的查询的缓存时长，如 10s；为 0 时不缓存
+    pub recent_ttl: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 1000,
+            ttl: "10m".to_string(),
+            recent_ttl: "10s".to_string(),
+        }
+    }
+}
+
+/// AI 分析结果缓存配置
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AiCacheConfig {
+    pub enabled: bool,
+
+    /// 缓存的 JSON 文件路径
+    pub store_path: String,
+
+    /// 缓存时长，如 7d
+    pub ttl: String,
+
+    /// 最多缓存的分析结果数，超出时淘汰最早的
+    pub max_entries: usize,
+}
+
+impl Default for AiCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store_path: "data/ai_cache.json".to_string(),
+            ttl: "7d".to_string(),
+            max_entries: 500,
+        }
+    }
+}
+
+/// AI 多轮分析会话配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AiSessionsConfig {
+    /// 会话的 JSON 文件路径
+    pub store_path: String,
+
+    /// 最多保存的会话数，超出时删除最久未更新的
+    pub max_sessions: usize,
+
+    /// 每个会话最多的追问次数
+    pub max_turns: usize,
+}
+
+impl Default for AiSessionsConfig {
+    fn default() -> Self {
+        Self {
+            store_path: "data/ai_sessions.json".to_string(),
+            max_sessions: 200,
+            max_turns: 20,
+        }
+    }
+}
+
+/// AI 调查：模型自行调用日志搜索等工具的次数与 token 限制
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AiAgentConfig {
+    /// 最多的工具调用轮数，用完后要求模型根据已有结果给出结论
+    pub max_steps: usize,
+
+    /// 整个调查的 token 预算（输入与输出之和），按估算的输入加最大输出计，超出的调用不会发出
+    pub max_tokens: u64,
+
+    /// 每次调用的最大输出 token 数
+    pub max_output_tokens: u32,
+
+    /// 每次工具调用最多返回的日志条数
+    pub max_tool_hits: usize,
+
+    /// 每个工具结果放入对话的最大 token 数，超出部分截断
+    pub max_result_tokens: usize,
+}
+
+impl Default for AiAgentConfig {
+    fn default() -> Self {
+        Self {
+            max_steps: 8,
+            max_tokens: 60000,
+            max_output_tokens: 1500,
+            max_tool_hits: 30,
+            max_result_tokens: 3000,
+        }
+    }
+}
+
+/// 发送给 AI 前的敏感信息脱敏配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+
+    /// 启用的内置检测器
+    pub detectors: Vec<RedactionDetector>,
+
+    /// 自定义规则，在内置检测器之前执行
+    pub custom_patterns: Vec<CustomRedactionPattern>,
+
+    /// 原样发送、不做检测的标签字段（完整路径，如 k8s.namespace）
+    pub allow_fields: Vec<String>,
+
+    /// 整个值都替换为占位符的标签字段，字段名包含其中任一项即匹配（不区分大小写），优先于 allow_fields
+    pub deny_fields: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            detectors: RedactionDetector::ALL.to_vec(),
+            custom_patterns: Vec::new(),
+            allow_fields: Vec::new(),
+            deny_fields: [
+                "password",
+                "passwd",
+                "secret",
+                "token",
+                "authorization",
+                "cookie",
+                "api_key",
+                "credential",
+            ]
+            .iter()
+            .map(|field| field.to_string())
+            .collect(),
+        }
+    }
+}
+
+/// 内置的敏感信息检测器
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionDetector {
+    /// 邮箱地址
+    Email,
+    /// 手机号（中国大陆手机号与带国家码的号码）
+    Phone,
+    /// IPv4 / IPv6 地址
+    Ip,
+    /// JWT
+    Jwt,
+    /// Authorization 头中的 Bearer / Basic 凭证
+    Bearer,
+    /// 常见服务商的 API Key（sk-、AKIA、ghp_ 等前缀）
+    ApiKey,
+    /// password=、token: 等键值对中的值
+    Secret,
+    /// 通过 Luhn 校验的银行卡号
+    CreditCard,
+}
+
+impl RedactionDetector {
+    pub const ALL: [RedactionDetector; 8] = [
+        RedactionDetector::Email,
+        RedactionDetector::Phone,
+        RedactionDetector::Ip,
+        RedactionDetector::Jwt,
+        RedactionDetector::Bearer,
+        RedactionDetector::ApiKey,
+        RedactionDetector::Secret,
+        RedactionDetector::CreditCard,
+    ];
+}
+
+/// 自定义脱敏规则；正则中有名为 `value` 的捕获组时只替换该组，否则替换整个匹配
+#[derive(Debug, Clone, Deserialize)]
+pub struct CustomRedactionPattern {
+    /// 规则名，用作占位符前缀，如 order_id -> <ORDER_ID_1>
+    pub name: String,
+    pub pattern: String,
 }
 
 impl Config {
     pub fn load() -> Result<Self, ConfigError> {
         let config = ConfigBuilder::builder()
-            .add_source(File::with_name("config/config").required(false))
+            .add_source(File::with_name(CONFIG_FILE).required(false))
             .add_source(Environment::with_prefix("APP").separator("__"))
             .build()?;
 
         config.try_deserialize()
     }
+
+    /// 打印 AI 配置（API Key 仅显示末尾 4 位）
+    pub fn log_ai_config(&self) {
+        info!("AI Analyzer Config:");
+        info!("  Provider: {}", self.ai_analyzer.provider.as_str());
+        info!("  Base URL: {}", self.ai_analyzer.base_url);
+        info!("  Model: {}", self.ai_analyzer.model);
+        info!("  API Key exists: {}", self.ai_analyzer.api_key.is_some());
+        if let Some(key) = &self.ai_analyzer.api_key {
+            if key.is_empty() {
+                info!("  WARNING: API key is empty! Set APP__AI_ANALYZER__API_KEY environment variable");
+            } else {
+                info!("  API Key (last 4 chars): ****{}", &key[key.len().saturating_sub(4)..]);
+            }
+        }
+    }
+
+    /// 语义校验：URL 是否合法、端口范围、索引 ID 格式等。返回所有问题而不是只报第一个
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.server.host.trim().is_empty() {
+            errors.push("server.host cannot be empty".to_string());
+        }
+        if self.server.port == 0 {
+            errors.push("server.port must be between 1 and 65535".to_string());
+        }
+
+        validate_url("quickwit.base_url", &self.quickwit.base_url, &mut errors);
+        if !is_valid_index_id(&self.quickwit.index_id) {
+            errors.push(format!(
+                "quickwit.index_id '{}' is invalid: must start with a letter and contain 3-255 letters, digits, '-', '_' or '.'",
+                self.quickwit.index_id
+            ));
+        }
+
+        validate_url("ai_analyzer.base_url", &self.ai_analyzer.base_url, &mut errors);
+        if self.ai_analyzer.model.trim().is_empty() {
+            errors.push("ai_analyzer.model cannot be empty".to_string());
+        }
+        if self.ai_analyzer.max_context_tokens < 500 {
+            errors.push("ai_analyzer.max_context_tokens must be at least 500".to_string());
+        }
+        if self.ai_analyzer.default_template.trim().is_empty() {
+            errors.push("ai_analyzer.default_template cannot be empty".to_string());
+        }
+        if self.ai_analyzer.default_language.trim().is_empty() {
+            errors.push("ai_analyzer.default_language cannot be empty".to_string());
+        }
+
+        if self.issues.store_path.trim().is_empty() {
+            errors.push("issues.store_path cannot be empty".to_string());
+        }
+        for (key, value) in [
+            ("issues.scan_interval", &self.issues.scan_interval),
+            ("issues.initial_lookback", &self.issues.initial_lookback),
+            ("cache.ttl", &self.cache.ttl),
+            ("ai_cache.ttl", &self.ai_cache.ttl),
+            ("quickwit.timeout", &self.quickwit.timeout),
+            ("quickwit.retry_backoff", &self.quickwit.retry_backoff),
+            ("quickwit.breaker_cooldown", &self.quickwit.breaker_cooldown),
+        ] {
+            match parse_duration(value) {
+                Ok(duration) if duration > chrono::Duration::zero() => {}
+                Ok(_) => errors.push(format!("{} must be positive", key)),
+                Err(e) => errors.push(format!("{}: {}", key, e)),
+            }
+        }
+        match parse_duration(&self.cache.recent_ttl) {
+            Ok(duration) if duration < chrono::Duration::zero() => {
+                errors.push("cache.recent_ttl cannot be negative".to_string())
+            }
+            Ok(_) => {}
+            Err(e) => errors.push(format!("cache.recent_ttl: {}", e)),
+        }
+        if self.quickwit.max_retries > 10 {
+            errors.push("quickwit.max_retries must be at most 10".to_string());
+        }
+        if self.quickwit.breaker_failure_threshold == 0 {
+            errors.push("quickwit.breaker_failure_threshold must be at least 1".to_string());
+        }
+        if self.cache.max_entries == 0 {
+            errors.push("cache.max_entries must be at least 1".to_string());
+        }
+        if self.ai_cache.store_path.trim().is_empty() {
+            errors.push("ai_cache.store_path cannot be empty".to_string());
+        }
+        if self.ai_cache.max_entries == 0 {
+            errors.push("ai_cache.max_entries must be at least 1".to_string());
+        }
+        if self.ai_sessions.store_path.trim().is_empty() {
+            errors.push("ai_sessions.store_path cannot be empty".to_string());
+        }
+        if self.ai_sessions.max_sessions == 0 {
+            errors.push("ai_sessions.max_sessions must be at least 1".to_string());
+        }
+        if self.ai_sessions.max_turns == 0 {
+            errors.push("ai_sessions.max_turns must be at least 1".to_string());
+        }
+        if self.ai_agent.max_steps == 0 {
+            errors.push("ai_agent.max_steps must be at least 1".to_string());
+        }
+        if self.ai_agent.max_tokens == 0 {
+            errors.push("ai_agent.max_tokens must be at least 1".to_string());
+        }
+        if self.ai_agent.max_output_tokens == 0 {
+            errors.push("ai_agent.max_output_tokens must be at least 1".to_string());
+        }
+        if self.ai_agent.max_tool_hits == 0 || self.ai_agent.max_tool_hits > 100 {
+            errors.push("ai_agent.max_tool_hits must be between 1 and 100".to_string());
+        }
+        if self.ai_agent.max_result_tokens == 0 {
+            errors.push("ai_agent.max_result_tokens must be at least 1".to_string());
+        }
+        for (index, custom) in self.redaction.custom_patterns.iter().enumerate() {
+            if !custom.name.chars().any(|c| c.is_ascii_alphanumeric()) {
+                errors.push(format!(
+                    "redaction.custom_patterns[{}].name must contain a letter or digit",
+                    index
+                ));
+            }
+            if let Err(e) = regex::Regex::new(&custom.pattern) {
+                errors.push(format!(
+                    "redaction.custom_patterns[{}].pattern is not a valid regex: {}",
+                    index, e
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+fn validate_url(key: &str, value: &str, errors: &mut Vec<String>) {
+    match Url::parse(value) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+            if url.host_str().is_none() {
+                errors.push(format!("{} '{}' has no host", key, value));
+            }
+        }
+        Ok(url) => errors.push(format!(
+            "{} '{}' must use http or https, got '{}'",
+            key,
+            value,
+            url.scheme()
+        )),
+        Err(e) => errors.push(format!("{} '{}' is not a valid URL: {}", key, value, e)),
+    }
+}
+
+/// Quickwit 索引 ID 规则：^[a-zA-Z][a-zA-Z0-9-_\.]{2,254}$
+fn is_valid_index_id(index_id: &str) -> bool {
+    let mut chars = index_id.chars();
+    let starts_with_letter = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+    starts_with_letter
+        && (3..=255).contains(&index_id.len())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
 }
@@ -1,32 +1,81 @@
-use actix_web::{error::ResponseError, HttpResponse};
+use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum AppError {
     #[error("Quickwit error: {0}")]
     QuickwitError(String),
 
+    /// Quickwit 在超时时间内没有返回
+    #[error("Quickwit timeout: {0}")]
+    QuickwitTimeout(String),
+
+    /// Quickwit 无法连接、返回 502/503/504 或已熔断
+    #[error("Quickwit unavailable: {0}")]
+    QuickwitUnavailable(String),
+
+    /// Quickwit 拒绝了查询（如语法错误、字段不存在）
+    #[error("Bad query: {0}")]
+    BadQuery(String),
+
+    /// AI 服务调用失败（连接失败、超时、返回错误或响应无法解析）
+    #[error("AI error: {0}")]
+    AiError(String),
+
     #[error("Validation error: {0}")]
     ValidationError(String),
 
     #[error("Parse error: {0}")]
     ParseError(String),
+
+    #[error("Index not found: {0}")]
+    IndexNotFound(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+}
+
+impl AppError {
+    /// 不带错误类型前缀的错误信息
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::QuickwitError(msg)
+            | AppError::QuickwitTimeout(msg)
+            | AppError::QuickwitUnavailable(msg)
+            | AppError::BadQuery(msg)
+            | AppError::AiError(msg)
+            | AppError::ValidationError(msg)
+            | AppError::ParseError(msg)
+            | AppError::IndexNotFound(msg)
+            | AppError::NotFound(msg)
+            | AppError::StorageError(msg) => msg,
+        }
+    }
 }
 
 impl ResponseError for AppError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self {
-            AppError::ValidationError(msg) => {
-                HttpResponse::BadRequest().json(serde_json::json!({"error": msg}))
-            }
-            AppError::QuickwitError(msg) => {
-                HttpResponse::BadGateway().json(serde_json::json!({"error": msg}))
-            }
-            AppError::ParseError(msg) => {
-                HttpResponse::InternalServerError().json(serde_json::json!({"error": msg}))
-            }
+            AppError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            AppError::QuickwitError(_) => StatusCode::BAD_GATEWAY,
+            AppError::QuickwitTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::QuickwitUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::BadQuery(_) => StatusCode::BAD_REQUEST,
+            AppError::AiError(_) => StatusCode::BAD_GATEWAY,
+            AppError::ParseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::IndexNotFound(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::StorageError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({"error": self.message()}))
+    }
 }
 
 impl From<String> for AppError {
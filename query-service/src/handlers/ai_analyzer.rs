@@ -1,6 +1,41 @@
-use crate::{error::AppError, models::query::{AiAnalyzeRequest, AiAnalyzeResponse}, AppState};
-use actix_web::{web, HttpResponse, Result};
-use log::info;
+use crate::{
+    error::AppError,
+    models::{
+        ai_agent::AiAgentRequest,
+        query::{
+            AiAnalyzeRequest, AiAnalyzeResponse, AiQueryRequest, AiSearchAnalyzeRequest,
+            AiSearchAnalyzeResponse, LogHit,
+        },
+        time_range::parse_time_zone,
+    },
+    services::{
+        ai_agent::AgentContext,
+        ai_analyzer::{AnalysisRequest, AnalysisStream},
+        fingerprint::fingerprint,
+        patterns::{Drain, DEFAULT_SIMILARITY},
+        prompt_packer::PromptEntry,
+        prompt_template::PromptLocale,
+        query_translator::{self, QueryContext, MAX_QUESTION_CHARS},
+        redaction::{strip_placeholder_ids, Placeholders, Redaction},
+        stack_trace::StackTraceParser,
+    },
+    AppState,
+};
+use actix_web::{http::header, web, HttpResponse, Result};
+use chrono::Utc;
+use futures_util::{stream, StreamExt};
+use log::{info, warn};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// 每条日志在 prompt 中保留的堆栈帧数
+const PROMPT_STACK_FRAMES: usize = 8;
+
+/// 分析搜索结果时采样日志数的上限
+const MAX_AI_SAMPLE_SIZE: usize = 2000;
+
+/// 每个模式在 prompt 中列出的服务数
+const PATTERN_SERVICES: usize = 5;
 
 pub async fn analyze_error(
     state: web::Data<AppState>,
@@ -8,60 +43,620 @@ pub async fn analyze_error(
 ) -> Result<HttpResponse, AppError> {
     let trace_id = &req.trace_id;
 
-    // 参数验证
-    if trace_id.is_empty() {
+    info!("AI analyze request for trace_id: {}", trace_id);
+
+    // 查询该 trace_id 的所有错误日志（已脱敏）
+    let Some((request, placeholders)) = prepare_analysis(&state, &req).await? else {
+        let response = AiAnalyzeResponse {
+            analysis: no_error_logs(reply_locale(&state, req.language.as_deref())?).to_string(),
+            trace_id: trace_id.to_string(),
+            context: None,
+            cached_at: None,
+        };
+        return Ok(HttpResponse::Ok().json(response));
+    };
+
+    // 调用 AI 分析（相同日志的分析结果可能来自缓存）
+    let result = state.ai_analyzer().analyze_error_logs(request).await?;
+
+    info!("AI analysis completed for trace_id: {}", trace_id);
+
+    // 回答中的占位符还原为原值后返回
+    let response = AiAnalyzeResponse {
+        analysis: placeholders.rehydrate(&result.analysis),
+        trace_id: trace_id.to_string(),
+        context: Some(result.context),
+        cached_at: result.cached_at,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 流式 AI 分析，以 Server-Sent Events 返回。
+///
+/// 事件：`delta`（`{"content": "..."}`，分析内容增量）、`error`（`{"error": "..."}`，
+/// 生成中途失败）、`done`（结束标记，带上下文统计与缓存时间，总是最后一个事件）。
+/// 命中缓存时只有一个包含完整结果的 `delta`。
+/// 开始生成前的错误（参数错误、查询失败、AI 服务拒绝请求）仍以普通 JSON 错误返回。
+/// 客户端断开连接时取消上游 AI 请求
+pub async fn analyze_error_stream(
+    state: web::Data<AppState>,
+    req: web::Json<AiAnalyzeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let trace_id = req.trace_id.clone();
+
+    info!("AI analyze stream request for trace_id: {}", trace_id);
+
+    let (deltas, context, cached_at) = match prepare_analysis(&state, &req).await? {
+        None => {
+            let analysis = no_error_logs(reply_locale(&state, req.language.as_deref())?);
+            let deltas = stream::once(async move { Ok(analysis.to_string()) }).boxed();
+            (deltas, None, None)
+        }
+        Some((request, placeholders)) => {
+            let result = state
+                .ai_analyzer()
+                .analyze_error_logs_stream(request)
+                .await?;
+            let deltas = placeholders.rehydrate_stream(result.stream);
+            (deltas, Some(result.context), result.cached_at)
+        }
+    };
+
+    Ok(sse_response(
+        deltas,
+        json!({ "trace_id": trace_id, "context": context, "cached_at": cached_at }),
+    ))
+}
+
+/// 找不到错误日志时的分析结果
+fn no_error_logs(locale: PromptLocale) -> &'static str {
+    match locale {
+        PromptLocale::Zh => "未找到该 trace_id 对应的错误日志。",
+        PromptLocale::En => "No error logs were found for this trace_id.",
+    }
+}
+
+/// 没有匹配的日志时的分析结果
+fn no_matching_logs(locale: PromptLocale) -> &'static str {
+    match locale {
+        PromptLocale::Zh => "没有匹配的日志。",
+        PromptLocale::En => "No logs match the search.",
+    }
+}
+
+/// 固定回复的语言，与请求的回答语言一致
+fn reply_locale(state: &AppState, language: Option<&str>) -> Result<PromptLocale, AppError> {
+    let language = state.ai_analyzer().language(language)?;
+    Ok(PromptLocale::for_language(&language))
+}
+
+/// 分析任意搜索结果：按时间分段采样日志，脱敏后按消息模式归类，
+/// 按数量从多到少把每个模式的统计与一条示例交给 AI 总结
+pub async fn analyze_search(
+    state: web::Data<AppState>,
+    req: web::Json<AiSearchAnalyzeRequest>,
+) -> Result<HttpResponse, AppError> {
+    info!("AI analyze request for search: {}", req.search.query);
+
+    let (prepared, sample) = prepare_search_analysis(&state, &req).await?;
+    let Some((request, placeholders)) = prepared else {
+        return Ok(HttpResponse::Ok().json(AiSearchAnalyzeResponse {
+            analysis: no_matching_logs(reply_locale(&state, req.language.as_deref())?).to_string(),
+            total: sample.total,
+            sampled: sample.sampled,
+            patterns: sample.patterns,
+            context: None,
+            cached_at: None,
+        }));
+    };
+
+    let result = state.ai_analyzer().analyze_error_logs(request).await?;
+
+    Ok(HttpResponse::Ok().json(AiSearchAnalyzeResponse {
+        analysis: placeholders.rehydrate(&result.analysis),
+        total: sample.total,
+        sampled: sample.sampled,
+        patterns: sample.patterns,
+        context: Some(result.context),
+        cached_at: result.cached_at,
+    }))
+}
+
+/// 流式分析任意搜索结果，事件与 `/api/v1/ai/analyze/stream` 相同，
+/// `done` 事件带采样统计（total、sampled、patterns）
+pub async fn analyze_search_stream(
+    state: web::Data<AppState>,
+    req: web::Json<AiSearchAnalyzeRequest>,
+) -> Result<HttpResponse, AppError> {
+    info!("AI analyze stream request for search: {}", req.search.query);
+
+    let (prepared, sample) = prepare_search_analysis(&state, &req).await?;
+    let (deltas, context, cached_at) = match prepared {
+        None => {
+            let analysis = no_matching_logs(reply_locale(&state, req.language.as_deref())?);
+            let deltas = stream::once(async move { Ok(analysis.to_string()) }).boxed();
+            (deltas, None, None)
+        }
+        Some((request, placeholders)) => {
+            let result = state
+                .ai_analyzer()
+                .analyze_error_logs_stream(request)
+                .await?;
+            let deltas = placeholders.rehydrate_stream(result.stream);
+            (deltas, Some(result.context), result.cached_at)
+        }
+    };
+
+    Ok(sse_response(
+        deltas,
+        json!({
+            "total": sample.total,
+            "sampled": sample.sampled,
+            "patterns": sample.patterns,
+            "context": context,
+            "cached_at": cached_at,
+        }),
+    ))
+}
+
+/// 把自然语言问题转换为搜索条件，结合可用字段与最近出现的服务生成并校验，
+/// 只返回搜索请求供用户确认或修改，不执行搜索
+pub async fn translate_query(
+    state: web::Data<AppState>,
+    req: web::Json<AiQueryRequest>,
+) -> Result<HttpResponse, AppError> {
+    let question = req.question.trim();
+    if question.is_empty() {
+        return Err(AppError::ValidationError("question is empty".to_string()));
+    }
+    if question.chars().count() > MAX_QUESTION_CHARS {
+        return Err(AppError::ValidationError(format!(
+            "question must be at most {} characters",
+            MAX_QUESTION_CHARS
+        )));
+    }
+    let tz = parse_time_zone(req.time_zone.as_deref()).map_err(AppError::ValidationError)?;
+    let analyzer = state.ai_analyzer();
+    let language = analyzer.language(req.language.as_deref())?;
+
+    info!("AI query translation request: {}", question);
+
+    // 服务列表只用于引导生成，获取失败时仍然转换
+    let services = state.quickwit().list_services().await.unwrap_or_else(|e| {
+        warn!("Failed to list services for query translation: {}", e);
+        Vec::new()
+    });
+    let ctx = QueryContext {
+        services: &services,
+        now: Utc::now().with_timezone(&tz),
+        time_zone: req.time_zone.as_deref(),
+        language: &language,
+    };
+    let response = query_translator::translate(&analyzer, question, &ctx).await?;
+
+    info!("AI query translation result: {}", response.search.query);
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 调查的问题的最大长度（字符数）
+const MAX_AGENT_QUESTION_CHARS: usize = 2000;
+
+/// AI 调查：模型通过搜索日志、查看 trace 与上下文、统计字段取值等工具自行查询，
+/// 在步数与 token 预算内给出结论，同时返回执行过的全部查询
+pub async fn investigate(
+    state: web::Data<AppState>,
+    req: web::Json<AiAgentRequest>,
+) -> Result<HttpResponse, AppError> {
+    let question = req.question.trim();
+    if question.is_empty() {
+        return Err(AppError::ValidationError("question is empty".to_string()));
+    }
+    if question.chars().count() > MAX_AGENT_QUESTION_CHARS {
+        return Err(AppError::ValidationError(format!(
+            "question must be at most {} characters",
+            MAX_AGENT_QUESTION_CHARS
+        )));
+    }
+    let trace_id = req
+        .trace_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|trace_id| !trace_id.is_empty());
+    let tz = parse_time_zone(req.time_zone.as_deref()).map_err(AppError::ValidationError)?;
+    let analyzer = state.ai_analyzer();
+    let language = analyzer.language(req.language.as_deref())?;
+
+    info!("AI agent request: {}", question);
+
+    // 服务列表只用于引导调查，获取失败时仍然继续
+    let quickwit = state.quickwit();
+    let services = quickwit.list_services().await.unwrap_or_else(|e| {
+        warn!("Failed to list services for AI agent: {}", e);
+        Vec::new()
+    });
+    let parser = state.stack_trace_parser();
+    let redactor = state.redactor();
+    let ctx = AgentContext {
+        analyzer: &analyzer,
+        quickwit: &quickwit,
+        parser: &parser,
+        redactor: &redactor,
+        services: &services,
+        now: Utc::now().with_timezone(&tz),
+        time_zone: req.time_zone.as_deref(),
+        language: &language,
+    };
+    let response = state
+        .ai_agent()
+        .investigate(&ctx, question, trace_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 将分析内容增量包装为 SSE 响应，最后发送带 `done` 数据的结束事件
+fn sse_response(deltas: AnalysisStream, done: serde_json::Value) -> HttpResponse {
+    let events = deltas
+        .map(|item| match item {
+            Ok(content) => sse_event("delta", &json!({ "content": content })),
+            Err(e) => {
+                log::error!("AI analysis stream failed: {}", e);
+                sse_event("error", &json!({ "error": e.message() }))
+            }
+        })
+        .chain(stream::once(async move {
+            info!("AI analysis stream completed");
+            sse_event("done", &done)
+        }))
+        .map(Ok::<_, actix_web::Error>);
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // 禁止 nginx 等反向代理缓冲，保证增量及时送达
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events)
+}
+
+fn sse_event(event: &str, data: &serde_json::Value) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// 可用的提示词模板及默认模板、默认语言
+pub async fn list_templates(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    let analyzer = state.ai_analyzer();
+    let templates = analyzer.templates();
+    Ok(HttpResponse::Ok().json(json!({
+        "templates": templates.list(),
+        "default_template": templates.default_template(),
+        "default_language": analyzer.default_language(),
+    })))
+}
+
+/// 校验参数（模板、语言）后查询并格式化错误日志；没有错误日志时返回 None
+pub(super) async fn prepare_analysis(
+    state: &AppState,
+    req: &AiAnalyzeRequest,
+) -> Result<Option<(AnalysisRequest, Placeholders)>, AppError> {
+    if req.trace_id.is_empty() {
         return Err(AppError::ValidationError(
             "trace_id cannot be empty".to_string(),
         ));
     }
+    let analyzer = state.ai_analyzer();
+    let language = analyzer.language(req.language.as_deref())?;
+    let template = analyzer.template(req.template.as_deref(), &language)?;
+    let locale = PromptLocale::for_language(&language);
 
-    info!("AI analyze request for trace_id: {}", trace_id);
+    let error_logs = get_error_logs_by_trace_id(state, &req.trace_id).await?;
+    if error_logs.is_empty() {
+        return Ok(None);
+    }
 
-    // 查询该 trace_id 的所有错误日志
-    let error_logs = get_error_logs_by_trace_id(&state, trace_id).await?;
+    let (logs, placeholders) = format_error_logs(state, &req.trace_id, &error_logs, locale);
 
-    if error_logs.is_empty() {
-        let response = AiAnalyzeResponse {
-            analysis: "未找到该 trace_id 对应的错误日志。".to_string(),
-            trace_id: trace_id.to_string(),
-        };
-        return Ok(HttpResponse::Ok().json(response));
+    let mut services: Vec<String> = Vec::new();
+    for log in &error_logs {
+        if !services.contains(&log.service) {
+            services.push(log.service.clone());
+        }
+    }
+    let time_range = error_logs
+        .iter()
+        .map(|log| log.timestamp)
+        .min()
+        .zip(error_logs.iter().map(|log| log.timestamp).max());
+
+    Ok(Some((
+        AnalysisRequest {
+            subject: format!("trace_id {}", req.trace_id),
+            trace_id: Some(req.trace_id.clone()),
+            scope: format!("Trace ID: {}", req.trace_id),
+            logs,
+            services,
+            time_range,
+            template,
+            language,
+            force_refresh: req.force_refresh,
+        },
+        placeholders,
+    )))
+}
+
+/// 搜索结果的采样情况
+pub(super) struct SearchSample {
+    total: u64,
+    sampled: usize,
+    patterns: usize,
+}
+
+/// 校验参数后采样搜索结果并按消息模式归类；没有匹配的日志时返回 None。
+/// 消息先脱敏再聚类，同一模式中不同的邮箱、号码等会归为同一个变量
+pub(super) async fn prepare_search_analysis(
+    state: &AppState,
+    req: &AiSearchAnalyzeRequest,
+) -> Result<(Option<(AnalysisRequest, Placeholders)>, SearchSample), AppError> {
+    req.search.validate().map_err(AppError::ValidationError)?;
+    if req.sample_size < 1 || req.sample_size > MAX_AI_SAMPLE_SIZE {
+        return Err(AppError::ValidationError(format!(
+            "sample_size must be between 1 and {}",
+            MAX_AI_SAMPLE_SIZE
+        )));
     }
+    let analyzer = state.ai_analyzer();
+    let language = analyzer.language(req.language.as_deref())?;
+    let template = analyzer.template(req.template.as_deref(), &language)?;
+    let locale = PromptLocale::for_language(&language);
 
-    // 格式化日志
-    let formatted_logs: Vec<String> = error_logs
+    let (start_time, end_time) = req
+        .search
+        .compute_time_range()
+        .map_err(AppError::ValidationError)?;
+    let tz = req.search.tz().map_err(AppError::ValidationError)?;
+
+    let sampled = state
+        .quickwit()
+        .sample(&req.search, start_time, end_time, req.sample_size, tz)
+        .await?;
+    let hits = &sampled.hits;
+    let mut sample = SearchSample {
+        total: sampled.total,
+        sampled: hits.len(),
+        patterns: 0,
+    };
+    if hits.is_empty() {
+        return Ok((None, sample));
+    }
+
+    let parser = state.stack_trace_parser();
+    let redactor = state.redactor();
+    let mut redaction = redactor.session();
+
+    let mut drain = Drain::new(DEFAULT_SIMILARITY);
+    for hit in hits {
+        drain.add(&strip_placeholder_ids(&redaction.redact(&hit.message)));
+    }
+    let clusters = drain.into_clusters();
+    sample.patterns = clusters.len();
+
+    let mut services: Vec<String> = Vec::new();
+    let logs: Vec<PromptEntry> = clusters
+        .into_iter()
+        .enumerate()
+        .map(|(index, cluster)| {
+            let members: Vec<&LogHit> = cluster.members.iter().map(|&i| &hits[i]).collect();
+            let first_seen = members.iter().map(|hit| hit.timestamp).min();
+            let last_seen = members.iter().map(|hit| hit.timestamp).max();
+
+            let mut levels: BTreeMap<&str, usize> = BTreeMap::new();
+            let mut pattern_services: Vec<&str> = Vec::new();
+            for hit in &members {
+                *levels.entry(hit.level.as_str()).or_insert(0) += 1;
+                if !pattern_services.contains(&hit.service.as_str()) {
+                    pattern_services.push(&hit.service);
+                }
+                if !services.contains(&hit.service) {
+                    services.push(hit.service.clone());
+                }
+            }
+            let levels: Vec<String> = levels
+                .iter()
+                .map(|(level, count)| format!("{} {}", level, count))
+                .collect();
+            let time_range = first_seen
+                .zip(last_seen)
+                .map(|(first, last)| {
+                    format!(
+                        "{} ~ {}",
+                        first.format("%Y-%m-%d %H:%M:%S"),
+                        last.format("%Y-%m-%d %H:%M:%S")
+                    )
+                })
+                .unwrap_or_default();
+
+            // 采样按时间倒序，第一条即该模式最近的一条日志；数量按所在时间段的采样比例估算
+            let (example, stack) = format_log(members[0], &parser, &mut redaction, locale);
+            let count = sampled.estimate(&cluster.members);
+            let pattern_services: Vec<&str> = pattern_services
+                .iter()
+                .take(PATTERN_SERVICES)
+                .copied()
+                .collect();
+            let header = match locale {
+                PromptLocale::Zh => format!(
+                    "模式：{}\n估算共 {} 条，级别：{}，服务：{}，时间：{}\n示例：{}",
+                    cluster.template,
+                    count,
+                    levels.join("、"),
+                    pattern_services.join("、"),
+                    time_range,
+                    example
+                ),
+                PromptLocale::En => format!(
+                    "Pattern: {}\nEstimated count: {}, levels: {}, services: {}, time: {}\nExample: {}",
+                    cluster.template,
+                    count,
+                    levels.join(", "),
+                    pattern_services.join(", "),
+                    time_range,
+                    example
+                ),
+            };
+            PromptEntry {
+                key: index.to_string(),
+                count: members.len(),
+                header,
+                stack,
+            }
+        })
+        .collect();
+
+    // 查询语句与过滤条件中也可能包含邮箱等敏感信息
+    let zh = locale == PromptLocale::Zh;
+    let query = redaction.redact(&req.search.query);
+    let mut scope = if zh {
+        format!("查询：{}", query)
+    } else {
+        format!("Query: {}", query)
+    };
+    if !req.search.filters.is_empty() {
+        let mut filters: Vec<String> = req
+            .search
+            .filters
+            .iter()
+            .map(|(field, value)| format!("{}={}", field, redaction.redact(value)))
+            .collect();
+        filters.sort();
+        if zh {
+            scope.push_str(&format!("\n过滤：{}", filters.join(", ")));
+        } else {
+            scope.push_str(&format!("\nFilters: {}", filters.join(", ")));
+        }
+    }
+    let all_sampled = sample.sampled as u64 >= sample.total;
+    scope.push('\n');
+    scope.push_str(&match (zh, all_sampled) {
+        (true, true) => format!(
+            "时间范围内共匹配 {} 条日志，全部参与分析，归纳为 {} 种消息模式（按数量从多到少排列）",
+            sample.total, sample.patterns
+        ),
+        (true, false) => format!(
+            "时间范围内共匹配 {} 条日志，按时间分段采样了 {} 条（各段按匹配数分配采样数），归纳为 {} 种消息模式（按数量从多到少排列）",
+            sample.total, sample.sampled, sample.patterns
+        ),
+        (false, true) => format!(
+            "{} logs matched in the time range, all of them analyzed and grouped into {} message patterns (most frequent first)",
+            sample.total, sample.patterns
+        ),
+        (false, false) => format!(
+            "{} logs matched in the time range; {} were sampled across time slices (in proportion to each slice's matches) and grouped into {} message patterns (most frequent first)",
+            sample.total, sample.sampled, sample.patterns
+        ),
+    });
+
+    let placeholders = redaction.into_placeholders();
+    log_redactions(&placeholders, &req.search.query);
+
+    let request = AnalysisRequest {
+        subject: format!("search '{}'", req.search.query),
+        trace_id: None,
+        scope,
+        logs,
+        services,
+        time_range: Some((
+            start_time.with_timezone(&tz).fixed_offset(),
+            end_time.with_timezone(&tz).fixed_offset(),
+        )),
+        template,
+        language,
+        force_refresh: req.force_refresh,
+    };
+    Ok((Some((request, placeholders)), sample))
+}
+
+/// 将错误日志格式化为 prompt 中的日志，以错误指纹作为去重 key。
+/// 同时返回占位符与原值的对应关系，用于还原 AI 的回答
+fn format_error_logs(
+    state: &AppState,
+    trace_id: &str,
+    error_logs: &[LogHit],
+    locale: PromptLocale,
+) -> (Vec<PromptEntry>, Placeholders) {
+    let parser = state.stack_trace_parser();
+    let redactor = state.redactor();
+    let mut redaction = redactor.session();
+    let formatted_logs: Vec<PromptEntry> = error_logs
         .iter()
         .map(|log| {
-            format!(
-                "[{}] [{}] [{}] {}",
-                log.timestamp.format("%Y-%m-%d %H:%M:%S.%3f"),
-                log.level,
-                log.service,
-                log.message
-            )
+            let (header, stack) = format_log(log, &parser, &mut redaction, locale);
+            PromptEntry {
+                key: fingerprint(log, &parser).id,
+                count: 1,
+                header,
+                stack,
+            }
         })
         .collect();
 
-    // 调用 AI 分析
-    let analysis = state
-        .ai_analyzer
-        .analyze_error_logs(formatted_logs, trace_id)
-        .await?;
+    let placeholders = redaction.into_placeholders();
+    log_redactions(&placeholders, trace_id);
 
-    info!("AI analysis completed for trace_id: {}", trace_id);
+    (formatted_logs, placeholders)
+}
 
-    let response = AiAnalyzeResponse {
-        analysis,
-        trace_id: trace_id.to_string(),
-    };
+/// 格式化单条日志：时间、级别、服务、消息与标签，以及只保留异常行和业务帧的堆栈摘要。
+/// 消息、堆栈与标签中的敏感信息替换为占位符
+fn format_log(
+    log: &LogHit,
+    parser: &StackTraceParser,
+    redaction: &mut Redaction,
+    locale: PromptLocale,
+) -> (String, Option<String>) {
+    let mut header = format!(
+        "[{}] [{}] [{}] {}",
+        log.timestamp.format("%Y-%m-%d %H:%M:%S.%3f"),
+        log.level,
+        log.service,
+        redaction.redact(&log.message)
+    );
+    if let Some(labels) = &log.labels {
+        let labels = redaction.redact_labels(labels);
+        if !labels.is_empty() {
+            let title = match locale {
+                PromptLocale::Zh => "标签",
+                PromptLocale::En => "Labels",
+            };
+            header.push_str(&format!("\n{}: {}", title, labels.join(", ")));
+        }
+    }
+    let stack = log.stack_trace.as_ref().map(|stack_trace| {
+        let parsed = parser.parse(stack_trace);
+        // 无法识别的堆栈格式保留原文，由 prompt 打包时截断
+        let stack = if parsed.frames.is_empty() {
+            stack_trace.clone()
+        } else {
+            parsed.summary(PROMPT_STACK_FRAMES)
+        };
+        redaction.redact(&stack)
+    });
+    (header, stack)
+}
 
-    Ok(HttpResponse::Ok().json(response))
+fn log_redactions(placeholders: &Placeholders, subject: &str) {
+    if !placeholders.is_empty() {
+        info!(
+            "Redacted {} sensitive values before AI analysis for {}",
+            placeholders.len(),
+            subject
+        );
+    }
 }
 
 async fn get_error_logs_by_trace_id(
     state: &AppState,
     trace_id: &str,
-) -> Result<Vec<crate::models::query::LogHit>, AppError> {
+) -> Result<Vec<LogHit>, AppError> {
     use crate::models::query::SearchRequest;
     use chrono::Utc;
 
@@ -73,14 +668,20 @@ async fn get_error_logs_by_trace_id(
         filters: std::collections::HashMap::new(),
         time_range_type: "absolute".to_string(),
         relative_time_key: None,
+        relative_end_key: None,
+        time_zone: None,
         start_time: Some(start_time),
         end_time: Some(end_time),
         page: 1,
         page_size: 20,  // 减少到 20 条，减少请求大小
         sort_by: "timestamp".to_string(),
         sort_desc: true,
+        sort: Vec::new(),
+        highlight: false,
+        frames: false,
+        no_cache: false,
     };
 
-    let result = state.quickwit.search(&search_req, start_time, end_time).await?;
+    let result = state.quickwit().search(&search_req, start_time, end_time).await?;
     Ok(result.hits)
 }
@@ -0,0 +1,192 @@
+use super::ai_analyzer::{prepare_analysis, prepare_search_analysis};
+use crate::{
+    error::AppError,
+    models::{
+        ai_sessions::{
+            AiSessionCreateRequest, AiSessionListResponse, AiSessionMessageRequest, AiSessionTurn,
+            AiSessionsQuery,
+        },
+        query::{AiAnalyzeRequest, AiSearchAnalyzeRequest},
+    },
+    services::{ai_provider::ChatMessage, ai_sessions::StoredSession},
+    AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use chrono::Utc;
+use log::info;
+
+/// 追问的最大长度（字符数）
+const MAX_FOLLOW_UP_CHARS: usize = 2000;
+
+pub async fn list_sessions(
+    state: web::Data<AppState>,
+    query: web::Query<AiSessionsQuery>,
+) -> Result<HttpResponse, AppError> {
+    if query.limit < 1 || query.limit > 500 {
+        return Err(AppError::ValidationError(
+            "limit must be between 1 and 500".to_string(),
+        ));
+    }
+
+    let (sessions, total) = state.ai_sessions.list(query.limit);
+    Ok(HttpResponse::Ok().json(AiSessionListResponse { sessions, total }))
+}
+
+pub async fn get_session(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let session = state
+        .ai_sessions
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound(format!("session {} not found", id)))?;
+    Ok(HttpResponse::Ok().json(session.to_response()))
+}
+
+pub async fn delete_session(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    state.ai_sessions.delete(&id).await?;
+    info!("AI session {} deleted", id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// 创建会话：分析一个 trace 的错误或一次搜索的结果作为第一轮，
+/// 首轮提示词与脱敏占位符随会话保存，后续追问在此基础上继续
+pub async fn create_session(
+    state: web::Data<AppState>,
+    req: web::Json<AiSessionCreateRequest>,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+    let (prepared, search) = match (req.trace_id, req.search) {
+        (Some(trace_id), None) => {
+            let analyze = AiAnalyzeRequest {
+                trace_id,
+                force_refresh: false,
+                template: req.template,
+                language: req.language,
+            };
+            let prepared = prepare_analysis(&state, &analyze).await?.ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "no error logs found for trace_id {}",
+                    analyze.trace_id
+                ))
+            })?;
+            (prepared, None)
+        }
+        (None, Some(search)) => {
+            let analyze = AiSearchAnalyzeRequest {
+                search,
+                sample_size: req.sample_size,
+                force_refresh: false,
+                template: req.template,
+                language: req.language,
+            };
+            let (prepared, _) = prepare_search_analysis(&state, &analyze).await?;
+            let prepared = prepared
+                .ok_or_else(|| AppError::NotFound("no logs match the search".to_string()))?;
+            (prepared, Some(analyze.search))
+        }
+        _ => {
+            return Err(AppError::ValidationError(
+                "exactly one of trace_id and search is required".to_string(),
+            ))
+        }
+    };
+    let (request, placeholders) = prepared;
+
+    // 首轮与普通分析相同，可以复用缓存
+    let analyzer = state.ai_analyzer();
+    let (chat, context) = analyzer.prepare_prompt(&request);
+    let result = analyzer
+        .analyze_prepared(&request.subject, &chat, context, false)
+        .await?;
+
+    // 标题取自已脱敏的分析范围（查询语句中可能有敏感信息），展示时还原
+    let title = request.scope.lines().next().unwrap_or_default().to_string();
+    let now = Utc::now();
+    let mut messages = chat.messages;
+    let session = StoredSession {
+        id: StoredSession::new_id(),
+        title,
+        trace_id: request.trace_id,
+        search,
+        template: request.template.name.clone(),
+        language: request.language,
+        system: chat.system,
+        prompt: messages.remove(0).content,
+        temperature: chat.temperature,
+        max_tokens: chat.max_tokens,
+        context: result.context,
+        turns: vec![AiSessionTurn {
+            question: None,
+            answer: result.analysis,
+            created_at: now,
+        }],
+        placeholders,
+        created_at: now,
+        updated_at: now,
+    };
+    state.ai_sessions.insert(session.clone()).await?;
+
+    info!("AI session {} created for {}", session.id, request.subject);
+    Ok(HttpResponse::Ok().json(session.to_response()))
+}
+
+/// 追问：带上首轮日志与之前的全部问答调用 AI，问题中的敏感信息沿用会话的占位符脱敏。
+/// 同一会话的追问依次执行
+pub async fn add_message(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    req: web::Json<AiSessionMessageRequest>,
+) -> Result<HttpResponse, AppError> {
+    let question = req.question.trim();
+    if question.is_empty() {
+        return Err(AppError::ValidationError("question is empty".to_string()));
+    }
+    if question.chars().count() > MAX_FOLLOW_UP_CHARS {
+        return Err(AppError::ValidationError(format!(
+            "question must be at most {} characters",
+            MAX_FOLLOW_UP_CHARS
+        )));
+    }
+
+    let not_found = || AppError::NotFound(format!("session {} not found", id));
+    state.ai_sessions.get(&id).ok_or_else(not_found)?;
+
+    // 持有追问锁直到保存，锁内重新读取会话，包含之前并发追问的问答与占位符
+    let _turn_lock = state.ai_sessions.lock_turns(&id).await;
+    let session = state.ai_sessions.get(&id).ok_or_else(not_found)?;
+    state.ai_sessions.check_follow_up(&session)?;
+
+    info!("AI session {} follow-up question", id);
+
+    let redactor = state.redactor();
+    let mut redaction = redactor.resume(session.placeholders.clone());
+    let question = redaction.redact(question);
+    let placeholders = redaction.into_placeholders();
+
+    let mut messages = session.messages();
+    messages.push(ChatMessage::user(&question));
+    let answer = state
+        .ai_analyzer()
+        .chat(
+            session.system.clone(),
+            messages,
+            session.temperature,
+            session.max_tokens,
+        )
+        .await?;
+
+    let turn = AiSessionTurn {
+        question: Some(question),
+        answer,
+        created_at: Utc::now(),
+    };
+    let session = state
+        .ai_sessions
+        .append_turn(&id, turn, placeholders)
+        .await?;
+    Ok(HttpResponse::Ok().json(session.to_response()))
+}
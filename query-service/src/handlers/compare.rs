@@ -0,0 +1,377 @@
+use crate::{
+    error::AppError,
+    models::{
+        compare::{CompareEntry, CompareRequest, CompareResponse, CompareWindow},
+        query::{AggregationRequest, TermBucket},
+        time_range::parse_duration,
+    },
+    services::{patterns::normalize_message, quickwit::LogSample},
+    AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use futures_util::future::{try_join, try_join_all};
+use log::info;
+use std::collections::{BTreeMap, HashMap};
+
+/// 参与对比的字段维度
+const COMPARE_FIELDS: &[&str] = &["service", "level", "host"];
+
+/// 每个字段聚合的桶数量，只在该范围内识别新出现的值
+const COMPARE_BUCKETS: usize = 100;
+
+pub async fn compare(
+    state: web::Data<AppState>,
+    req: web::Json<CompareRequest>,
+) -> Result<HttpResponse, AppError> {
+    req.search.validate().map_err(AppError::ValidationError)?;
+    if req.limit < 1 || req.limit > 200 {
+        return Err(AppError::ValidationError(
+            "limit must be between 1 and 200".to_string(),
+        ));
+    }
+    if req.sample_size < 1 || req.sample_size > 1000 {
+        return Err(AppError::ValidationError(
+            "sample_size must be between 1 and 1000".to_string(),
+        ));
+    }
+
+    let current = req
+        .search
+        .compute_time_range()
+        .map_err(AppError::ValidationError)?;
+    let tz = req.search.tz().map_err(AppError::ValidationError)?;
+    let baseline = baseline_window(&req, current)?;
+
+    info!(
+        "Compare request: query={}, current={} ~ {}, baseline={} ~ {}",
+        req.search.query, current.0, current.1, baseline.0, baseline.1
+    );
+
+    let quickwit = state.quickwit();
+
+    // 各字段在两个窗口上的聚合
+    let aggregations = COMPARE_FIELDS.iter().flat_map(|field| {
+        [current, baseline].map(|window| {
+            let agg_req = AggregationRequest {
+                search: req.search.for_window(window.0, window.1, 1, 1),
+                field: field.to_string(),
+                size: COMPARE_BUCKETS,
+            };
+            let quickwit = quickwit.clone();
+            async move { quickwit.aggregate(&agg_req, window.0, window.1).await }
+        })
+    });
+
+    // 消息模式统计使用按时间分段的采样日志，避免只采到窗口末尾而把其他模式误判为新增或消失
+    let sample = |window: (DateTime<Utc>, DateTime<Utc>)| {
+        let quickwit = quickwit.clone();
+        let search = &req.search;
+        let sample_size = req.sample_size;
+        async move {
+            quickwit
+                .sample(search, window.0, window.1, sample_size, tz)
+                .await
+        }
+    };
+
+    let (aggregations, (current_sample, baseline_sample)) = try_join(
+        try_join_all(aggregations),
+        try_join(sample(current), sample(baseline)),
+    )
+    .await?;
+
+    // 速率比：当前窗口与基线窗口长度不同时按单位时间比较
+    let rate_ratio = window_seconds(baseline) / window_seconds(current);
+
+    let mut dimensions = BTreeMap::new();
+    for (field, pair) in COMPARE_FIELDS.iter().zip(aggregations.chunks(2)) {
+        let entries = diff(
+            &bucket_counts(&pair[0].buckets),
+            &bucket_counts(&pair[1].buckets),
+            &HashMap::new(),
+            rate_ratio,
+            req.limit,
+        );
+        dimensions.insert(field.to_string(), entries);
+    }
+
+    // 模式数量为按采样权重估算的匹配数，与其他维度一样按单位时间比较
+    let (current_patterns, examples) = pattern_counts(&current_sample);
+    let (baseline_patterns, _) = pattern_counts(&baseline_sample);
+    dimensions.insert(
+        "pattern".to_string(),
+        diff(
+            &current_patterns,
+            &baseline_patterns,
+            &examples,
+            rate_ratio,
+            req.limit,
+        ),
+    );
+
+    let response = CompareResponse {
+        current: CompareWindow {
+            start_time: current.0,
+            end_time: current.1,
+            total: aggregations[0].total,
+            sampled: current_sample.hits.len(),
+        },
+        baseline: CompareWindow {
+            start_time: baseline.0,
+            end_time: baseline.1,
+            total: aggregations[1].total,
+            sampled: baseline_sample.hits.len(),
+        },
+        dimensions,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+fn baseline_window(
+    req: &CompareRequest,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+) -> Result<(DateTime<Utc>, DateTime<Utc>), AppError> {
+    if let (Some(baseline_start), Some(baseline_end)) =
+        (req.baseline_start_time, req.baseline_end_time)
+    {
+        if baseline_start >= baseline_end {
+            return Err(AppError::ValidationError(
+                "baseline_start_time must be before baseline_end_time".to_string(),
+            ));
+        }
+        return Ok((baseline_start, baseline_end));
+    }
+
+    let offset = match req.baseline_offset.as_deref() {
+        Some(offset) => parse_duration(offset).map_err(AppError::ValidationError)?,
+        None => end - start,
+    };
+    if offset <= chrono::Duration::zero() {
+        return Err(AppError::ValidationError(
+            "baseline_offset must be positive".to_string(),
+        ));
+    }
+
+    Ok((start - offset, end - offset))
+}
+
+fn window_seconds((start, end): (DateTime<Utc>, DateTime<Utc>)) -> f64 {
+    ((end - start).num_milliseconds() as f64 / 1000.0).max(1.0)
+}
+
+fn bucket_counts(buckets: &[TermBucket]) -> HashMap<String, u64> {
+    buckets
+        .iter()
+        .map(|bucket| {
+            let key = match bucket.key.as_str() {
+                Some(key) => key.to_string(),
+                None => bucket.key.to_string(),
+            };
+            (key, bucket.doc_count)
+        })
+        .collect()
+}
+
+/// 按归一化后的消息模式估算匹配数，并记录每个模式的一条示例
+fn pattern_counts(sample: &LogSample) -> (HashMap<String, u64>, HashMap<String, String>) {
+    let mut members: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut examples = HashMap::new();
+    for (index, hit) in sample.hits.iter().enumerate() {
+        let pattern = normalize_message(&hit.message);
+        members.entry(pattern.clone()).or_default().push(index);
+        examples
+            .entry(pattern)
+            .or_insert_with(|| hit.message.clone());
+    }
+    let counts = members
+        .into_iter()
+        .map(|(pattern, members)| {
+            let count = sample.estimate(&members);
+            (pattern, count)
+        })
+        .collect();
+    (counts, examples)
+}
+
+/// 计算两个窗口的计数差异。`rate_ratio` 为基线与当前窗口的规模比，用于换算相对变化。
+/// 排序：仅当前窗口出现的值在前（按数量），其余按相对增幅从大到小
+fn diff(
+    current: &HashMap<String, u64>,
+    baseline: &HashMap<String, u64>,
+    examples: &HashMap<String, String>,
+    rate_ratio: f64,
+    limit: usize,
+) -> Vec<CompareEntry> {
+    let mut keys: Vec<&String> = current.keys().chain(baseline.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut entries: Vec<CompareEntry> = keys
+        .into_iter()
+        .map(|key| {
+            let current_count = current.get(key).copied().unwrap_or(0);
+            let baseline_count = baseline.get(key).copied().unwrap_or(0);
+            let change_ratio = (baseline_count > 0).then(|| {
+                current_count as f64 * rate_ratio / baseline_count as f64 - 1.0
+            });
+
+            CompareEntry {
+                key: key.clone(),
+                current: current_count,
+                baseline: baseline_count,
+                delta: current_count as i64 - baseline_count as i64,
+                change_ratio,
+                new: baseline_count == 0,
+                example: examples.get(key).cloned(),
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.new
+            .cmp(&a.new)
+            .then_with(|| match (a.change_ratio, b.change_ratio) {
+                (Some(a_ratio), Some(b_ratio)) => b_ratio.total_cmp(&a_ratio),
+                _ => b.current.cmp(&a.current),
+            })
+            .then_with(|| b.delta.cmp(&a.delta))
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    entries.truncate(limit);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::query::LogHit;
+    use serde_json::json;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn request(extra: serde_json::Value) -> CompareRequest {
+        let mut body = json!({
+            "query": "level:ERROR",
+            "time_range_type": "absolute",
+            "start_time": "2026-10-18T10:00:00Z",
+            "end_time": "2026-10-18T11:00:00Z"
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn hit(message: &str) -> LogHit {
+        serde_json::from_value(json!({
+            "timestamp": "2026-10-18T10:00:00Z",
+            "message": message,
+            "level": "ERROR",
+            "service": "order",
+            "env": "prod"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn baseline_window_defaults_to_the_previous_window() {
+        let current = (utc("2026-10-18T10:00:00Z"), utc("2026-10-18T11:00:00Z"));
+
+        let baseline = baseline_window(&request(json!({})), current).unwrap();
+        assert_eq!(
+            baseline,
+            (utc("2026-10-18T09:00:00Z"), utc("2026-10-18T10:00:00Z"))
+        );
+
+        let baseline =
+            baseline_window(&request(json!({"baseline_offset": "1d"})), current).unwrap();
+        assert_eq!(
+            baseline,
+            (utc("2026-10-17T10:00:00Z"), utc("2026-10-17T11:00:00Z"))
+        );
+
+        // 显式指定的基线窗口优先于偏移
+        let explicit = request(json!({
+            "baseline_offset": "1d",
+            "baseline_start_time": "2026-10-11T08:00:00Z",
+            "baseline_end_time": "2026-10-11T10:00:00Z"
+        }));
+        assert_eq!(
+            baseline_window(&explicit, current).unwrap(),
+            (utc("2026-10-11T08:00:00Z"), utc("2026-10-11T10:00:00Z"))
+        );
+
+        let reversed = request(json!({
+            "baseline_start_time": "2026-10-11T10:00:00Z",
+            "baseline_end_time": "2026-10-11T08:00:00Z"
+        }));
+        assert!(baseline_window(&reversed, current).is_err());
+    }
+
+    #[test]
+    fn change_ratio_compares_rates_of_unequal_windows() {
+        let current = (utc("2026-10-18T10:00:00Z"), utc("2026-10-18T11:00:00Z"));
+        let baseline = (utc("2026-10-18T08:00:00Z"), utc("2026-10-18T10:00:00Z"));
+        let rate_ratio = window_seconds(baseline) / window_seconds(current);
+        assert_eq!(rate_ratio, 2.0);
+
+        let counts = |count| HashMap::from([("payment".to_string(), count)]);
+        // 1 小时 120 条对比 2 小时 120 条：速率翻倍
+        let entries = diff(&counts(120), &counts(120), &HashMap::new(), rate_ratio, 10);
+        assert_eq!(entries[0].delta, 0);
+        assert_eq!(entries[0].change_ratio, Some(1.0));
+
+        // 速率相同
+        let entries = diff(&counts(60), &counts(120), &HashMap::new(), rate_ratio, 10);
+        assert_eq!(entries[0].change_ratio, Some(0.0));
+    }
+
+    #[test]
+    fn patterns_are_estimated_from_weighted_samples() {
+        // 两段各采样 2 条，前一段代表 100 条匹配，后一段代表 10 条
+        let current = LogSample {
+            hits: vec![
+                hit("order 1 not found"),
+                hit("order 2 not found"),
+                hit("payment timeout after 3000 ms"),
+                hit("order 3 not found"),
+            ],
+            weights: vec![50.0, 50.0, 5.0, 5.0],
+            total: 110,
+        };
+        let baseline = LogSample {
+            hits: vec![hit("order 4 not found"), hit("cache miss for user 42")],
+            weights: vec![1.0, 1.0],
+            total: 2,
+        };
+
+        let (current_patterns, examples) = pattern_counts(&current);
+        let (baseline_patterns, _) = pattern_counts(&baseline);
+        let order = normalize_message("order 1 not found");
+        assert_eq!(current_patterns[&order], 105);
+        assert_eq!(examples[&order], "order 1 not found");
+
+        let entries = diff(&current_patterns, &baseline_patterns, &examples, 1.0, 10);
+        let keys: Vec<&str> = entries.iter().map(|entry| entry.key.as_str()).collect();
+        let timeout = normalize_message("payment timeout after 3000 ms");
+        let cache_miss = normalize_message("cache miss for user 42");
+        assert_eq!(
+            keys,
+            vec![timeout.as_str(), order.as_str(), cache_miss.as_str()]
+        );
+
+        // 仅当前窗口出现的模式在前，仅基线出现的模式计数为 0、变化为 -100%
+        assert!(entries[0].new);
+        assert_eq!(entries[0].current, 5);
+        assert_eq!(entries[1].change_ratio, Some(104.0));
+        assert!(!entries[2].new);
+        assert_eq!(entries[2].current, 0);
+        assert_eq!(entries[2].change_ratio, Some(-1.0));
+    }
+}
@@ -1,7 +1,85 @@
-use actix_web::{HttpResponse, Result};
+use crate::{error::AppError, AppState};
+use actix_web::{web, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Instant;
 
 pub async fn health_check() -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "status": "healthy"
     })))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ReadyQuery {
+    /// 是否同时探测 AI 服务（非关键依赖，不影响就绪状态）
+    #[serde(default)]
+    pub ai: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependencyStatus {
+    pub status: &'static str,
+    pub critical: bool,
+    pub latency_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 深度就绪检查：Quickwit 集群健康与索引存在性为关键依赖，任一失败返回 503
+pub async fn readiness_check(
+    state: web::Data<AppState>,
+    query: web::Query<ReadyQuery>,
+) -> Result<HttpResponse> {
+    let quickwit_client = state.quickwit();
+    let (quickwit, index) = futures_util::join!(
+        check(true, quickwit_client.check_health()),
+        check(true, quickwit_client.check_index()),
+    );
+
+    let mut checks = serde_json::Map::new();
+    let ready = quickwit.status == "up" && index.status == "up";
+    checks.insert("quickwit".to_string(), serde_json::json!(quickwit));
+    checks.insert("index".to_string(), serde_json::json!(index));
+
+    if query.ai {
+        let ai = check(false, state.ai_analyzer().probe()).await;
+        checks.insert("ai_analyzer".to_string(), serde_json::json!(ai));
+    }
+
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "checks": checks,
+    });
+
+    if ready {
+        Ok(HttpResponse::Ok().json(body))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(body))
+    }
+}
+
+async fn check(
+    critical: bool,
+    probe: impl Future<Output = Result<(), AppError>>,
+) -> DependencyStatus {
+    let start = Instant::now();
+    let result = probe.await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(()) => DependencyStatus {
+            status: "up",
+            critical,
+            latency_ms,
+            error: None,
+        },
+        Err(e) => DependencyStatus {
+            status: "down",
+            critical,
+            latency_ms,
+            error: Some(e.to_string()),
+        },
+    }
+}
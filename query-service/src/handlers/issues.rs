@@ -0,0 +1,56 @@
+use crate::{
+    error::AppError,
+    models::issues::{IssueStatusUpdate, IssuesQuery, IssuesResponse},
+    AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use log::info;
+
+pub async fn list_issues(
+    state: web::Data<AppState>,
+    query: web::Query<IssuesQuery>,
+) -> Result<HttpResponse, AppError> {
+    if query.limit < 1 || query.limit > 500 {
+        return Err(AppError::ValidationError(
+            "limit must be between 1 and 500".to_string(),
+        ));
+    }
+
+    let (issues, total) = state.issues.list(&query);
+    Ok(HttpResponse::Ok().json(IssuesResponse {
+        issues,
+        total,
+        scanned_until: state.issues.scanned_until(),
+    }))
+}
+
+pub async fn get_issue(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let issue = state
+        .issues
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound(format!("issue {} not found", id)))?;
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+/// 修改 issue 状态（open / resolved / ignored）
+pub async fn update_issue_status(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    req: web::Json<IssueStatusUpdate>,
+) -> Result<HttpResponse, AppError> {
+    let issue = state.issues.set_status(&id, req.status).await?;
+    info!("Issue {} marked as {:?}", issue.id, issue.status);
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+/// 立即扫描新的 ERROR 日志，不等待后台定时扫描
+pub async fn scan_issues(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    let summary = state
+        .issues
+        .scan(&state.quickwit(), &state.stack_trace_parser())
+        .await?;
+    Ok(HttpResponse::Ok().json(summary))
+}
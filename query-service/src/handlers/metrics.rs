@@ -0,0 +1,8 @@
+use crate::AppState;
+use actix_web::{web, HttpResponse, Result};
+
+pub async fn metrics(state: web::Data<AppState>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(state.metrics.render()))
+}
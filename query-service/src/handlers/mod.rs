@@ -1,3 +1,8 @@
 pub mod health;
 pub mod search;
 pub mod ai_analyzer;
+pub mod metrics;
+pub mod compare;
+pub mod patterns;
+pub mod issues;
+pub mod ai_sessions;
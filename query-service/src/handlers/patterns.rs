@@ -0,0 +1,108 @@
+use crate::{
+    error::AppError,
+    models::{
+        patterns::{LogPattern, PatternsRequest, PatternsResponse},
+        query::LogHit,
+    },
+    services::patterns::{Drain, DEFAULT_SIMILARITY},
+    AppState,
+};
+use actix_web::{web, HttpResponse, Result};
+use log::info;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// 采样日志数上限
+const MAX_SAMPLE_SIZE: usize = 10000;
+
+/// 采样匹配的日志（按时间分段共 sample_size 条）并聚类为消息模板
+pub async fn patterns(
+    state: web::Data<AppState>,
+    req: web::Json<PatternsRequest>,
+) -> Result<HttpResponse, AppError> {
+    req.search.validate().map_err(AppError::ValidationError)?;
+    if req.sample_size < 1 || req.sample_size > MAX_SAMPLE_SIZE {
+        return Err(AppError::ValidationError(format!(
+            "sample_size must be between 1 and {}",
+            MAX_SAMPLE_SIZE
+        )));
+    }
+    if req.max_patterns < 1 || req.max_patterns > 500 {
+        return Err(AppError::ValidationError(
+            "max_patterns must be between 1 and 500".to_string(),
+        ));
+    }
+    if req.max_examples > 20 {
+        return Err(AppError::ValidationError(
+            "max_examples must be at most 20".to_string(),
+        ));
+    }
+    let similarity = req.similarity.unwrap_or(DEFAULT_SIMILARITY);
+    if !(0.0..=1.0).contains(&similarity) {
+        return Err(AppError::ValidationError(
+            "similarity must be between 0 and 1".to_string(),
+        ));
+    }
+
+    let (start_time, end_time) = req
+        .search
+        .compute_time_range()
+        .map_err(AppError::ValidationError)?;
+    let tz = req.search.tz().map_err(AppError::ValidationError)?;
+
+    info!(
+        "Patterns request: query={}, start_time={}, end_time={}, sample_size={}",
+        req.search.query, start_time, end_time, req.sample_size
+    );
+
+    let start = Instant::now();
+
+    // 按时间分段采样日志
+    let sample = state
+        .quickwit()
+        .sample(&req.search, start_time, end_time, req.sample_size, tz)
+        .await?;
+    let hits = &sample.hits;
+
+    let mut drain = Drain::new(similarity);
+    for hit in hits {
+        drain.add(&hit.message);
+    }
+
+    let patterns: Vec<LogPattern> = drain
+        .into_clusters()
+        .into_iter()
+        .take(req.max_patterns)
+        .filter_map(|cluster| {
+            let members: Vec<&LogHit> = cluster.members.iter().map(|&i| &hits[i]).collect();
+            let first_seen = members.iter().map(|hit| hit.timestamp).min()?;
+            let last_seen = members.iter().map(|hit| hit.timestamp).max()?;
+
+            let mut levels = BTreeMap::new();
+            for hit in &members {
+                *levels.entry(hit.level.clone()).or_insert(0) += 1;
+            }
+
+            Some(LogPattern {
+                pattern: cluster.template,
+                count: members.len(),
+                estimated_count: sample.estimate(&cluster.members),
+                first_seen,
+                last_seen,
+                levels,
+                examples: members
+                    .into_iter()
+                    .take(req.max_examples)
+                    .cloned()
+                    .collect(),
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(PatternsResponse {
+        total: sample.total,
+        sampled: hits.len(),
+        patterns,
+        took_ms: start.elapsed().as_millis() as u64,
+    }))
+}
@@ -1,10 +1,146 @@
-use crate::{error::AppError, models::query::SearchRequest, AppState};
-use actix_web::{web, HttpResponse, Result};
+use crate::{
+    error::AppError,
+    models::{
+        msearch::{
+            MultiSearchItem, MultiSearchItemResponse, MultiSearchRequest, MultiSearchResponse,
+            MultiSearchResult,
+        },
+        query::{
+            AggregationRequest, AggregationResponse, CacheStatus, SearchRequest, SearchResponse,
+            SEARCH_FIELDS,
+        },
+    },
+    AppState,
+};
+use actix_web::{http::header, web, HttpResponse, HttpResponseBuilder, ResponseError, Result};
+use futures_util::{stream, StreamExt};
+use serde_json::Value;
+use std::future::Future;
+use std::time::Instant;
+
+/// 批量搜索单次最多包含的请求数
+const MAX_MSEARCH_REQUESTS: usize = 50;
+
+/// 批量搜索默认并发数与并发上限
+const DEFAULT_MSEARCH_CONCURRENCY: usize = 4;
+const MAX_MSEARCH_CONCURRENCY: usize = 16;
 
 pub async fn search(
     state: web::Data<AppState>,
     req: web::Json<SearchRequest>,
 ) -> Result<HttpResponse, AppError> {
+    let result = run_search(&state, &req).await?;
+    let mut response = HttpResponse::Ok();
+    insert_cache_headers(&mut response, result.cache);
+    Ok(response.json(result))
+}
+
+/// 写入缓存相关的响应头：`X-Cache` 表示命中情况，`Cache-Control` 为结果剩余的有效期
+fn insert_cache_headers(response: &mut HttpResponseBuilder, cache: CacheStatus) {
+    let (status, ttl_secs) = match cache {
+        CacheStatus::Disabled => return,
+        CacheStatus::Hit { age_secs, ttl_secs } => {
+            response.insert_header((header::AGE, age_secs.to_string()));
+            ("HIT", ttl_secs)
+        }
+        CacheStatus::Miss { ttl_secs } => ("MISS", ttl_secs),
+        CacheStatus::Bypass { ttl_secs } => ("BYPASS", ttl_secs),
+    };
+    response.insert_header(("X-Cache", status));
+    response.insert_header((
+        header::CACHE_CONTROL,
+        format!("private, max-age={}", ttl_secs),
+    ));
+}
+
+/// 批量执行搜索与聚合请求，单个请求失败不影响其他请求
+pub async fn msearch(
+    state: web::Data<AppState>,
+    req: web::Json<MultiSearchRequest>,
+) -> Result<HttpResponse, AppError> {
+    let MultiSearchRequest {
+        requests,
+        max_concurrency,
+    } = req.into_inner();
+
+    check_batch_size(requests.len())?;
+    let concurrency = max_concurrency
+        .unwrap_or(DEFAULT_MSEARCH_CONCURRENCY)
+        .clamp(1, MAX_MSEARCH_CONCURRENCY);
+
+    log::info!(
+        "Multi-search request: {} items, concurrency={}",
+        requests.len(),
+        concurrency
+    );
+
+    let start = Instant::now();
+    let state = &state;
+    let responses = run_batch(requests, concurrency, |item| async move {
+        match item {
+            MultiSearchItem::Search(req) => {
+                run_search(state, &req).await.map(MultiSearchResult::Search)
+            }
+            MultiSearchItem::Aggregation(req) => run_aggregation(state, &req)
+                .await
+                .map(MultiSearchResult::Aggregation),
+        }
+    })
+    .await;
+
+    Ok(HttpResponse::Ok().json(MultiSearchResponse {
+        responses,
+        took_ms: start.elapsed().as_millis() as u64,
+    }))
+}
+
+fn check_batch_size(len: usize) -> Result<(), AppError> {
+    if len == 0 || len > MAX_MSEARCH_REQUESTS {
+        return Err(AppError::ValidationError(format!(
+            "requests must contain between 1 and {} items",
+            MAX_MSEARCH_REQUESTS
+        )));
+    }
+    Ok(())
+}
+
+/// 逐个解析并执行批量请求，结果按请求顺序返回；格式错误或执行失败只影响该请求的结果
+async fn run_batch<F, Fut>(
+    requests: Vec<Value>,
+    concurrency: usize,
+    run: F,
+) -> Vec<MultiSearchItemResponse>
+where
+    F: Fn(MultiSearchItem) -> Fut,
+    Fut: Future<Output = Result<MultiSearchResult, AppError>>,
+{
+    let run = &run;
+    stream::iter(requests)
+        .map(|item| async move {
+            let result = match serde_json::from_value::<MultiSearchItem>(item) {
+                Ok(item) => run(item).await,
+                Err(e) => Err(AppError::ValidationError(format!("invalid request: {}", e))),
+            };
+
+            match result {
+                Ok(result) => MultiSearchItemResponse {
+                    status: 200,
+                    result: Some(result),
+                    error: None,
+                },
+                Err(e) => MultiSearchItemResponse {
+                    status: e.status_code().as_u16(),
+                    result: None,
+                    error: Some(e.message().to_string()),
+                },
+            }
+        })
+        .buffered(concurrency)
+        .collect()
+        .await
+}
+
+async fn run_search(state: &AppState, req: &SearchRequest) -> Result<SearchResponse, AppError> {
     // 参数验证
     req.validate().map_err(AppError::ValidationError)?;
 
@@ -12,6 +148,7 @@ pub async fn search(
     let (start_time, end_time) = req
         .compute_time_range()
         .map_err(AppError::ValidationError)?;
+    let tz = req.tz().map_err(AppError::ValidationError)?;
 
     // 记录日志
     log::info!(
@@ -25,29 +162,120 @@ pub async fn search(
     );
 
     // 执行搜索
-    let result = state.quickwit.search(&req, start_time, end_time).await?;
+    let mut response = state
+        .quickwit()
+        .search(req, start_time, end_time)
+        .await?
+        .with_time_zone(tz);
+
+    if req.frames {
+        let parser = state.stack_trace_parser();
+        for hit in &mut response.hits {
+            hit.frames = hit
+                .stack_trace
+                .as_deref()
+                .map(|stack_trace| parser.parse(stack_trace).frames);
+        }
+    }
+
+    Ok(response)
+}
+
+async fn run_aggregation(
+    state: &AppState,
+    req: &AggregationRequest,
+) -> Result<AggregationResponse, AppError> {
+    req.validate().map_err(AppError::ValidationError)?;
+
+    let (start_time, end_time) = req
+        .search
+        .compute_time_range()
+        .map_err(AppError::ValidationError)?;
 
-    Ok(HttpResponse::Ok().json(result))
+    state.quickwit().aggregate(req, start_time, end_time).await
 }
 
 pub async fn get_fields() -> Result<HttpResponse> {
-    let fields = vec![
-        "timestamp",
-        "message",
-        "level",
-        "service",
-        "host",
-        "env",
-        "trace_id",
-        "span_id",
-        "source_file",
-        "line_number",
-    ];
-
-    Ok(HttpResponse::Ok().json(serde_json::json!({ "fields": fields })))
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "fields": SEARCH_FIELDS })))
 }
 
 pub async fn list_services(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
-    let services = state.quickwit.list_services().await?;
+    let services = state.quickwit().list_services().await?;
     Ok(HttpResponse::Ok().json(serde_json::json!({ "services": services })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn search(query: &str) -> Value {
+        json!({ "type": "search", "query": query })
+    }
+
+    #[tokio::test]
+    async fn failed_items_do_not_affect_the_others() {
+        let requests = vec![
+            search("level:ERROR"),
+            json!({ "type": "search", "page": "first" }),
+            search("fail"),
+            json!({ "type": "aggregation", "field": "service", "query": "*" }),
+        ];
+        let responses = run_batch(requests, 2, |item| async move {
+            match item {
+                MultiSearchItem::Search(req) if req.query == "fail" => {
+                    Err(AppError::BadQuery("syntax error".to_string()))
+                }
+                MultiSearchItem::Search(req) => Ok(MultiSearchResult::Search(SearchResponse {
+                    total: 1,
+                    hits: Vec::new(),
+                    page: req.page,
+                    page_size: req.page_size,
+                    took_ms: 1,
+                    cache: CacheStatus::Disabled,
+                })),
+                MultiSearchItem::Aggregation(_) => {
+                    Ok(MultiSearchResult::Aggregation(AggregationResponse {
+                        total: 2,
+                        buckets: Vec::new(),
+                        took_ms: 1,
+                        cache: CacheStatus::Disabled,
+                    }))
+                }
+            }
+        })
+        .await;
+
+        let statuses: Vec<u16> = responses.iter().map(|response| response.status).collect();
+        assert_eq!(statuses, vec![200, 400, 400, 200]);
+        assert!(matches!(
+            responses[0].result,
+            Some(MultiSearchResult::Search(_))
+        ));
+        assert!(responses[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("invalid request"));
+        assert_eq!(responses[2].error.as_deref(), Some("syntax error"));
+        assert!(responses[2].result.is_none());
+        assert!(matches!(
+            responses[3].result,
+            Some(MultiSearchResult::Aggregation(_))
+        ));
+    }
+
+    #[test]
+    fn batch_size_is_limited() {
+        assert!(check_batch_size(1).is_ok());
+        assert!(check_batch_size(MAX_MSEARCH_REQUESTS).is_ok());
+        assert!(matches!(
+            check_batch_size(0),
+            Err(AppError::ValidationError(_))
+        ));
+        assert!(matches!(
+            check_batch_size(MAX_MSEARCH_REQUESTS + 1),
+            Err(AppError::ValidationError(_))
+        ));
+    }
+}
@@ -0,0 +1,10 @@
+pub mod config;
+pub mod error;
+pub mod handlers;
+pub mod metrics;
+pub mod models;
+pub mod reload;
+pub mod services;
+pub mod state;
+
+pub use state::AppState;
@@ -1,20 +1,13 @@
 use actix_web::{middleware, web, App, HttpServer};
-use log::info;
+use log::{error, info, warn};
 
-mod config;
-mod error;
-mod handlers;
-mod models;
-mod services;
-
-use config::Config;
-use services::{quickwit::QuickwitClient, ai_analyzer::AiAnalyzerClient};
-
-#[derive(Clone)]
-pub struct AppState {
-    pub quickwit: QuickwitClient,
-    pub ai_analyzer: AiAnalyzerClient,
-}
+use log_query_service::config::Config;
+use log_query_service::error::AppError;
+use log_query_service::metrics::{self, Metrics};
+use log_query_service::services::ai_cache::AiAnalysisCache;
+use log_query_service::services::ai_sessions::AiSessionStore;
+use log_query_service::services::issues::{spawn_issue_scanner, IssueTracker};
+use log_query_service::{handlers, reload, AppState};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -24,40 +17,74 @@ async fn main() -> std::io::Result<()> {
     // 加载 .env 文件（如果存在）
     dotenv::dotenv().ok();
 
-    // 加载配置
-    let config = Config::load().expect("Failed to load config");
-
-    // 调试：打印 AI 配置
-    info!("AI Analyzer Config:");
-    info!("  Base URL: {}", config.ai_analyzer.base_url);
-    info!("  Model: {}", config.ai_analyzer.model);
-    info!("  API Key exists: {}", config.ai_analyzer.api_key.is_some());
-    if let Some(key) = &config.ai_analyzer.api_key {
-        if key.is_empty() {
-            info!("  WARNING: API key is empty! Set APP__AI_ANALYZER__API_KEY environment variable");
-        } else {
-            info!("  API Key (last 4 chars): ****{}", &key[key.len().saturating_sub(4)..]);
+    // 加载并校验配置
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load config: {}", e);
+            std::process::exit(1);
         }
+    };
+    if let Err(e) = config.validate() {
+        error!("Invalid config: {}", e);
+        std::process::exit(1);
     }
 
-    // 创建 Quickwit 客户端
-    let quickwit_client = QuickwitClient::new(
-        config.quickwit.base_url.clone(),
-        config.quickwit.index_id.clone(),
-    );
+    config.log_ai_config();
+
+    // 创建 Prometheus 指标
+    let metrics = Metrics::new();
+
+    // 加载已有的 issue 数据
+    let issues = match IssueTracker::open(&config.issues) {
+        Ok(issues) => issues,
+        Err(e) => {
+            error!("Failed to open issue store: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // 加载已有的 AI 分析会话
+    let ai_sessions = match AiSessionStore::open(&config.ai_sessions) {
+        Ok(ai_sessions) => ai_sessions,
+        Err(e) => {
+            error!("Failed to open AI session store: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // 创建 AI 分析器客户端
-    let ai_analyzer_client = AiAnalyzerClient::new(
-        config.ai_analyzer.base_url.clone(),
-        config.ai_analyzer.api_key.clone(),
-        config.ai_analyzer.model.clone(),
-    );
+    // 加载已缓存的 AI 分析结果
+    let ai_cache = match AiAnalysisCache::open(&config.ai_cache, metrics.clone()) {
+        Ok(ai_cache) => ai_cache,
+        Err(e) => {
+            error!("Failed to open AI cache: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let app_state = AppState {
-        quickwit: quickwit_client,
-        ai_analyzer: ai_analyzer_client,
+    let app_state = match AppState::new(&config, metrics, issues, ai_sessions, ai_cache) {
+        Ok(app_state) => app_state,
+        Err(e) => {
+            error!("Invalid config: {}", e);
+            std::process::exit(1);
+        }
     };
 
+    // 检查索引是否存在：索引明确不存在时拒绝启动，Quickwit 暂不可达时仅告警
+    match app_state.quickwit().check_index().await {
+        Ok(()) => info!("Quickwit index '{}' is available", config.quickwit.index_id),
+        Err(AppError::IndexNotFound(msg)) => {
+            error!("Invalid config: {}", msg);
+            std::process::exit(1);
+        }
+        Err(e) => warn!("Could not verify Quickwit index at startup: {}", e),
+    }
+
+    reload::spawn_config_watcher(app_state.clone(), config.clone());
+    if config.issues.enabled {
+        spawn_issue_scanner(app_state.clone());
+    }
+
     let bind_addr = format!("{}:{}", config.server.host, config.server.port);
     info!("Starting server on {}", bind_addr);
 
@@ -67,9 +94,22 @@ async fn main() -> std::io::Result<()> {
             // 中间件
             .wrap(middleware::Logger::default())
             .wrap(actix_cors::Cors::permissive())
+            .wrap(middleware::from_fn(metrics::track_requests))
             // 路由
             .route("/health", web::get().to(handlers::health::health_check))
+            .route("/ready", web::get().to(handlers::health::readiness_check))
+            .route("/metrics", web::get().to(handlers::metrics::metrics))
             .route("/api/v1/search", web::post().to(handlers::search::search))
+            .route("/api/v1/msearch", web::post().to(handlers::search::msearch))
+            .route("/api/v1/compare", web::post().to(handlers::compare::compare))
+            .route("/api/v1/patterns", web::post().to(handlers::patterns::patterns))
+            .route("/api/v1/issues", web::get().to(handlers::issues::list_issues))
+            .route("/api/v1/issues/scan", web::post().to(handlers::issues::scan_issues))
+            .route("/api/v1/issues/{id}", web::get().to(handlers::issues::get_issue))
+            .route(
+                "/api/v1/issues/{id}",
+                web::patch().to(handlers::issues::update_issue_status),
+            )
             .route(
                 "/api/v1/fields",
                 web::get().to(handlers::search::get_fields),
@@ -78,10 +118,54 @@ async fn main() -> std::io::Result<()> {
                 "/api/v1/services",
                 web::get().to(handlers::search::list_services),
             )
+            .route(
+                "/api/v1/ai/templates",
+                web::get().to(handlers::ai_analyzer::list_templates),
+            )
             .route(
                 "/api/v1/ai/analyze",
                 web::post().to(handlers::ai_analyzer::analyze_error),
             )
+            .route(
+                "/api/v1/ai/analyze/stream",
+                web::post().to(handlers::ai_analyzer::analyze_error_stream),
+            )
+            .route(
+                "/api/v1/ai/analyze/search",
+                web::post().to(handlers::ai_analyzer::analyze_search),
+            )
+            .route(
+                "/api/v1/ai/analyze/search/stream",
+                web::post().to(handlers::ai_analyzer::analyze_search_stream),
+            )
+            .route(
+                "/api/v1/ai/query",
+                web::post().to(handlers::ai_analyzer::translate_query),
+            )
+            .route(
+                "/api/v1/ai/agent",
+                web::post().to(handlers::ai_analyzer::investigate),
+            )
+            .route(
+                "/api/v1/ai/sessions",
+                web::get().to(handlers::ai_sessions::list_sessions),
+            )
+            .route(
+                "/api/v1/ai/sessions",
+                web::post().to(handlers::ai_sessions::create_session),
+            )
+            .route(
+                "/api/v1/ai/sessions/{id}",
+                web::get().to(handlers::ai_sessions::get_session),
+            )
+            .route(
+                "/api/v1/ai/sessions/{id}",
+                web::delete().to(handlers::ai_sessions::delete_session),
+            )
+            .route(
+                "/api/v1/ai/sessions/{id}/messages",
+                web::post().to(handlers::ai_sessions::add_message),
+            )
     })
     .bind(&bind_addr)?
     .run()
@@ -0,0 +1,300 @@
+use crate::AppState;
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::time::{Duration, Instant};
+
+/// HTTP / Quickwit 请求耗时分桶（秒）
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// AI 调用耗时分桶（秒），LLM 响应通常在数秒到数分钟
+const AI_LATENCY_BUCKETS: &[f64] = &[0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 180.0];
+
+/// Prometheus 指标集合，内部均为 Arc，可以廉价 clone
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    quickwit_request_duration: HistogramVec,
+    quickwit_errors: IntCounterVec,
+    quickwit_retries: IntCounterVec,
+    quickwit_rejected: IntCounterVec,
+    quickwit_circuit_open: IntGauge,
+    ai_request_duration: HistogramVec,
+    ai_tokens: IntCounterVec,
+    ai_failures: IntCounterVec,
+    hit_parse_failures: IntCounter,
+    cache_requests: IntCounterVec,
+    cache_entries: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("quicklog".to_string()), None)
+            .expect("Failed to create metrics registry");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("metric can be created");
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and status",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["method", "route", "status"],
+        )
+        .expect("metric can be created");
+
+        let quickwit_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "quickwit_request_duration_seconds",
+                "Quickwit call latency by operation",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["operation"],
+        )
+        .expect("metric can be created");
+
+        let quickwit_errors = IntCounterVec::new(
+            Opts::new("quickwit_errors_total", "Failed Quickwit calls by operation"),
+            &["operation"],
+        )
+        .expect("metric can be created");
+
+        let quickwit_retries = IntCounterVec::new(
+            Opts::new("quickwit_retries_total", "Retried Quickwit calls by operation"),
+            &["operation"],
+        )
+        .expect("metric can be created");
+
+        let quickwit_rejected = IntCounterVec::new(
+            Opts::new(
+                "quickwit_circuit_rejected_total",
+                "Quickwit calls rejected by the open circuit breaker",
+            ),
+            &["operation"],
+        )
+        .expect("metric can be created");
+
+        let quickwit_circuit_open = IntGauge::new(
+            "quickwit_circuit_open",
+            "Whether the Quickwit circuit breaker is open (1) or closed (0)",
+        )
+        .expect("metric can be created");
+
+        let ai_request_duration = HistogramVec::new(
+            HistogramOpts::new("ai_request_duration_seconds", "AI provider call latency")
+                .buckets(AI_LATENCY_BUCKETS.to_vec()),
+            &["model"],
+        )
+        .expect("metric can be created");
+
+        let ai_tokens = IntCounterVec::new(
+            Opts::new("ai_tokens_total", "Tokens reported by the AI provider"),
+            &["model", "kind"],
+        )
+        .expect("metric can be created");
+
+        let ai_failures = IntCounterVec::new(
+            Opts::new("ai_failures_total", "Failed AI provider calls"),
+            &["model"],
+        )
+        .expect("metric can be created");
+
+        let hit_parse_failures = IntCounter::new(
+            "search_hit_parse_failures_total",
+            "Quickwit hits that could not be parsed into LogHit",
+        )
+        .expect("metric can be created");
+
+        let cache_requests = IntCounterVec::new(
+            Opts::new("cache_requests_total", "Cache lookups by cache and result"),
+            &["cache", "result"],
+        )
+        .expect("metric can be created");
+
+        let cache_entries = IntGaugeVec::new(
+            Opts::new("cache_entries", "Number of entries in each cache"),
+            &["cache"],
+        )
+        .expect("metric can be created");
+
+        registry
+            .register(Box::new(http_requests.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(quickwit_request_duration.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(quickwit_errors.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(quickwit_retries.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(quickwit_rejected.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(quickwit_circuit_open.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(ai_request_duration.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(ai_tokens.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(ai_failures.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(hit_parse_failures.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(cache_requests.clone()))
+            .expect("metric can be registered");
+        registry
+            .register(Box::new(cache_entries.clone()))
+            .expect("metric can be registered");
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            quickwit_request_duration,
+            quickwit_errors,
+            quickwit_retries,
+            quickwit_rejected,
+            quickwit_circuit_open,
+            ai_request_duration,
+            ai_tokens,
+            ai_failures,
+            hit_parse_failures,
+            cache_requests,
+            cache_entries,
+        }
+    }
+
+    pub fn observe_http(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration
+            .with_label_values(&labels)
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_quickwit(&self, operation: &str, elapsed: Duration, success: bool) {
+        self.quickwit_request_duration
+            .with_label_values(&[operation])
+            .observe(elapsed.as_secs_f64());
+        if !success {
+            self.quickwit_errors.with_label_values(&[operation]).inc();
+        }
+    }
+
+    pub fn observe_quickwit_retry(&self, operation: &str) {
+        self.quickwit_retries.with_label_values(&[operation]).inc();
+    }
+
+    /// 熔断期间被直接拒绝的调用
+    pub fn observe_quickwit_rejected(&self, operation: &str) {
+        self.quickwit_rejected.with_label_values(&[operation]).inc();
+    }
+
+    pub fn set_quickwit_circuit_open(&self, open: bool) {
+        self.quickwit_circuit_open.set(open as i64);
+    }
+
+    pub fn observe_ai(&self, model: &str, elapsed: Duration, success: bool) {
+        self.ai_request_duration
+            .with_label_values(&[model])
+            .observe(elapsed.as_secs_f64());
+        if !success {
+            self.ai_failures.with_label_values(&[model]).inc();
+        }
+    }
+
+    pub fn add_ai_tokens(&self, model: &str, prompt_tokens: u64, completion_tokens: u64) {
+        self.ai_tokens
+            .with_label_values(&[model, "prompt"])
+            .inc_by(prompt_tokens);
+        self.ai_tokens
+            .with_label_values(&[model, "completion"])
+            .inc_by(completion_tokens);
+    }
+
+    pub fn add_hit_parse_failures(&self, count: u64) {
+        self.hit_parse_failures.inc_by(count);
+    }
+
+    /// 记录一次缓存查找，`result` 为 hit / miss / bypass。命中率 = hit / (hit + miss)
+    pub fn observe_cache(&self, cache: &str, result: &str) {
+        self.cache_requests.with_label_values(&[cache, result]).inc();
+    }
+
+    pub fn set_cache_entries(&self, cache: &str, entries: usize) {
+        self.cache_entries
+            .with_label_values(&[cache])
+            .set(entries as i64);
+    }
+
+    /// 以 Prometheus 文本格式导出所有指标
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        let encoder = TextEncoder::new();
+        if let Err(e) = encoder.encode(&self.registry.gather(), &mut buffer) {
+            log::error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 记录每个请求的路由、状态码与耗时。路由使用匹配模式而不是原始路径，避免标签基数膨胀
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let metrics = req
+        .app_data::<web::Data<AppState>>()
+        .map(|state| state.metrics.clone());
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let start = Instant::now();
+
+    let result = next.call(req).await;
+
+    if let Some(metrics) = metrics {
+        let status = match &result {
+            Ok(res) => res.status().as_u16(),
+            Err(e) => e.as_response_error().status_code().as_u16(),
+        };
+        metrics.observe_http(&method, &route, status, start.elapsed());
+    }
+
+    result
+}
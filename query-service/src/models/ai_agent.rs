@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// AI 调查：模型根据问题自行搜索日志，直到得出结论或用完步数、token 预算
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiAgentRequest {
+    pub question: String,
+
+    /// 从该 trace 开始调查
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+
+    /// IANA 时区，用于理解问题中的时间与展示日志时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+
+    /// 回答语言，如 zh、en，不指定时使用 ai_analyzer.default_language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// 调查中的一次工具调用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiAgentStep {
+    /// 工具名，如 search_logs
+    pub tool: String,
+
+    /// 调用参数（占位符已还原为原值）
+    pub arguments: serde_json::Value,
+
+    /// 结果概要，如匹配条数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+
+    /// 参数无效或查询失败时的错误，错误同样返回给模型
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    pub took_ms: u64,
+}
+
+/// 调查结束的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiAgentStopReason {
+    /// 模型给出了结论
+    Completed,
+    /// 用完了工具调用轮数
+    StepLimit,
+    /// 用完了 token 预算
+    TokenLimit,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AiAgentUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiAgentResponse {
+    pub analysis: String,
+
+    /// 按顺序执行的工具调用
+    pub steps: Vec<AiAgentStep>,
+
+    pub stop_reason: AiAgentStopReason,
+
+    /// 调用 AI 的次数
+    pub ai_calls: usize,
+
+    /// token 用量；服务商未返回用量时为估算值
+    pub usage: AiAgentUsage,
+}
@@ -0,0 +1,98 @@
+use super::query::{default_ai_sample_size, AiContextSummary, SearchRequest};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 创建会话：分析一个 trace 的错误或一次搜索的结果，`trace_id` 与 `search` 二选一
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiSessionCreateRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<SearchRequest>,
+
+    /// 分析搜索结果时的采样日志数
+    #[serde(default = "default_ai_sample_size")]
+    pub sample_size: usize,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// 追问
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiSessionMessageRequest {
+    pub question: String,
+}
+
+/// 会话中的一轮：第一轮为初始分析（没有 question），之后为追问与回答
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiSessionTurn {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub question: Option<String>,
+
+    pub answer: String,
+
+    pub created_at: DateTime<Utc>,
+}
+
+/// 会话详情，问答中的占位符已还原为原值
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiSessionResponse {
+    pub id: String,
+
+    pub title: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<SearchRequest>,
+
+    pub template: String,
+
+    /// 回答语言的名称
+    pub language: String,
+
+    /// 首轮放入 prompt 的日志及省略情况
+    pub context: AiContextSummary,
+
+    pub turns: Vec<AiSessionTurn>,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 会话列表中的一项
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiSessionSummary {
+    pub id: String,
+    pub title: String,
+
+    /// 问答轮数，包括初始分析
+    pub turns: usize,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiSessionListResponse {
+    /// 按最近更新时间倒序
+    pub sessions: Vec<AiSessionSummary>,
+
+    pub total: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AiSessionsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    50
+}
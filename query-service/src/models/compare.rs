@@ -0,0 +1,76 @@
+use super::query::SearchRequest;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 对比请求：`search` 中的查询与时间范围为当前窗口，基线窗口默认为紧邻的前一个等长窗口
+#[derive(Debug, Deserialize)]
+pub struct CompareRequest {
+    #[serde(flatten)]
+    pub search: SearchRequest,
+
+    /// 基线相对当前窗口的偏移，如 `1h`、`1d`、`1w`；默认为当前窗口长度
+    #[serde(default)]
+    pub baseline_offset: Option<String>,
+
+    /// 显式指定的基线窗口，优先于 baseline_offset
+    #[serde(default)]
+    pub baseline_start_time: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pub baseline_end_time: Option<DateTime<Utc>>,
+
+    /// 每个维度返回的条目数
+    #[serde(default = "default_compare_limit")]
+    pub limit: usize,
+
+    /// 每个窗口用于消息模式统计的采样日志数，按时间分段采样
+    #[serde(default = "default_sample_size")]
+    pub sample_size: usize,
+}
+
+fn default_compare_limit() -> usize {
+    20
+}
+
+fn default_sample_size() -> usize {
+    500
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareResponse {
+    pub current: CompareWindow,
+    pub baseline: CompareWindow,
+
+    /// 各维度（service / level / host / pattern）的变化，按增幅从大到小排序
+    pub dimensions: BTreeMap<String, Vec<CompareEntry>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareWindow {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub total: u64,
+
+    /// 用于消息模式统计的采样数
+    pub sampled: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareEntry {
+    pub key: String,
+    /// 窗口内的数量；模式维度为按采样权重估算的匹配数
+    pub current: u64,
+    pub baseline: u64,
+    pub delta: i64,
+
+    /// 按单位时间速率计算的相对变化（0.5 表示增长 50%），基线为 0 时为空
+    pub change_ratio: Option<f64>,
+
+    /// 仅在当前窗口出现
+    pub new: bool,
+
+    /// 模式维度的示例消息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+}
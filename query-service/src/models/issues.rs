@@ -0,0 +1,122 @@
+use super::query::LogHit;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueStatus {
+    Open,
+    Resolved,
+    Ignored,
+}
+
+/// 按错误指纹聚合的一组 ERROR 日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    /// 错误指纹
+    pub id: String,
+
+    pub title: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_type: Option<String>,
+
+    /// 最靠近出错位置的业务帧
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub culprit: Option<String>,
+
+    /// 参与指纹计算的帧
+    pub frames: Vec<String>,
+
+    pub status: IssueStatus,
+
+    /// 已解决的 issue 再次出现时为 true，修改状态后清除
+    pub regressed: bool,
+
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub count: u64,
+
+    /// 按 service/env 统计的出现次数
+    pub occurrences: Vec<IssueOccurrence>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<DateTime<Utc>>,
+
+    /// 最近一次回归的时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regressed_at: Option<DateTime<Utc>>,
+
+    pub regression_count: u32,
+
+    /// 最近一条日志
+    pub latest_event: LogHit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueOccurrence {
+    pub service: String,
+    pub env: String,
+    pub count: u64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssuesQuery {
+    #[serde(default)]
+    pub status: Option<IssueStatus>,
+
+    #[serde(default)]
+    pub service: Option<String>,
+
+    #[serde(default)]
+    pub env: Option<String>,
+
+    /// 只返回回归的 issue
+    #[serde(default)]
+    pub regressed: Option<bool>,
+
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    50
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssuesResponse {
+    /// 按最近出现时间倒序
+    pub issues: Vec<Issue>,
+
+    /// 满足过滤条件的 issue 总数
+    pub total: usize,
+
+    /// 已扫描到的时间点，此前的 ERROR 日志都已计入
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scanned_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueStatusUpdate {
+    pub status: IssueStatus,
+}
+
+/// 一次扫描的结果
+#[derive(Debug, Default, Serialize)]
+pub struct IssueScanSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<DateTime<Utc>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<DateTime<Utc>>,
+
+    /// 扫描到的 ERROR 日志数
+    pub events: usize,
+
+    pub new_issues: usize,
+
+    /// 本次扫描中回归的 issue id
+    pub regressions: Vec<String>,
+}
@@ -1 +1,8 @@
+pub mod ai_agent;
+pub mod ai_sessions;
+pub mod compare;
+pub mod issues;
+pub mod msearch;
+pub mod patterns;
 pub mod query;
+pub mod time_range;
@@ -0,0 +1,45 @@
+use super::query::{AggregationRequest, AggregationResponse, SearchRequest, SearchResponse};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct MultiSearchRequest {
+    /// 批量请求，结果按相同顺序返回。逐个解析为 `MultiSearchItem`，
+    /// 格式错误的请求只影响自身的结果
+    pub requests: Vec<serde_json::Value>,
+
+    /// 最大并发数，默认 4，上限 16
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MultiSearchItem {
+    Search(SearchRequest),
+    Aggregation(AggregationRequest),
+}
+
+#[derive(Debug, Serialize)]
+pub struct MultiSearchResponse {
+    pub responses: Vec<MultiSearchItemResponse>,
+    pub took_ms: u64,
+}
+
+/// 单个请求的结果：成功时带 result，失败时带 error，互不影响
+#[derive(Debug, Serialize)]
+pub struct MultiSearchItemResponse {
+    pub status: u16,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<MultiSearchResult>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum MultiSearchResult {
+    Search(SearchResponse),
+    Aggregation(AggregationResponse),
+}
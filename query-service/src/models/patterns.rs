@@ -0,0 +1,71 @@
+use super::query::{LogHit, SearchRequest};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+pub struct PatternsRequest {
+    /// 查询条件与时间范围（分页与排序参数会被忽略）
+    #[serde(flatten)]
+    pub search: SearchRequest,
+
+    /// 参与聚类的采样日志数
+    #[serde(default = "default_sample_size")]
+    pub sample_size: usize,
+
+    /// 返回的模式数量
+    #[serde(default = "default_max_patterns")]
+    pub max_patterns: usize,
+
+    /// 每个模式返回的示例日志数
+    #[serde(default = "default_max_examples")]
+    pub max_examples: usize,
+
+    /// 相似度阈值（0~1），越大模式划分越细
+    #[serde(default)]
+    pub similarity: Option<f64>,
+}
+
+fn default_sample_size() -> usize {
+    2000
+}
+
+fn default_max_patterns() -> usize {
+    50
+}
+
+fn default_max_examples() -> usize {
+    3
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatternsResponse {
+    /// 匹配的日志总数
+    pub total: u64,
+
+    /// 实际参与聚类的日志数
+    pub sampled: usize,
+
+    pub patterns: Vec<LogPattern>,
+    pub took_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogPattern {
+    /// 模板，变量部分以 `<NUM>`、`<UUID>`、`<IP>`、`<HEX>`、`<*>` 表示
+    pub pattern: String,
+
+    /// 采样中的数量
+    pub count: usize,
+
+    /// 按各时间段的采样比例估算的总数量
+    pub estimated_count: u64,
+
+    pub first_seen: DateTime<FixedOffset>,
+    pub last_seen: DateTime<FixedOffset>,
+
+    /// 各日志级别的数量
+    pub levels: BTreeMap<String, usize>,
+
+    pub examples: Vec<LogHit>,
+}
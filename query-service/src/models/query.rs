@@ -1,8 +1,12 @@
-use chrono::{DateTime, Utc};
+use super::time_range::{parse_time_zone, resolve_relative};
+use chrono::{DateTime, FixedOffset, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchRequest {
     /// 查询字符串（Lucene 语法）
     pub query: String,
@@ -15,10 +19,19 @@ pub struct SearchRequest {
     #[serde(default = "default_time_range_type")]
     pub time_range_type: String,
 
-    /// 相对时间 key（当 time_range_type 为 relative 时使用）
+    /// 相对时间起点（当 time_range_type 为 relative 时使用）：
+    /// 时长如 `15m`、`90m`、`2w`，或日期运算如 `now-1d/d`
     #[serde(default)]
     pub relative_time_key: Option<String>,
 
+    /// 相对时间终点，日期运算表达式如 `now/d`，默认为 `now`
+    #[serde(default)]
+    pub relative_end_key: Option<String>,
+
+    /// IANA 时区（如 Asia/Shanghai），决定日期运算中的天边界和响应中时间戳的渲染，默认 UTC
+    #[serde(default)]
+    pub time_zone: Option<String>,
+
     /// 绝对时间（当 time_range_type 为 absolute 时使用）
     #[serde(default)]
     pub start_time: Option<DateTime<Utc>>,
@@ -33,12 +46,81 @@ pub struct SearchRequest {
     #[serde(default = "default_page_size")]
     pub page_size: usize,
 
-    /// 排序
+    /// 排序（单字段，兼容旧版本；`sort` 非空时忽略）
     #[serde(default = "default_sort_by")]
     pub sort_by: String,
 
     #[serde(default = "default_sort_desc")]
     pub sort_desc: bool,
+
+    /// 多字段排序，按顺序依次比较，最多 2 个字段
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+
+    /// 是否返回 message / stack_trace 中匹配词的高亮位置与片段
+    #[serde(default)]
+    pub highlight: bool,
+
+    /// 是否把 stack_trace 解析为结构化的调用帧
+    #[serde(default)]
+    pub frames: bool,
+
+    /// 跳过查询结果缓存，直接查询 Quickwit（结果仍会写入缓存）
+    #[serde(default)]
+    pub no_cache: bool,
+}
+
+/// 索引中可查询的字段
+pub const SEARCH_FIELDS: &[&str] = &[
+    "timestamp",
+    "message",
+    "level",
+    "service",
+    "host",
+    "env",
+    "trace_id",
+    "span_id",
+    "source_file",
+    "line_number",
+];
+
+/// 索引中的 fast field，可用于排序和聚合
+pub const FAST_FIELDS: &[&str] = &[
+    "timestamp",
+    "level",
+    "service",
+    "host",
+    "env",
+    "trace_id",
+    "line_number",
+];
+
+/// Quickwit 单次查询最多支持的排序字段数
+pub const MAX_SORT_KEYS: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    pub field: String,
+
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortKey {
+    pub fn new(field: &str, order: SortOrder) -> Self {
+        Self {
+            field: field.to_string(),
+            order,
+        }
+    }
 }
 
 fn default_time_range_type() -> String {
@@ -67,6 +149,26 @@ impl SearchRequest {
             return Err("page_size must be between 1 and 1000".to_string());
         }
 
+        // 验证排序字段
+        let sort_keys = self.requested_sort_keys();
+        if sort_keys.len() > MAX_SORT_KEYS {
+            return Err(format!("at most {} sort keys are supported", MAX_SORT_KEYS));
+        }
+        for (i, key) in sort_keys.iter().enumerate() {
+            if !FAST_FIELDS.contains(&key.field.as_str()) {
+                return Err(format!(
+                    "cannot sort by '{}': sortable fields are {}",
+                    key.field,
+                    FAST_FIELDS.join(", ")
+                ));
+            }
+            if sort_keys[..i].iter().any(|k| k.field == key.field) {
+                return Err(format!("duplicate sort field: {}", key.field));
+            }
+        }
+
+        let tz = parse_time_zone(self.time_zone.as_deref())?;
+
         // 验证时间范围
         match self.time_range_type.as_str() {
             "relative" => {
@@ -78,14 +180,17 @@ impl SearchRequest {
                             .to_string(),
                     );
                 }
-                // 验证 relative_time_key 是否合法
-                match self.relative_time_key.as_ref().unwrap().as_str() {
-                    "1m" | "5m" | "15m" | "1h" | "4h" | "1d" | "7d" | "30d" => Ok(()),
-                    _ => Err(format!(
-                        "invalid relative_time_key: {}",
-                        self.relative_time_key.as_ref().unwrap()
-                    )),
-                }?;
+                // 验证相对时间表达式是否合法
+                let (start, end) = self.resolve_relative_range(Utc::now(), tz)?;
+                if start >= end {
+                    return Err(format!(
+                        "relative time range is empty: {} resolves to {}, {} resolves to {}",
+                        self.relative_time_key.as_ref().unwrap(),
+                        start,
+                        self.relative_end_key.as_deref().unwrap_or("now"),
+                        end
+                    ));
+                }
             }
             "absolute" => {
                 if self.start_time.is_none() || self.end_time.is_none() {
@@ -110,25 +215,8 @@ impl SearchRequest {
     pub fn compute_time_range(&self) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
         match self.time_range_type.as_str() {
             "relative" => {
-                let key = self
-                    .relative_time_key
-                    .as_ref()
-                    .ok_or("missing relative_time_key")?;
-                let now = Utc::now();
-
-                let start_time = match key.as_str() {
-                    "1m" => now - chrono::Duration::minutes(1),
-                    "5m" => now - chrono::Duration::minutes(5),
-                    "15m" => now - chrono::Duration::minutes(15),
-                    "1h" => now - chrono::Duration::hours(1),
-                    "4h" => now - chrono::Duration::hours(4),
-                    "1d" => now - chrono::Duration::days(1),
-                    "7d" => now - chrono::Duration::days(7),
-                    "30d" => now - chrono::Duration::days(30),
-                    _ => return Err(format!("unknown relative_time_key: {}", key)),
-                };
-
-                Ok((start_time, now))
+                let tz = parse_time_zone(self.time_zone.as_deref())?;
+                self.resolve_relative_range(Utc::now(), tz)
             }
             "absolute" => {
                 let start = self.start_time.ok_or("missing start_time")?;
@@ -138,20 +226,178 @@ impl SearchRequest {
             _ => Err(format!("unknown time_range_type: {}", self.time_range_type)),
         }
     }
+
+    /// 实际使用的排序字段：请求指定的字段，未包含 timestamp 且未达上限时追加 timestamp 作为次级排序
+    pub fn sort_keys(&self) -> Vec<SortKey> {
+        let mut keys = self.requested_sort_keys();
+        if keys.len() < MAX_SORT_KEYS && !keys.iter().any(|k| k.field == "timestamp") {
+            keys.push(SortKey::new("timestamp", SortOrder::Desc));
+        }
+        keys
+    }
+
+    fn requested_sort_keys(&self) -> Vec<SortKey> {
+        if !self.sort.is_empty() {
+            return self.sort.clone();
+        }
+        let order = if self.sort_desc {
+            SortOrder::Desc
+        } else {
+            SortOrder::Asc
+        };
+        vec![SortKey::new(&self.sort_by, order)]
+    }
+
+    /// 基于当前查询条件生成指定绝对时间窗口、按时间倒序的分页请求
+    pub fn for_window(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        page: usize,
+        page_size: usize,
+    ) -> SearchRequest {
+        SearchRequest {
+            time_range_type: "absolute".to_string(),
+            relative_time_key: None,
+            relative_end_key: None,
+            start_time: Some(start_time),
+            end_time: Some(end_time),
+            page,
+            page_size,
+            sort_by: "timestamp".to_string(),
+            sort_desc: true,
+            sort: Vec::new(),
+            highlight: false,
+            frames: false,
+            ..self.clone()
+        }
+    }
+
+    /// 请求的时区，未指定时为 UTC
+    pub fn tz(&self) -> Result<Tz, String> {
+        parse_time_zone(self.time_zone.as_deref())
+    }
+
+    fn resolve_relative_range(
+        &self,
+        now: DateTime<Utc>,
+        tz: Tz,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+        let key = self
+            .relative_time_key
+            .as_ref()
+            .ok_or("missing relative_time_key")?;
+        let start = resolve_relative(key, now, tz)?;
+        let end = match self.relative_end_key.as_deref() {
+            Some(end_key) if !end_key.trim().is_empty() => resolve_relative(end_key, now, tz)?,
+            _ => now,
+        };
+        Ok((start, end))
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResponse {
     pub total: u64,
     pub hits: Vec<LogHit>,
     pub page: usize,
     pub page_size: usize,
     pub took_ms: u64,
+
+    /// 查询缓存状态，由 handler 写入响应头
+    #[serde(skip)]
+    pub cache: CacheStatus,
+}
+
+/// 查询结果缓存状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// 未启用缓存或该查询不缓存
+    #[default]
+    Disabled,
+    Hit {
+        age_secs: u64,
+        ttl_secs: u64,
+    },
+    Miss {
+        ttl_secs: u64,
+    },
+    /// 请求指定了 no_cache
+    Bypass {
+        ttl_secs: u64,
+    },
+}
+
+impl SearchResponse {
+    /// 将命中日志的时间戳转换到指定时区渲染
+    pub fn with_time_zone(mut self, tz: Tz) -> Self {
+        for hit in &mut self.hits {
+            hit.timestamp = hit.timestamp.with_timezone(&tz).fixed_offset();
+        }
+        self
+    }
+}
+
+/// 词项聚合请求：按 `field` 统计匹配日志数量（分页与排序参数会被忽略）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AggregationRequest {
+    #[serde(flatten)]
+    pub search: SearchRequest,
+
+    /// 聚合字段，必须是 fast field
+    pub field: String,
+
+    /// 返回的桶数量
+    #[serde(default = "default_aggregation_size")]
+    pub size: usize,
+}
+
+fn default_aggregation_size() -> usize {
+    10
+}
+
+impl AggregationRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        self.search.validate()?;
+        if !FAST_FIELDS.contains(&self.field.as_str()) || self.field == "timestamp" {
+            return Err(format!(
+                "cannot aggregate on '{}': use one of {}",
+                self.field,
+                FAST_FIELDS
+                    .iter()
+                    .filter(|f| **f != "timestamp")
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if self.size < 1 || self.size > 1000 {
+            return Err("size must be between 1 and 1000".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct AggregationResponse {
+    /// 匹配的日志总数
+    pub total: u64,
+    pub buckets: Vec<TermBucket>,
+    pub took_ms: u64,
+
+    #[serde(skip)]
+    pub cache: CacheStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermBucket {
+    pub key: serde_json::Value,
+    pub doc_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogHit {
-    pub timestamp: DateTime<Utc>,
+    pub timestamp: DateTime<FixedOffset>,
     pub message: String,
     pub level: String,
     pub service: String,
@@ -172,16 +418,323 @@ pub struct LogHit {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub labels: Option<serde_json::Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_number: Option<u64>,
+
+    /// 匹配词高亮，key 为字段名（仅在请求 highlight 时返回）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<HashMap<String, FieldHighlight>>,
+
+    /// 从 stack_trace 解析出的调用帧，出错位置在前（仅在请求 frames 时返回）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frames: Option<Vec<StackFrame>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldHighlight {
+    /// 包含匹配词的片段，匹配词以 `<b>` 标签包裹，其余内容已做 HTML 转义
+    pub snippets: Vec<String>,
+
+    /// 匹配词在完整字段值中的位置（字符偏移，左闭右开）
+    pub ranges: Vec<HighlightRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 堆栈中的一帧
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackFrame {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+
+    /// Java 类名、Go 包路径、Rust 模块路径或 Python 模块名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+
+    /// 是否为业务代码（非框架、标准库或第三方依赖）
+    pub in_app: bool,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function.as_deref().unwrap_or("<unknown>"))?;
+        if let Some(file) = &self.file {
+            write!(f, " ({}", file)?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl LogHit {
+    /// 按单个可排序字段比较
+    pub fn cmp_field(&self, other: &Self, field: &str) -> Ordering {
+        match field {
+            "timestamp" => self.timestamp.cmp(&other.timestamp),
+            "level" => self.level.cmp(&other.level),
+            "service" => self.service.cmp(&other.service),
+            "host" => self.host.cmp(&other.host),
+            "env" => self.env.cmp(&other.env),
+            "trace_id" => self.trace_id.cmp(&other.trace_id),
+            "line_number" => self.line_number.cmp(&other.line_number),
+            _ => Ordering::Equal,
+        }
+    }
+
+    /// 按排序字段依次比较，全部相同时以 `cmp_chronological` 兜底，保证顺序确定
+    pub fn cmp_by_keys(&self, other: &Self, keys: &[SortKey]) -> Ordering {
+        keys.iter()
+            .map(|key| {
+                let ordering = self.cmp_field(other, &key.field);
+                match key.order {
+                    SortOrder::Asc => ordering,
+                    SortOrder::Desc => ordering.reverse(),
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| self.cmp_chronological(other))
+    }
+
+    /// 按时间先后比较；时间戳相同时依次比较 service、host、trace_id、span_id、message，
+    /// 保证排序结果稳定
+    pub fn cmp_chronological(&self, other: &Self) -> Ordering {
+        self.timestamp
+            .cmp(&other.timestamp)
+            .then_with(|| self.service.cmp(&other.service))
+            .then_with(|| self.host.cmp(&other.host))
+            .then_with(|| self.trace_id.cmp(&other.trace_id))
+            .then_with(|| self.span_id.cmp(&other.span_id))
+            .then_with(|| self.message.cmp(&other.message))
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AiAnalyzeRequest {
     pub trace_id: String,
+
+    /// 忽略缓存的分析结果，重新调用 AI
+    #[serde(default)]
+    pub force_refresh: bool,
+
+    /// 提示词模板名，不指定时使用 ai_analyzer.default_template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    /// 回答语言，如 zh、en，不指定时使用 ai_analyzer.default_language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AiAnalyzeResponse {
     pub analysis: String,
     pub trace_id: String,
+
+    /// 放入 prompt 的日志及省略情况；没有错误日志时不返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<AiContextSummary>,
+
+    /// 结果来自缓存时为当时分析完成的时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_at: Option<DateTime<Utc>>,
+}
+
+/// 对任意搜索结果的 AI 分析：按时间分段采样日志，按消息模式归类后交给 AI 总结
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiSearchAnalyzeRequest {
+    /// 查询条件与时间范围（分页与排序参数会被忽略）
+    #[serde(flatten)]
+    pub search: SearchRequest,
+
+    /// 参与分析的采样日志数
+    #[serde(default = "default_ai_sample_size")]
+    pub sample_size: usize,
+
+    /// 忽略缓存的分析结果，重新调用 AI
+    #[serde(default)]
+    pub force_refresh: bool,
+
+    /// 提示词模板名，不指定时使用 ai_analyzer.default_template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    /// 回答语言，如 zh、en，不指定时使用 ai_analyzer.default_language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+pub(super) fn default_ai_sample_size() -> usize {
+    500
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiSearchAnalyzeResponse {
+    pub analysis: String,
+
+    /// 匹配的日志总数
+    pub total: u64,
+
+    /// 参与分析的采样日志数
+    pub sampled: usize,
+
+    /// 采样日志归纳出的消息模式数
+    pub patterns: usize,
+
+    /// 放入 prompt 的模式及省略情况；没有匹配的日志时不返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<AiContextSummary>,
+
+    /// 结果来自缓存时为当时分析完成的时间
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_at: Option<DateTime<Utc>>,
+}
+
+/// 自然语言转换为搜索条件
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiQueryRequest {
+    /// 问题，如 "payment timeouts in prod since 10am"
+    pub question: String,
+
+    /// IANA 时区，决定 "10 点"、"今天" 等时间的含义，默认 UTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+
+    /// 说明文字的语言，不指定时使用 ai_analyzer.default_language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// 生成的搜索条件，由用户确认或修改后再执行
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiQueryResponse {
+    /// 已校验的搜索请求，可直接提交给 `/api/v1/search`
+    pub search: SearchRequest,
+
+    /// 对查询条件的说明
+    pub explanation: String,
+
+    /// 需要用户确认的地方，如未知的服务名或字段
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// AI 分析的上下文统计：受 token 预算限制，重复日志合并计数，超出预算的内容被截断或省略
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AiContextSummary {
+    /// 参与分析的日志条数
+    pub total_logs: usize,
+
+    /// 去重后的错误数（分析搜索结果时为消息模式数）
+    pub distinct_errors: usize,
+
+    /// 放入 prompt 的错误数
+    pub included_errors: usize,
+
+    /// 合并为计数的重复日志条数
+    pub duplicate_logs: usize,
+
+    /// 因预算不足未放入的错误数
+    pub omitted_errors: usize,
+
+    /// 内容被截断的日志条数
+    pub truncated_logs: usize,
+
+    /// 因预算不足未放入的堆栈数
+    pub omitted_stack_traces: usize,
+
+    /// 日志部分的估算 token 数
+    pub estimated_tokens: usize,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(sort: serde_json::Value) -> SearchRequest {
+        serde_json::from_value(json!({
+            "query": "*",
+            "time_range_type": "relative",
+            "relative_time_key": "15m",
+            "sort": sort,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn legacy_sort_fields_are_used_when_sort_is_empty() {
+        let req: SearchRequest = serde_json::from_value(json!({
+            "query": "*",
+            "relative_time_key": "15m",
+            "time_range_type": "relative",
+            "sort_by": "line_number",
+            "sort_desc": false,
+        }))
+        .unwrap();
+
+        assert_eq!(
+            req.sort_keys(),
+            vec![
+                SortKey::new("line_number", SortOrder::Asc),
+                SortKey::new("timestamp", SortOrder::Desc),
+            ]
+        );
+    }
+
+    #[test]
+    fn timestamp_is_not_added_twice() {
+        let req = request(json!([
+            {"field": "level", "order": "asc"},
+            {"field": "timestamp", "order": "asc"},
+        ]));
+
+        assert!(req.validate().is_ok());
+        assert_eq!(
+            req.sort_keys(),
+            vec![
+                SortKey::new("level", SortOrder::Asc),
+                SortKey::new("timestamp", SortOrder::Asc),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_fields_that_are_not_fast() {
+        let req = request(json!([{"field": "message"}]));
+        assert!(req.validate().unwrap_err().contains("cannot sort by 'message'"));
+    }
+
+    #[test]
+    fn rejects_duplicate_and_too_many_keys() {
+        let req = request(json!([{"field": "level"}, {"field": "level"}]));
+        assert!(req.validate().unwrap_err().contains("duplicate"));
+
+        let req = request(json!([
+            {"field": "level"},
+            {"field": "service"},
+            {"field": "timestamp"},
+        ]));
+        assert!(req.validate().unwrap_err().contains("at most"));
+    }
+}
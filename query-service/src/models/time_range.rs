@@ -0,0 +1,223 @@
+use chrono::{
+    DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike,
+    Utc,
+};
+use chrono_tz::Tz;
+
+/// 日期运算支持的单位
+const UNITS: &str = "smhdwMy";
+
+/// 解析 IANA 时区名（如 Asia/Shanghai），未指定时使用 UTC
+pub fn parse_time_zone(name: Option<&str>) -> Result<Tz, String> {
+    match name {
+        None => Ok(Tz::UTC),
+        Some(name) if name.trim().is_empty() => Ok(Tz::UTC),
+        Some(name) => name
+            .trim()
+            .parse::<Tz>()
+            .map_err(|_| format!("invalid time_zone: {}", name)),
+    }
+}
+
+/// 解析 500ms / 30s / 90m / 3h / 1d / 2w 形式的时长
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in duration '{}' (use ms, s, m, h, d or w)", s))?;
+    let (amount, unit) = s.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+
+    let duration = match unit {
+        "ms" => Duration::try_milliseconds(amount),
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => {
+            return Err(format!(
+                "unknown unit '{}' in duration '{}' (use ms, s, m, h, d or w)",
+                unit, s
+            ))
+        }
+    };
+    duration.ok_or_else(|| format!("duration '{}' is out of range", s))
+}
+
+/// 解析相对时间表达式：
+/// - 时长（如 `90m`、`2w`）表示 `now` 之前的时长
+/// - 日期运算（如 `now-1d/d`、`now/w`、`now-6h`），`/单位` 表示向下取整到该单位的边界，
+///   日、周、月、年的边界按 `tz` 计算
+///
+/// 单位：s 秒、m 分、h 时、d 天、w 周、M 月、y 年
+pub fn resolve_relative(expr: &str, now: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>, String> {
+    let expr = expr.trim();
+    let Some(mut rest) = expr.strip_prefix("now") else {
+        return Ok(now - parse_duration(expr)?);
+    };
+
+    let invalid = |reason: &str| format!("invalid time expression '{}': {}", expr, reason);
+    let mut time = now.with_timezone(&tz);
+
+    while !rest.is_empty() {
+        let op = rest.chars().next().unwrap_or_default();
+        rest = &rest[op.len_utf8()..];
+        match op {
+            '+' | '-' => {
+                let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let amount: i64 = rest[..digits]
+                    .parse()
+                    .map_err(|_| invalid("expected a number after '+' or '-'"))?;
+                let unit = rest[digits..]
+                    .chars()
+                    .next()
+                    .ok_or_else(|| invalid("missing unit"))?;
+                rest = &rest[digits + unit.len_utf8()..];
+                if !UNITS.contains(unit) {
+                    return Err(invalid("unknown unit (use s, m, h, d, w, M or y)"));
+                }
+
+                let amount = if op == '-' { -amount } else { amount };
+                time = shift(time, amount, unit).ok_or_else(|| invalid("value out of range"))?;
+            }
+            '/' => {
+                let unit = rest.chars().next().ok_or_else(|| invalid("missing unit"))?;
+                rest = &rest[unit.len_utf8()..];
+                if !UNITS.contains(unit) {
+                    return Err(invalid("unknown rounding unit (use s, m, h, d, w, M or y)"));
+                }
+                time = round_down(time, unit).ok_or_else(|| invalid("value out of range"))?;
+            }
+            _ => return Err(invalid("expected '+', '-' or '/'")),
+        }
+    }
+
+    Ok(time.with_timezone(&Utc))
+}
+
+fn shift(time: DateTime<Tz>, amount: i64, unit: char) -> Option<DateTime<Tz>> {
+    let tz = time.timezone();
+    match unit {
+        // 秒、分、时按绝对时长计算
+        's' => time.checked_add_signed(Duration::try_seconds(amount)?),
+        'm' => time.checked_add_signed(Duration::try_minutes(amount)?),
+        'h' => time.checked_add_signed(Duration::try_hours(amount)?),
+        // 天及以上按日历计算，跨夏令时仍保持本地时间不变
+        'd' | 'w' => {
+            let days = if unit == 'w' { amount.checked_mul(7)? } else { amount };
+            let local = time.naive_local().checked_add_signed(Duration::try_days(days)?)?;
+            localize(tz, local)
+        }
+        'M' | 'y' => {
+            let months = if unit == 'y' { amount.checked_mul(12)? } else { amount };
+            let delta = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+            let local = if months >= 0 {
+                time.naive_local().checked_add_months(delta)?
+            } else {
+                time.naive_local().checked_sub_months(delta)?
+            };
+            localize(tz, local)
+        }
+        _ => None,
+    }
+}
+
+fn round_down(time: DateTime<Tz>, unit: char) -> Option<DateTime<Tz>> {
+    let tz = time.timezone();
+    let local = time.naive_local();
+    let date = local.date();
+    let midnight = |date: NaiveDate| localize(tz, date.and_time(NaiveTime::MIN));
+
+    match unit {
+        's' => localize(tz, date.and_hms_opt(local.hour(), local.minute(), local.second())?),
+        'm' => localize(tz, date.and_hms_opt(local.hour(), local.minute(), 0)?),
+        'h' => localize(tz, date.and_hms_opt(local.hour(), 0, 0)?),
+        'd' => midnight(date),
+        // 周从周一开始
+        'w' => midnight(date - Duration::days(date.weekday().num_days_from_monday() as i64)),
+        'M' => midnight(date.with_day(1)?),
+        'y' => midnight(NaiveDate::from_ymd_opt(date.year(), 1, 1)?),
+        _ => None,
+    }
+}
+
+/// 本地时间转换为带时区时间；夏令时导致的不存在时间向后取最近的有效时间
+fn localize(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn date_math_rounds_in_time_zone() {
+        // 上海时间 2026-10-18 10:30（周日）
+        let now = utc("2026-10-18T02:30:00Z");
+        let shanghai = Tz::Asia__Shanghai;
+
+        let resolve = |expr: &str| resolve_relative(expr, now, shanghai).unwrap();
+        assert_eq!(resolve("now-1d/d+10h"), utc("2026-10-17T02:00:00Z"));
+        assert_eq!(resolve("now/d"), utc("2026-10-17T16:00:00Z"));
+        assert_eq!(resolve("now/w"), utc("2026-10-11T16:00:00Z"));
+        assert_eq!(resolve("now-1M/M"), utc("2026-08-31T16:00:00Z"));
+        assert_eq!(resolve("90m"), utc("2026-10-18T01:00:00Z"));
+        assert_eq!(
+            resolve_relative("now/d", now, Tz::UTC).unwrap(),
+            utc("2026-10-18T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn date_math_across_dst_changes() {
+        let new_york = Tz::America__New_York;
+
+        // 2026-03-08 夏令时开始，当天只有 23 小时；纽约时间 14:00 (EDT)
+        let now = utc("2026-03-08T18:00:00Z");
+        let resolve = |expr: &str| resolve_relative(expr, now, new_york).unwrap();
+        assert_eq!(resolve("now/d"), utc("2026-03-08T05:00:00Z"));
+        // 小时按绝对时长计算：零点 (EST) 之后 3 小时为 04:00 (EDT)
+        assert_eq!(resolve("now/d+3h"), utc("2026-03-08T08:00:00Z"));
+        // 天按日历计算，本地时间保持 14:00，实际相隔 23 小时
+        assert_eq!(resolve("now-1d"), utc("2026-03-07T19:00:00Z"));
+
+        // 本地时间 02:30 不存在，取之后最近的有效时间 03:30 (EDT)
+        let now = utc("2026-03-09T06:30:00Z");
+        assert_eq!(
+            resolve_relative("now-1d", now, new_york).unwrap(),
+            utc("2026-03-08T07:30:00Z")
+        );
+
+        // 2026-11-01 夏令时结束，当天有 25 小时；纽约时间 12:00 (EST)
+        let now = utc("2026-11-01T17:00:00Z");
+        let resolve = |expr: &str| resolve_relative(expr, now, new_york).unwrap();
+        assert_eq!(resolve("now/d"), utc("2026-11-01T04:00:00Z"));
+        assert_eq!(resolve("now/d+1d"), utc("2026-11-02T05:00:00Z"));
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        let now = utc("2026-10-18T02:30:00Z");
+        for expr in ["now-1x", "now/q", "now-d", "now*2", "now/", "15x", "m", "now-1dd"] {
+            assert!(
+                resolve_relative(expr, now, Tz::UTC).is_err(),
+                "{} should be rejected",
+                expr
+            );
+        }
+        assert!(parse_duration("1y").is_err());
+        assert!(parse_time_zone(Some("Mars/Olympus")).is_err());
+        assert_eq!(parse_time_zone(None).unwrap(), Tz::UTC);
+    }
+}
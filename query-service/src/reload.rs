@@ -0,0 +1,88 @@
+use crate::config::{Config, CONFIG_FILE};
+use crate::AppState;
+use log::{error, info, warn};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// 配置文件变更检查间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 监听 SIGHUP 与配置文件变更，重新加载配置并原子替换 AppState 中的客户端。
+/// 正在处理的请求持有旧客户端的引用，不受影响
+pub fn spawn_config_watcher(state: AppState, initial: Config) {
+    actix_rt::spawn(async move {
+        let mut sighup = match signal(SignalKind::hangup()) {
+            Ok(sighup) => Some(sighup),
+            Err(e) => {
+                warn!("Failed to install SIGHUP handler: {}", e);
+                None
+            }
+        };
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        let mut last_modified = config_modified_at();
+        let mut current = initial;
+
+        loop {
+            let triggered_by_signal = tokio::select! {
+                Some(_) = async {
+                    match sighup.as_mut() {
+                        Some(sighup) => sighup.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => true,
+                _ = interval.tick() => false,
+            };
+
+            if triggered_by_signal {
+                info!("Received SIGHUP, reloading config");
+            } else {
+                let modified = config_modified_at();
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                info!("Config file changed, reloading config");
+            }
+
+            match reload(&state, &current).await {
+                Ok(config) => current = config,
+                Err(e) => error!("Config reload failed, keeping previous config: {}", e),
+            }
+        }
+    });
+}
+
+async fn reload(state: &AppState, current: &Config) -> Result<Config, String> {
+    let config = Config::load().map_err(|e| e.to_string())?;
+    config.validate()?;
+
+    if config.server.host != current.server.host || config.server.port != current.server.port {
+        warn!("server.host/server.port changes require a restart and were not applied");
+    }
+    if config.issues != current.issues {
+        warn!("issues config changes require a restart and were not applied");
+    }
+    if config.ai_cache != current.ai_cache {
+        warn!("ai_cache config changes require a restart and were not applied");
+    }
+
+    state.reload(&config, current).await?;
+    info!(
+        "Config reloaded: quickwit={} index={} ai_provider={} ai_model={}",
+        config.quickwit.base_url,
+        config.quickwit.index_id,
+        config.ai_analyzer.provider.as_str(),
+        config.ai_analyzer.model
+    );
+
+    Ok(config)
+}
+
+fn config_modified_at() -> Option<SystemTime> {
+    ["yaml", "yml", "toml", "json"]
+        .iter()
+        .map(|ext| format!("{}.{}", CONFIG_FILE, ext))
+        .find_map(|path| Path::new(&path).metadata().ok())
+        .and_then(|metadata| metadata.modified().ok())
+}
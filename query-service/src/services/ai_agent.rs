@@ -0,0 +1,1441 @@
+use crate::config::AiAgentConfig;
+use crate::error::AppError;
+use crate::models::ai_agent::{AiAgentResponse, AiAgentStep, AiAgentStopReason, AiAgentUsage};
+use crate::models::query::{AggregationRequest, LogHit, SearchRequest, FAST_FIELDS, SEARCH_FIELDS};
+use crate::services::ai_analyzer::AiAnalyzerClient;
+use crate::services::ai_provider::{ChatMessage, ChatResponse, ChatRole, ToolCall, ToolSpec};
+use crate::services::prompt_packer::TokenEstimator;
+use crate::services::prompt_template::PromptLocale;
+use crate::services::quickwit::QuickwitClient;
+use crate::services::redaction::{Redaction, Redactor};
+use crate::services::stack_trace::StackTraceParser;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use chrono_tz::Tz;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// 搜索类工具未指定时间范围时的起点
+const DEFAULT_SINCE: &str = "1h";
+
+/// get_trace 查找的时间窗口（小时）
+const TRACE_LOOKBACK_HOURS: i64 = 24;
+
+/// get_context 默认与最大的前后秒数
+const DEFAULT_CONTEXT_SECONDS: i64 = 60;
+const MAX_CONTEXT_SECONDS: i64 = 3600;
+
+/// top_values 默认与最多返回的取值数
+const DEFAULT_TOP_VALUES: usize = 10;
+const MAX_TOP_VALUES: usize = 50;
+
+/// 一轮中最多执行的工具调用，多出的调用返回错误
+const MAX_CALLS_PER_STEP: usize = 5;
+
+/// 工具结果中每条日志保留的堆栈帧数
+const STACK_FRAMES: usize = 3;
+
+const TEMPERATURE: f32 = 0.2;
+
+/// 调查：模型通过工具自行搜索日志，在步数与 token 预算内给出结论
+pub struct AiAgent {
+    config: AiAgentConfig,
+}
+
+/// 调查所需的客户端与上下文
+pub struct AgentContext<'a> {
+    pub analyzer: &'a AiAnalyzerClient,
+    pub quickwit: &'a QuickwitClient,
+    pub parser: &'a StackTraceParser,
+    pub redactor: &'a Redactor,
+    /// 最近 24 小时出现过的服务
+    pub services: &'a [String],
+    /// 当前时间，用于理解问题中的 "10 点以来" 等说法
+    pub now: DateTime<Tz>,
+    /// 请求中的时区，工具生成的搜索请求使用该时区
+    pub time_zone: Option<&'a str>,
+    /// 回答语言的名称
+    pub language: &'a str,
+}
+
+impl AgentContext<'_> {
+    /// 提示词与工具结果的语言，跟随回答语言
+    fn locale(&self) -> PromptLocale {
+        PromptLocale::for_language(self.language)
+    }
+}
+
+/// 工具的执行结果：返回给模型的文本与记录在步骤中的概要
+struct ToolOutput {
+    text: String,
+    summary: String,
+}
+
+/// 搜索类工具共用的时间范围参数
+#[derive(Debug, Default, Deserialize)]
+struct TimeRangeArgs {
+    #[serde(default)]
+    since: Option<String>,
+    #[serde(default)]
+    until: Option<String>,
+    #[serde(default)]
+    start_time: Option<String>,
+    #[serde(default)]
+    end_time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchLogsArgs {
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    filters: HashMap<String, String>,
+    #[serde(flatten)]
+    range: TimeRangeArgs,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetTraceArgs {
+    trace_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetContextArgs {
+    timestamp: DateTime<Utc>,
+    service: String,
+    #[serde(default)]
+    host: Option<String>,
+    #[serde(default)]
+    seconds: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopValuesArgs {
+    field: String,
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    filters: HashMap<String, String>,
+    #[serde(flatten)]
+    range: TimeRangeArgs,
+    #[serde(default)]
+    size: Option<usize>,
+}
+
+impl AiAgent {
+    pub fn new(config: &AiAgentConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// 执行调查。每轮把工具定义与历史消息发给模型，执行模型请求的工具调用后把结果加入对话，
+    /// 直到模型给出结论；用完步数或 token 预算时要求模型根据已有结果给出结论。
+    /// 每次调用前按估算的输入加上最大输出检查预算，超出预算的调用不会发出。
+    /// 问题与工具结果中的敏感信息脱敏后发送，工具参数与结论中的占位符还原为原值
+    pub async fn investigate(
+        &self,
+        ctx: &AgentContext<'_>,
+        question: &str,
+        trace_id: Option<&str>,
+    ) -> Result<AiAgentResponse, AppError> {
+        let locale = ctx.locale();
+        let estimator = ctx.analyzer.token_estimator();
+        let mut redaction = ctx.redactor.session();
+        let tools = self.tool_specs(locale);
+        let system = match locale {
+            PromptLocale::Zh => format!(
+                "你是资深的 SRE，通过调用工具查询日志来排查线上问题。查明原因后使用 {} 给出结论：问题现象、根本原因（引用查到的日志作为证据）、影响范围、处理建议。",
+                ctx.language
+            ),
+            PromptLocale::En => format!(
+                "You are a senior SRE investigating production issues by querying logs with tools. Once you have found the cause, answer in {} with: symptoms, root cause (cite the logs you found as evidence), impact, and recommended actions.",
+                ctx.language
+            ),
+        };
+        let prompt = self.build_prompt(ctx, &redaction.redact(question), trace_id);
+        let mut messages = vec![ChatMessage::user(prompt)];
+
+        let mut steps: Vec<AiAgentStep> = Vec::new();
+        let mut usage = AiAgentUsage::default();
+        let mut ai_calls = 0;
+        let mut conclusion = None;
+        let mut stop_reason = AiAgentStopReason::StepLimit;
+
+        for round in 1..=self.config.max_steps {
+            let prompt_tokens = estimate_prompt(&estimator, &system, &messages, &tools);
+            if !self.fits_budget(&usage, prompt_tokens) {
+                stop_reason = AiAgentStopReason::TokenLimit;
+                break;
+            }
+
+            let response = self
+                .call(ctx, &estimator, &system, &messages, &tools, &mut usage)
+                .await?;
+            ai_calls += 1;
+            if response.tool_calls.is_empty() {
+                conclusion = Some(response.content);
+                stop_reason = AiAgentStopReason::Completed;
+                break;
+            }
+
+            info!(
+                "AI agent round {}/{} requested {} tool calls",
+                round,
+                self.config.max_steps,
+                response.tool_calls.len()
+            );
+            messages.push(ChatMessage::assistant_tool_calls(
+                response.content,
+                response.tool_calls.clone(),
+            ));
+            for (index, call) in response.tool_calls.iter().enumerate() {
+                let (result, step) = if index < MAX_CALLS_PER_STEP {
+                    self.run_tool(ctx, &estimator, call, &mut redaction).await
+                } else {
+                    let error = format!(
+                        "at most {} tool calls are executed per step",
+                        MAX_CALLS_PER_STEP
+                    );
+                    (
+                        tool_error(locale, &error),
+                        AiAgentStep {
+                            tool: call.name.clone(),
+                            arguments: call.arguments.clone(),
+                            summary: None,
+                            error: Some(error),
+                            took_ms: 0,
+                        },
+                    )
+                };
+                messages.push(ChatMessage::tool_result(call.id.clone(), result));
+                steps.push(step);
+            }
+        }
+
+        // 用完步数或预算：保留工具定义（历史中有工具调用），要求模型直接给出结论
+        let conclusion = match conclusion {
+            Some(conclusion) => conclusion,
+            None => {
+                warn!(
+                    "AI agent stopped after {} tool calls: {:?}",
+                    steps.len(),
+                    stop_reason
+                );
+                messages.push(ChatMessage::user(match locale {
+                    PromptLocale::Zh => "调查次数已用完，请不要再调用工具，根据已有的查询结果给出结论，并说明还需要进一步确认的地方。",
+                    PromptLocale::En => "The investigation budget is used up. Do not call any more tools; give your conclusion based on the results so far and point out what still needs to be confirmed.",
+                }));
+                let content = if self.trim_to_budget(
+                    &estimator,
+                    &system,
+                    &mut messages,
+                    &tools,
+                    &usage,
+                    locale,
+                ) {
+                    let response = self
+                        .call(ctx, &estimator, &system, &messages, &tools, &mut usage)
+                        .await?;
+                    ai_calls += 1;
+                    response.content
+                } else {
+                    warn!("AI agent skipped the conclusion: token budget exhausted");
+                    String::new()
+                };
+                if content.trim().is_empty() {
+                    match locale {
+                        PromptLocale::Zh => "未能在调查次数内得出结论，请缩小问题范围后重试。",
+                        PromptLocale::En => "No conclusion was reached within the investigation budget. Please narrow down the question and try again.",
+                    }
+                    .to_string()
+                } else {
+                    content
+                }
+            }
+        };
+
+        info!(
+            "AI agent finished: {:?}, {} tool calls, {} AI calls, {} prompt + {} completion tokens",
+            stop_reason,
+            steps.len(),
+            ai_calls,
+            usage.prompt_tokens,
+            usage.completion_tokens
+        );
+
+        Ok(AiAgentResponse {
+            analysis: redaction.placeholders().rehydrate(&conclusion),
+            steps,
+            stop_reason,
+            ai_calls,
+            usage,
+        })
+    }
+
+    /// 按估算的输入加上最大输出计，下一次调用后是否仍在预算内
+    fn fits_budget(&self, usage: &AiAgentUsage, prompt_tokens: u64) -> bool {
+        usage.prompt_tokens
+            + usage.completion_tokens
+            + prompt_tokens
+            + u64::from(self.config.max_output_tokens)
+            <= self.config.max_tokens
+    }
+
+    /// 结论前的最后一次调用超出预算时，从最早的工具结果开始省略内容；
+    /// 全部省略后仍超出时返回 false，不再调用
+    fn trim_to_budget(
+        &self,
+        estimator: &TokenEstimator,
+        system: &str,
+        messages: &mut [ChatMessage],
+        tools: &[ToolSpec],
+        usage: &AiAgentUsage,
+        locale: PromptLocale,
+    ) -> bool {
+        let omitted_result = omitted_result(locale);
+        let mut omitted = 0;
+        loop {
+            if self.fits_budget(usage, estimate_prompt(estimator, system, messages, tools)) {
+                if omitted > 0 {
+                    info!(
+                        "AI agent omitted {} tool results to fit the token budget",
+                        omitted
+                    );
+                }
+                return true;
+            }
+            match messages
+                .iter_mut()
+                .find(|message| message.role == ChatRole::Tool && message.content != omitted_result)
+            {
+                Some(message) => {
+                    message.content = omitted_result.to_string();
+                    omitted += 1;
+                }
+                None => return false,
+            }
+        }
+    }
+
+    /// 调用一次模型并累计 token 用量；服务商未返回用量时按字符估算
+    async fn call(
+        &self,
+        ctx: &AgentContext<'_>,
+        estimator: &TokenEstimator,
+        system: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolSpec],
+        usage: &mut AiAgentUsage,
+    ) -> Result<ChatResponse, AppError> {
+        let response = ctx
+            .analyzer
+            .chat_with_tools(
+                system.to_string(),
+                messages.to_vec(),
+                tools.to_vec(),
+                TEMPERATURE,
+                self.config.max_output_tokens,
+            )
+            .await?;
+
+        match response.usage {
+            Some(reported) => {
+                usage.prompt_tokens += reported.prompt_tokens;
+                usage.completion_tokens += reported.completion_tokens;
+            }
+            None => {
+                let prompt = estimate_prompt(estimator, system, messages, tools);
+                let completion = estimator.estimate(&response.content)
+                    + response
+                        .tool_calls
+                        .iter()
+                        .map(|call| estimator.estimate(&call.arguments.to_string()))
+                        .sum::<usize>();
+                usage.prompt_tokens += prompt;
+                usage.completion_tokens += completion as u64;
+            }
+        }
+        Ok(response)
+    }
+
+    fn build_prompt(&self, ctx: &AgentContext, question: &str, trace_id: Option<&str>) -> String {
+        let locale = ctx.locale();
+        let services = if ctx.services.is_empty() {
+            match locale {
+                PromptLocale::Zh => "（未知）".to_string(),
+                PromptLocale::En => "(unknown)".to_string(),
+            }
+        } else {
+            ctx.services.join(", ")
+        };
+        let trace = trace_id
+            .map(|trace_id| match locale {
+                PromptLocale::Zh => format!("\n从 trace_id {} 开始调查。", trace_id),
+                PromptLocale::En => {
+                    format!("\nStart the investigation from trace_id {}.", trace_id)
+                }
+            })
+            .unwrap_or_default();
+        let now = ctx.now.format("%Y-%m-%d %H:%M:%S %:z %A");
+        let tz = ctx.now.timezone().name();
+        let fields = SEARCH_FIELDS.join(", ");
+        let fast_fields = aggregatable_fields().join(", ");
+        let max_steps = self.config.max_steps;
+        match locale {
+            PromptLocale::Zh => format!(
+                r#"调查以下问题：{question}{trace}
+
+当前时间：{now}（时区 {tz}）
+可搜索的字段：{fields}
+可统计取值的字段：{fast_fields}
+最近 24 小时出现过的服务：{services}
+
+调查方法：
+1. 先用 top_values 或 search_logs 了解错误的分布与变化，再用 get_trace、get_context 查看具体请求前后发生了什么
+2. 每次只查询需要的内容，最多调用 {max_steps} 轮工具
+3. 结论中引用查到的日志作为证据，不要编造没有查到的数据
+日志中的敏感信息已替换为 <EMAIL_1> 形式的占位符，查询时可以直接使用占位符。"#
+            ),
+            PromptLocale::En => format!(
+                r#"Investigate the following issue: {question}{trace}
+
+Current time: {now} (time zone {tz})
+Searchable fields: {fields}
+Fields with value statistics: {fast_fields}
+Services seen in the last 24 hours: {services}
+
+How to investigate:
+1. Start with top_values or search_logs to see how errors are distributed and how they change, then use get_trace and get_context to see what happened around specific requests
+2. Only query what you need, with at most {max_steps} rounds of tool calls
+3. Cite the logs you found as evidence in the conclusion; do not make up data you did not find
+Sensitive values in the logs are replaced with placeholders like <EMAIL_1>; you can use the placeholders in queries directly."#
+            ),
+        }
+    }
+
+    fn tool_specs(&self, locale: PromptLocale) -> Vec<ToolSpec> {
+        let text = |zh: String, en: String| match locale {
+            PromptLocale::Zh => zh,
+            PromptLocale::En => en,
+        };
+        let time_range = json!({
+            "since": {
+                "type": "string",
+                "description": text(
+                    format!("相对起点：时长如 15m、2h、1d，或日期运算如 now/d（今天零点）、now-1d/d（昨天零点），默认 {}", DEFAULT_SINCE),
+                    format!("Relative start: a duration such as 15m, 2h or 1d, or date math such as now/d (start of today) or now-1d/d (start of yesterday). Defaults to {}", DEFAULT_SINCE),
+                )
+            },
+            "until": {
+                "type": "string",
+                "description": text(
+                    "相对终点，日期运算如 now-1h，默认 now".to_string(),
+                    "Relative end as date math such as now-1h. Defaults to now".to_string(),
+                )
+            },
+            "start_time": {
+                "type": "string",
+                "description": text(
+                    "绝对起点，RFC 3339 格式，指定后忽略 since".to_string(),
+                    "Absolute start in RFC 3339 format; overrides since".to_string(),
+                )
+            },
+            "end_time": {
+                "type": "string",
+                "description": text(
+                    "绝对终点，RFC 3339 格式".to_string(),
+                    "Absolute end in RFC 3339 format".to_string(),
+                )
+            }
+        });
+        let with_range = |properties: Value| {
+            let mut properties = properties;
+            if let (Some(properties), Some(range)) =
+                (properties.as_object_mut(), time_range.as_object())
+            {
+                properties.extend(range.clone());
+            }
+            properties
+        };
+        let filters = json!({
+            "type": "object",
+            "description": text(
+                "字段精确匹配，如 {\"service\": \"payment\", \"level\": \"ERROR\"}".to_string(),
+                "Exact field matches, such as {\"service\": \"payment\", \"level\": \"ERROR\"}".to_string(),
+            ),
+            "additionalProperties": { "type": "string" }
+        });
+
+        vec![
+            ToolSpec {
+                name: "search_logs".to_string(),
+                description: text(
+                    "按 Lucene 查询语句搜索日志，返回匹配总数与最近的日志".to_string(),
+                    "Search logs with a Lucene query; returns the number of matches and the most recent logs".to_string(),
+                ),
+                parameters: json!({
+                    "type": "object",
+                    "properties": with_range(json!({
+                        "query": {
+                            "type": "string",
+                            "description": text(
+                                "Lucene 查询语句，如 level:ERROR AND message:timeout，默认 *".to_string(),
+                                "Lucene query such as level:ERROR AND message:timeout. Defaults to *".to_string(),
+                            )
+                        },
+                        "filters": filters,
+                        "limit": {
+                            "type": "integer",
+                            "description": text(
+                                format!("返回的日志条数，最多 {}", self.config.max_tool_hits),
+                                format!("Number of logs to return, at most {}", self.config.max_tool_hits),
+                            )
+                        }
+                    })),
+                }),
+            },
+            ToolSpec {
+                name: "get_trace".to_string(),
+                description: text(
+                    format!(
+                        "按时间顺序返回一个 trace 最近 {} 小时内的全部日志（所有级别）",
+                        TRACE_LOOKBACK_HOURS
+                    ),
+                    format!(
+                        "Return all logs (every level) of a trace from the last {} hours in time order",
+                        TRACE_LOOKBACK_HOURS
+                    ),
+                ),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "trace_id": { "type": "string" }
+                    },
+                    "required": ["trace_id"]
+                }),
+            },
+            ToolSpec {
+                name: "get_context".to_string(),
+                description: text(
+                    "返回某个服务在某条日志前后的日志，用于查看出错前后发生了什么".to_string(),
+                    "Return a service's logs around a given log, to see what happened before and after an error".to_string(),
+                ),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "timestamp": {
+                            "type": "string",
+                            "description": text(
+                                "日志的时间，RFC 3339 格式（可直接使用结果中的时间）".to_string(),
+                                "Time of the log in RFC 3339 format (timestamps from results can be used as is)".to_string(),
+                            )
+                        },
+                        "service": { "type": "string" },
+                        "host": {
+                            "type": "string",
+                            "description": text(
+                                "只看该主机的日志".to_string(),
+                                "Only include logs from this host".to_string(),
+                            )
+                        },
+                        "seconds": {
+                            "type": "integer",
+                            "description": text(
+                                format!("前后各多少秒，默认 {}，最多 {}", DEFAULT_CONTEXT_SECONDS, MAX_CONTEXT_SECONDS),
+                                format!("Seconds before and after, default {}, at most {}", DEFAULT_CONTEXT_SECONDS, MAX_CONTEXT_SECONDS),
+                            )
+                        }
+                    },
+                    "required": ["timestamp", "service"]
+                }),
+            },
+            ToolSpec {
+                name: "top_values".to_string(),
+                description: text(
+                    "统计匹配日志在某个字段上数量最多的取值，如哪些服务、主机、错误级别最多".to_string(),
+                    "Count the most frequent values of a field among matching logs, such as which services, hosts or levels have the most logs".to_string(),
+                ),
+                parameters: json!({
+                    "type": "object",
+                    "properties": with_range(json!({
+                        "field": { "type": "string", "enum": aggregatable_fields() },
+                        "query": {
+                            "type": "string",
+                            "description": text(
+                                "Lucene 查询语句，默认 *".to_string(),
+                                "Lucene query. Defaults to *".to_string(),
+                            )
+                        },
+                        "filters": filters,
+                        "size": {
+                            "type": "integer",
+                            "description": text(
+                                format!("返回的取值数，默认 {}，最多 {}", DEFAULT_TOP_VALUES, MAX_TOP_VALUES),
+                                format!("Number of values to return, default {}, at most {}", DEFAULT_TOP_VALUES, MAX_TOP_VALUES),
+                            )
+                        }
+                    })),
+                    "required": ["field"]
+                }),
+            },
+        ]
+    }
+
+    /// 执行一次工具调用。参数中的占位符先还原为原值；参数无效或查询失败时把错误返回给模型
+    async fn run_tool(
+        &self,
+        ctx: &AgentContext<'_>,
+        estimator: &TokenEstimator,
+        call: &ToolCall,
+        redaction: &mut Redaction<'_>,
+    ) -> (String, AiAgentStep) {
+        let arguments = rehydrate_value(&call.arguments, redaction);
+        let start = Instant::now();
+        let result = match call.name.as_str() {
+            "search_logs" => match parse_args(&arguments) {
+                Ok(args) => self.search_logs(ctx, args, redaction).await,
+                Err(e) => Err(e),
+            },
+            "get_trace" => match parse_args(&arguments) {
+                Ok(args) => self.get_trace(ctx, args, redaction).await,
+                Err(e) => Err(e),
+            },
+            "get_context" => match parse_args(&arguments) {
+                Ok(args) => self.get_context(ctx, args, redaction).await,
+                Err(e) => Err(e),
+            },
+            "top_values" => match parse_args(&arguments) {
+                Ok(args) => self.top_values(ctx, args, redaction).await,
+                Err(e) => Err(e),
+            },
+            name => Err(format!("unknown tool '{}'", name)),
+        };
+        let took_ms = start.elapsed().as_millis() as u64;
+
+        info!(
+            "AI agent tool {} {} ({} ms): {}",
+            call.name,
+            arguments,
+            took_ms,
+            match &result {
+                Ok(output) => output.summary.as_str(),
+                Err(e) => e.as_str(),
+            }
+        );
+
+        let mut step = AiAgentStep {
+            tool: call.name.clone(),
+            arguments,
+            summary: None,
+            error: None,
+            took_ms,
+        };
+        let locale = ctx.locale();
+        let text = match result {
+            Ok(output) => {
+                step.summary = Some(output.summary);
+                let (text, truncated) =
+                    estimator.truncate(&output.text, self.config.max_result_tokens, locale);
+                match (truncated, locale) {
+                    (false, _) => text,
+                    (true, PromptLocale::Zh) => {
+                        format!("{}\n…（结果已截断，可以缩小查询范围）", text)
+                    }
+                    (true, PromptLocale::En) => {
+                        format!("{}\n…(result truncated, try a narrower query)", text)
+                    }
+                }
+            }
+            Err(e) => {
+                let text = tool_error(locale, &e);
+                step.error = Some(e);
+                text
+            }
+        };
+        (text, step)
+    }
+
+    async fn search_logs(
+        &self,
+        ctx: &AgentContext<'_>,
+        args: SearchLogsArgs,
+        redaction: &mut Redaction<'_>,
+    ) -> Result<ToolOutput, String> {
+        let limit = args
+            .limit
+            .unwrap_or(self.config.max_tool_hits)
+            .clamp(1, self.config.max_tool_hits);
+        let search = search_request(
+            ctx,
+            args.query.as_deref(),
+            &args.filters,
+            &args.range,
+            limit,
+        )?;
+        let (hits, total) = self.fetch(ctx, &search).await?;
+
+        let mut text = match ctx.locale() {
+            PromptLocale::Zh => format!("共匹配 {} 条日志，显示最近的 {} 条：", total, hits.len()),
+            PromptLocale::En => format!(
+                "{} logs matched, showing the {} most recent:",
+                total,
+                hits.len()
+            ),
+        };
+        for hit in &hits {
+            text.push('\n');
+            text.push_str(&format_hit(hit, ctx.parser, redaction));
+        }
+        Ok(ToolOutput {
+            text,
+            summary: format!("{} matches, {} returned", total, hits.len()),
+        })
+    }
+
+    async fn get_trace(
+        &self,
+        ctx: &AgentContext<'_>,
+        args: GetTraceArgs,
+        redaction: &mut Redaction<'_>,
+    ) -> Result<ToolOutput, String> {
+        let trace_id = args.trace_id.trim();
+        if trace_id.is_empty()
+            || !trace_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("invalid trace_id '{}'", trace_id));
+        }
+
+        let end = Utc::now();
+        let start = end - ChronoDuration::hours(TRACE_LOOKBACK_HOURS);
+        let search = window_request(
+            ctx,
+            format!("trace_id:{}", trace_id),
+            HashMap::new(),
+            start,
+            end,
+            self.config.max_tool_hits,
+            false,
+        )?;
+        let (hits, total) = self.fetch(ctx, &search).await?;
+
+        let trace_id = redaction.redact(trace_id);
+        let mut text = match ctx.locale() {
+            PromptLocale::Zh => format!(
+                "trace {} 共 {} 条日志，按时间顺序显示前 {} 条：",
+                trace_id,
+                total,
+                hits.len()
+            ),
+            PromptLocale::En => format!(
+                "trace {} has {} logs, showing the first {} in time order:",
+                trace_id,
+                total,
+                hits.len()
+            ),
+        };
+        for hit in &hits {
+            text.push('\n');
+            text.push_str(&format_hit(hit, ctx.parser, redaction));
+        }
+        Ok(ToolOutput {
+            text,
+            summary: format!("{} logs", total),
+        })
+    }
+
+    async fn get_context(
+        &self,
+        ctx: &AgentContext<'_>,
+        args: GetContextArgs,
+        redaction: &mut Redaction<'_>,
+    ) -> Result<ToolOutput, String> {
+        let seconds = args
+            .seconds
+            .unwrap_or(DEFAULT_CONTEXT_SECONDS)
+            .clamp(1, MAX_CONTEXT_SECONDS);
+        let mut filters = HashMap::from([("service".to_string(), args.service.clone())]);
+        if let Some(host) = &args.host {
+            filters.insert("host".to_string(), host.clone());
+        }
+
+        // 之前与之后各取一半，之前的部分按时间倒序取最近的
+        let half = self.config.max_tool_hits.div_ceil(2);
+        let pivot = args.timestamp + ChronoDuration::milliseconds(1);
+        let before = window_request(
+            ctx,
+            "*".to_string(),
+            filters.clone(),
+            args.timestamp - ChronoDuration::seconds(seconds),
+            pivot,
+            half,
+            true,
+        )?;
+        let after = window_request(
+            ctx,
+            "*".to_string(),
+            filters,
+            pivot,
+            args.timestamp + ChronoDuration::seconds(seconds),
+            half,
+            false,
+        )?;
+        let (mut hits, _) = self.fetch(ctx, &before).await?;
+        hits.reverse();
+        hits.extend(self.fetch(ctx, &after).await?.0);
+
+        let service = redaction.redact(&args.service);
+        let mut text = match ctx.locale() {
+            PromptLocale::Zh => format!(
+                "{} 在该时间前后 {} 秒内的 {} 条日志（>> 标记该时间点的日志）：",
+                service,
+                seconds,
+                hits.len()
+            ),
+            PromptLocale::En => format!(
+                "{} logs of {} within {} seconds of that time (>> marks the log at that time):",
+                hits.len(),
+                service,
+                seconds
+            ),
+        };
+        for hit in &hits {
+            text.push('\n');
+            if hit.timestamp.timestamp_millis() == args.timestamp.timestamp_millis() {
+                text.push_str(">> ");
+            }
+            text.push_str(&format_hit(hit, ctx.parser, redaction));
+        }
+        Ok(ToolOutput {
+            text,
+            summary: format!("{} logs", hits.len()),
+        })
+    }
+
+    async fn top_values(
+        &self,
+        ctx: &AgentContext<'_>,
+        args: TopValuesArgs,
+        redaction: &mut Redaction<'_>,
+    ) -> Result<ToolOutput, String> {
+        let size = args
+            .size
+            .unwrap_or(DEFAULT_TOP_VALUES)
+            .clamp(1, MAX_TOP_VALUES);
+        let search = search_request(ctx, args.query.as_deref(), &args.filters, &args.range, 1)?;
+        let request = AggregationRequest {
+            search,
+            field: args.field,
+            size,
+        };
+        request.validate()?;
+        let (start, end) = request.search.compute_time_range()?;
+        let response = ctx
+            .quickwit
+            .aggregate(&request, start, end)
+            .await
+            .map_err(|e| e.message().to_string())?;
+
+        let mut text = match ctx.locale() {
+            PromptLocale::Zh => format!(
+                "字段 {} 数量最多的取值（共匹配 {} 条日志）：",
+                request.field, response.total
+            ),
+            PromptLocale::En => format!(
+                "Most frequent values of {} ({} logs matched):",
+                request.field, response.total
+            ),
+        };
+        for bucket in &response.buckets {
+            let value = match &bucket.key {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            text.push_str(&format!(
+                "\n{}: {}",
+                redaction.redact(&value),
+                bucket.doc_count
+            ));
+        }
+        Ok(ToolOutput {
+            text,
+            summary: format!(
+                "{} values over {} matches",
+                response.buckets.len(),
+                response.total
+            ),
+        })
+    }
+
+    /// 执行搜索，返回按请求时区渲染的日志与匹配总数
+    async fn fetch(
+        &self,
+        ctx: &AgentContext<'_>,
+        search: &SearchRequest,
+    ) -> Result<(Vec<LogHit>, u64), String> {
+        let (start, end) = search.compute_time_range()?;
+        let tz = search.tz()?;
+        let response = ctx
+            .quickwit
+            .search(search, start, end)
+            .await
+            .map_err(|e| e.message().to_string())?
+            .with_time_zone(tz);
+        Ok((response.hits, response.total))
+    }
+}
+
+/// 预算不足时替换工具结果的内容
+fn omitted_result(locale: PromptLocale) -> &'static str {
+    match locale {
+        PromptLocale::Zh => "（结果已省略）",
+        PromptLocale::En => "(result omitted)",
+    }
+}
+
+/// 返回给模型的工具错误
+fn tool_error(locale: PromptLocale, error: &str) -> String {
+    match locale {
+        PromptLocale::Zh => format!("错误：{}", error),
+        PromptLocale::En => format!("Error: {}", error),
+    }
+}
+
+/// 可以统计取值的字段
+fn aggregatable_fields() -> Vec<&'static str> {
+    FAST_FIELDS
+        .iter()
+        .filter(|field| **field != "timestamp")
+        .copied()
+        .collect()
+}
+
+fn parse_args<T: for<'de> Deserialize<'de>>(arguments: &Value) -> Result<T, String> {
+    if !arguments.is_object() {
+        return Err("arguments must be a JSON object".to_string());
+    }
+    serde_json::from_value(arguments.clone()).map_err(|e| format!("invalid arguments: {}", e))
+}
+
+/// 按工具参数构造搜索请求，经过反序列化补齐默认值后按搜索接口的规则校验
+fn search_request(
+    ctx: &AgentContext,
+    query: Option<&str>,
+    filters: &HashMap<String, String>,
+    range: &TimeRangeArgs,
+    limit: usize,
+) -> Result<SearchRequest, String> {
+    let time_range = if range.start_time.is_some() || range.end_time.is_some() {
+        json!({
+            "time_range_type": "absolute",
+            "start_time": range.start_time,
+            "end_time": range.end_time,
+        })
+    } else {
+        json!({
+            "time_range_type": "relative",
+            "relative_time_key": range.since.as_deref().unwrap_or(DEFAULT_SINCE),
+            "relative_end_key": range.until.as_deref().filter(|until| *until != "now"),
+        })
+    };
+    build_request(ctx, query.unwrap_or("*"), filters, time_range, limit, true)
+}
+
+/// 绝对时间窗口内的搜索请求
+fn window_request(
+    ctx: &AgentContext,
+    query: String,
+    filters: HashMap<String, String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: usize,
+    sort_desc: bool,
+) -> Result<SearchRequest, String> {
+    let time_range = json!({
+        "time_range_type": "absolute",
+        "start_time": start,
+        "end_time": end,
+    });
+    build_request(ctx, &query, &filters, time_range, limit, sort_desc)
+}
+
+fn build_request(
+    ctx: &AgentContext,
+    query: &str,
+    filters: &HashMap<String, String>,
+    time_range: Value,
+    limit: usize,
+    sort_desc: bool,
+) -> Result<SearchRequest, String> {
+    for field in filters.keys() {
+        if !SEARCH_FIELDS.contains(&field.as_str()) {
+            return Err(format!(
+                "unknown filter field '{}', available: {}",
+                field,
+                SEARCH_FIELDS.join(", ")
+            ));
+        }
+    }
+    let query = match query.trim() {
+        "" => "*",
+        query => query,
+    };
+
+    let mut search = json!({
+        "query": query,
+        "filters": filters,
+        "time_zone": ctx.time_zone,
+        "page_size": limit,
+        "sort_desc": sort_desc,
+    });
+    if let (Some(search), Some(time_range)) = (search.as_object_mut(), time_range.as_object()) {
+        search.extend(time_range.clone());
+    }
+    let search: SearchRequest =
+        serde_json::from_value(search).map_err(|e| format!("invalid time range: {}", e))?;
+    search.validate()?;
+    Ok(search)
+}
+
+/// 单行日志：时间（可直接用于 get_context）、级别、服务、主机、trace_id 与消息，
+/// 之后是只保留业务帧的堆栈摘要。敏感信息替换为占位符
+fn format_hit(hit: &LogHit, parser: &StackTraceParser, redaction: &mut Redaction) -> String {
+    let mut line = format!(
+        "[{}] [{}] [{}]",
+        hit.timestamp.format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+        hit.level,
+        hit.service
+    );
+    if let Some(host) = &hit.host {
+        line.push_str(&format!(" host={}", redaction.redact(host)));
+    }
+    if let Some(trace_id) = &hit.trace_id {
+        line.push_str(&format!(" trace_id={}", redaction.redact(trace_id)));
+    }
+    line.push(' ');
+    line.push_str(&redaction.redact(&hit.message));
+    if let Some(stack_trace) = &hit.stack_trace {
+        let parsed = parser.parse(stack_trace);
+        let stack = if parsed.frames.is_empty() {
+            stack_trace
+                .lines()
+                .take(STACK_FRAMES + 1)
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            parsed.summary(STACK_FRAMES)
+        };
+        for stack_line in redaction.redact(&stack).lines() {
+            line.push_str("\n    ");
+            line.push_str(stack_line);
+        }
+    }
+    line
+}
+
+/// 把参数中字符串里的占位符还原为原值，模型可能直接使用结果中的占位符查询
+fn rehydrate_value(value: &Value, redaction: &Redaction) -> Value {
+    match value {
+        Value::String(text) => Value::String(redaction.placeholders().rehydrate(text)),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| rehydrate_value(item, redaction))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), rehydrate_value(value, redaction)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+/// 一次调用的输入 token 估算：系统提示、历史消息与工具定义
+fn estimate_prompt(
+    estimator: &TokenEstimator,
+    system: &str,
+    messages: &[ChatMessage],
+    tools: &[ToolSpec],
+) -> u64 {
+    let messages: usize = messages
+        .iter()
+        .map(|message| estimator.estimate(&message_text(message)))
+        .sum();
+    let tools: usize = tools
+        .iter()
+        .map(|tool| {
+            estimator.estimate(&tool.description) + estimator.estimate(&tool.parameters.to_string())
+        })
+        .sum();
+    (estimator.estimate(system) + messages + tools) as u64
+}
+
+/// 估算 token 时消息的文本，包括工具调用的参数
+fn message_text(message: &ChatMessage) -> String {
+    let mut text = message.content.clone();
+    for call in &message.tool_calls {
+        text.push_str(&call.name);
+        text.push_str(&call.arguments.to_string());
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AiAnalyzerConfig, QuickwitConfig, RedactionConfig};
+    use crate::metrics::Metrics;
+    use crate::services::ai_provider::fake::FakeProvider;
+    use crate::services::ai_provider::{ChatRequest, TokenUsage};
+    use std::sync::Arc;
+
+    struct Fixture {
+        provider: Arc<FakeProvider>,
+        analyzer: AiAnalyzerClient,
+        quickwit: QuickwitClient,
+        parser: StackTraceParser,
+        redactor: Redactor,
+    }
+
+    impl Fixture {
+        fn new(reply: fn(&ChatRequest) -> ChatResponse) -> Self {
+            let provider = Arc::new(FakeProvider::new(reply));
+            let analyzer_config: AiAnalyzerConfig = serde_json::from_value(json!({
+                "base_url": "http://127.0.0.1:1",
+                "api_key": null,
+                "model": "gpt-4o-mini"
+            }))
+            .unwrap();
+            let quickwit_config: QuickwitConfig = serde_json::from_value(json!({
+                "base_url": "http://127.0.0.1:1",
+                "index_id": "logs"
+            }))
+            .unwrap();
+            Self {
+                analyzer: AiAnalyzerClient::new(&analyzer_config, Metrics::new(), None)
+                    .unwrap()
+                    .with_provider(provider.clone()),
+                provider,
+                quickwit: QuickwitClient::new(&quickwit_config, Metrics::new(), None),
+                parser: StackTraceParser::new(Vec::new()),
+                redactor: Redactor::new(&RedactionConfig::default()).unwrap(),
+            }
+        }
+
+        async fn investigate(&self, config: AiAgentConfig, question: &str) -> AiAgentResponse {
+            self.investigate_in(config, question, "中文").await
+        }
+
+        async fn investigate_in(
+            &self,
+            config: AiAgentConfig,
+            question: &str,
+            language: &str,
+        ) -> AiAgentResponse {
+            let ctx = AgentContext {
+                analyzer: &self.analyzer,
+                quickwit: &self.quickwit,
+                parser: &self.parser,
+                redactor: &self.redactor,
+                services: &[],
+                now: Utc::now().with_timezone(&chrono_tz::UTC),
+                time_zone: None,
+                language,
+            };
+            AiAgent::new(&config)
+                .investigate(&ctx, question, None)
+                .await
+                .unwrap()
+        }
+
+        fn requests(&self) -> Vec<ChatRequest> {
+            self.provider.requests()
+        }
+    }
+
+    fn call(id: &str, name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    fn reply(content: &str, tool_calls: Vec<ToolCall>, usage: Option<TokenUsage>) -> ChatResponse {
+        ChatResponse {
+            content: content.to_string(),
+            tool_calls,
+            usage,
+        }
+    }
+
+    /// 是否为调查的第一次请求
+    fn is_first(request: &ChatRequest) -> bool {
+        request.messages.len() == 1
+    }
+
+    /// 无效的 trace_id 在查询前返回错误，不访问 Quickwit
+    fn invalid_trace() -> ToolCall {
+        call("call_1", "get_trace", json!({ "trace_id": "not a trace" }))
+    }
+
+    #[tokio::test]
+    async fn stops_at_step_limit_and_asks_for_conclusion() {
+        let fixture = Fixture::new(|request| match request.messages.last() {
+            Some(message) if message.role == ChatRole::User && !is_first(request) => {
+                reply("连接池耗尽", Vec::new(), None)
+            }
+            _ => reply("", vec![invalid_trace()], None),
+        });
+        let config = AiAgentConfig {
+            max_steps: 2,
+            ..Default::default()
+        };
+        let response = fixture.investigate(config, "为什么支付失败？").await;
+
+        assert_eq!(response.stop_reason, AiAgentStopReason::StepLimit);
+        assert_eq!(response.analysis, "连接池耗尽");
+        assert_eq!(response.steps.len(), 2);
+        assert_eq!(response.ai_calls, 3);
+        assert_eq!(fixture.requests().len(), 3);
+        assert!(response.steps[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("invalid trace_id"));
+    }
+
+    #[tokio::test]
+    async fn stops_before_a_call_would_exceed_the_token_budget() {
+        let fixture = Fixture::new(|_| {
+            reply(
+                "",
+                vec![invalid_trace()],
+                Some(TokenUsage {
+                    prompt_tokens: 19_500,
+                    completion_tokens: 10,
+                }),
+            )
+        });
+        let config = AiAgentConfig {
+            max_tokens: 20_000,
+            max_output_tokens: 500,
+            ..Default::default()
+        };
+        let response = fixture.investigate(config, "为什么支付失败？").await;
+
+        // 第二轮与结论的估算输入都超出剩余预算，不再调用
+        assert_eq!(response.stop_reason, AiAgentStopReason::TokenLimit);
+        assert_eq!(response.ai_calls, 1);
+        assert_eq!(fixture.requests().len(), 1);
+        assert!(response.usage.prompt_tokens + response.usage.completion_tokens <= 20_000);
+        assert!(!response.analysis.is_empty());
+    }
+
+    #[test]
+    fn omits_oldest_tool_results_to_fit_the_budget() {
+        let estimator = TokenEstimator::for_model("gpt-4o-mini");
+        let tools = AiAgent::new(&AiAgentConfig::default()).tool_specs(PromptLocale::Zh);
+        let messages = vec![
+            ChatMessage::user("为什么支付失败？"),
+            ChatMessage::assistant_tool_calls(
+                "",
+                vec![
+                    call("call_1", "search_logs", json!({})),
+                    call("call_2", "search_logs", json!({})),
+                ],
+            ),
+            ChatMessage::tool_result("call_1", "timeout ".repeat(500)),
+            ChatMessage::tool_result("call_2", "refused ".repeat(500)),
+            ChatMessage::user("给出结论"),
+        ];
+        let mut trimmed = messages.clone();
+        trimmed[2].content = omitted_result(PromptLocale::Zh).to_string();
+        let agent = AiAgent::new(&AiAgentConfig {
+            max_tokens: estimate_prompt(&estimator, "system", &trimmed, &tools) + 100,
+            max_output_tokens: 100,
+            ..Default::default()
+        });
+
+        let mut fitted = messages.clone();
+        let usage = AiAgentUsage::default();
+        assert!(agent.trim_to_budget(
+            &estimator,
+            "system",
+            &mut fitted,
+            &tools,
+            &usage,
+            PromptLocale::Zh
+        ));
+        assert_eq!(fitted[2].content, omitted_result(PromptLocale::Zh));
+        assert_eq!(fitted[3].content, messages[3].content);
+
+        // 已用的 token 使全部省略后仍超出预算
+        let mut exhausted = messages;
+        let usage = AiAgentUsage {
+            prompt_tokens: agent.config.max_tokens,
+            ..Default::default()
+        };
+        assert!(!agent.trim_to_budget(
+            &estimator,
+            "system",
+            &mut exhausted,
+            &tools,
+            &usage,
+            PromptLocale::Zh
+        ));
+    }
+
+    #[tokio::test]
+    async fn follows_the_requested_language() {
+        let fixture = Fixture::new(|request| {
+            if is_first(request) {
+                reply("", vec![invalid_trace()], None)
+            } else {
+                reply("done", Vec::new(), None)
+            }
+        });
+        fixture
+            .investigate_in(AiAgentConfig::default(), "why do payments fail?", "English")
+            .await;
+
+        let requests = fixture.requests();
+        assert!(requests[0].system.starts_with("You are"));
+        assert!(requests[0].messages[0]
+            .content
+            .starts_with("Investigate the following issue"));
+        assert!(requests[0]
+            .tools
+            .iter()
+            .all(|tool| tool.description.is_ascii()));
+        let result = requests[1].messages.last().unwrap();
+        assert!(result.content.starts_with("Error: invalid trace_id"));
+    }
+
+    #[tokio::test]
+    async fn rejects_calls_beyond_the_per_step_limit() {
+        let fixture = Fixture::new(|request| {
+            if is_first(request) {
+                let calls = (0..MAX_CALLS_PER_STEP + 2)
+                    .map(|index| {
+                        call(
+                            &format!("call_{}", index),
+                            "get_trace",
+                            json!({ "trace_id": "" }),
+                        )
+                    })
+                    .collect();
+                reply("", calls, None)
+            } else {
+                reply("完成", Vec::new(), None)
+            }
+        });
+        let response = fixture
+            .investigate(AiAgentConfig::default(), "查一下")
+            .await;
+
+        assert_eq!(response.stop_reason, AiAgentStopReason::Completed);
+        assert_eq!(response.steps.len(), MAX_CALLS_PER_STEP + 2);
+        for (index, step) in response.steps.iter().enumerate() {
+            let error = step.error.as_deref().unwrap();
+            assert_eq!(
+                error.starts_with("at most"),
+                index >= MAX_CALLS_PER_STEP,
+                "{}",
+                error
+            );
+        }
+        // 每个工具调用都有对应的结果
+        let requests = fixture.requests();
+        let results: Vec<&str> = requests[1]
+            .messages
+            .iter()
+            .filter_map(|message| message.tool_call_id.as_deref())
+            .collect();
+        assert_eq!(results.len(), MAX_CALLS_PER_STEP + 2);
+        assert_eq!(results[MAX_CALLS_PER_STEP + 1], "call_6");
+    }
+
+    #[tokio::test]
+    async fn returns_unknown_tool_errors_to_the_model() {
+        let fixture = Fixture::new(|request| {
+            if is_first(request) {
+                reply("", vec![call("call_1", "drop_index", json!({}))], None)
+            } else {
+                reply("完成", Vec::new(), None)
+            }
+        });
+        let response = fixture
+            .investigate(AiAgentConfig::default(), "查一下")
+            .await;
+
+        assert_eq!(
+            response.steps[0].error.as_deref(),
+            Some("unknown tool 'drop_index'")
+        );
+        let requests = fixture.requests();
+        let result = requests[1].messages.last().unwrap();
+        assert_eq!(result.role, ChatRole::Tool);
+        assert_eq!(result.content, "错误：unknown tool 'drop_index'");
+    }
+
+    #[tokio::test]
+    async fn placeholders_round_trip_through_the_investigation() {
+        let fixture = Fixture::new(|request| {
+            if is_first(request) {
+                reply("", vec![invalid_trace()], None)
+            } else {
+                reply("用户 <EMAIL_1> 的请求失败", Vec::new(), None)
+            }
+        });
+        let response = fixture
+            .investigate(
+                AiAgentConfig::default(),
+                "alice@example.com 为什么下单失败？",
+            )
+            .await;
+
+        let requests = fixture.requests();
+        assert!(requests[0].messages[0].content.contains("<EMAIL_1>"));
+        assert!(!requests[0].messages[0]
+            .content
+            .contains("alice@example.com"));
+        assert_eq!(response.analysis, "用户 alice@example.com 的请求失败");
+    }
+
+    #[test]
+    fn rehydrate_value_restores_nested_placeholders() {
+        let redactor = Redactor::new(&RedactionConfig::default()).unwrap();
+        let mut redaction = redactor.session();
+        let placeholder = redaction.redact("alice@example.com");
+        assert_eq!(placeholder, "<EMAIL_1>");
+
+        let value = rehydrate_value(
+            &json!({
+                "query": "user:\"<EMAIL_1>\"",
+                "filters": { "user": "<EMAIL_1>" },
+                "ids": ["<EMAIL_1>", "<EMAIL_2>"],
+                "limit": 5
+            }),
+            &redaction,
+        );
+        assert_eq!(
+            value,
+            json!({
+                "query": "user:\"alice@example.com\"",
+                "filters": { "user": "alice@example.com" },
+                "ids": ["alice@example.com", "<EMAIL_2>"],
+                "limit": 5
+            })
+        );
+    }
+}
@@ -1,193 +1,605 @@
+use crate::config::AiAnalyzerConfig;
 use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::models::query::AiContextSummary;
+use crate::services::ai_cache::{AiAnalysisCache, CachedAnalysis};
+use crate::services::ai_provider::{
+    self, AiProvider, ChatMessage, ChatRequest, ChatResponse, StreamChunk, ToolSpec,
+};
+use crate::services::prompt_packer::{ContextPacker, PromptEntry, TokenEstimator};
+use crate::services::prompt_template::{
+    language_name, validate_language, PromptLocale, PromptTemplate, PromptTemplates, TemplateVars,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt};
 use log::info;
 use reqwest::Client;
-use serde_json::{json, Value};
-use std::time::Duration as StdDuration;
+use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
+
+/// 流式分析输出的文本增量序列，出错后结束
+pub type AnalysisStream = BoxStream<'static, Result<String, AppError>>;
+
+/// 模板未指定时的生成参数
+const DEFAULT_TEMPERATURE: f32 = 0.3;
+const DEFAULT_MAX_TOKENS: u32 = 1500;
+
+/// 缓存 key 的版本，修改 key 的组成或提示词的拼装方式时递增，使已缓存的分析失效
+const PROMPT_VERSION: &str = "3";
+
+/// 一次分析的输入
+pub struct AnalysisRequest {
+    /// 分析对象，用于日志与缓存记录，如 trace_id 或查询语句
+    pub subject: String,
+    /// 分析单个 trace 时的 trace_id
+    pub trace_id: Option<String>,
+    /// 提示词中对分析范围的说明，如 `Trace ID: abc`
+    pub scope: String,
+    /// 已脱敏的日志或日志模式
+    pub logs: Vec<PromptEntry>,
+    /// 日志涉及的服务
+    pub services: Vec<String>,
+    /// 日志的时间范围
+    pub time_range: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+    pub template: Arc<PromptTemplate>,
+    /// 回答语言的名称，如 English
+    pub language: String,
+    /// 忽略缓存重新分析
+    pub force_refresh: bool,
+}
+
+/// 一次分析的结果
+pub struct Analysis {
+    pub analysis: String,
+    pub context: AiContextSummary,
+    /// 来自缓存时为分析完成的时间
+    pub cached_at: Option<DateTime<Utc>>,
+}
+
+/// 流式分析：内容随生成逐段产出，命中缓存时一次产出完整结果
+pub struct StreamingAnalysis {
+    pub stream: AnalysisStream,
+    pub context: AiContextSummary,
+    pub cached_at: Option<DateTime<Utc>>,
+}
 
 #[derive(Clone)]
 pub struct AiAnalyzerClient {
-    /// AI API Base URL (e.g., OpenAI, Anthropic, or custom endpoint)
-    base_url: String,
-    /// API Key for authentication
-    api_key: Option<String>,
-    /// HTTP client
-    client: Client,
+    /// AI 服务商实现（OpenAI 兼容、Anthropic、Ollama）
+    provider: Arc<dyn AiProvider>,
     /// Model name to use
     model: String,
+    /// 按模型估算 token 的日志打包器
+    packer: Arc<ContextPacker>,
+    /// 提示词模板
+    templates: Arc<PromptTemplates>,
+    /// 请求未指定语言时的回答语言
+    default_language: String,
+    /// 分析结果缓存，未启用时为 None
+    cache: Option<Arc<AiAnalysisCache>>,
+    /// Prometheus metrics
+    metrics: Metrics,
 }
 
 impl AiAnalyzerClient {
-    pub fn new(base_url: String, api_key: Option<String>, model: String) -> Self {
+    /// 模板目录中有无效模板时返回错误
+    pub fn new(
+        config: &AiAnalyzerConfig,
+        metrics: Metrics,
+        cache: Option<Arc<AiAnalysisCache>>,
+    ) -> Result<Self, String> {
+        let templates =
+            PromptTemplates::load(&config.prompt_templates_dir, &config.default_template)?;
+
+        // 请求超时按流式与非流式分别设置，见 ai_provider::send
         let client = Client::builder()
-            .timeout(StdDuration::from_secs(180))  // 增加超时到 3 分钟
             .connect_timeout(StdDuration::from_secs(30))  // 连接超时 30 秒
             .build()
             .expect("Failed to build HTTP client");
 
-        Self {
-            base_url,
-            api_key,
-            client,
-            model,
+        Ok(Self {
+            provider: ai_provider::from_config(config, client),
+            model: config.model.clone(),
+            packer: Arc::new(ContextPacker::new(
+                TokenEstimator::for_model(&config.model),
+                config.max_context_tokens,
+            )),
+            templates: Arc::new(templates),
+            default_language: config.default_language.clone(),
+            cache,
+            metrics,
+        })
+    }
+
+    /// 替换服务商实现，测试中使用不发送请求的服务商
+    #[cfg(test)]
+    pub(crate) fn with_provider(mut self, provider: Arc<dyn AiProvider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// 按名称查找提示词模板，None 时使用默认模板；`language` 为回答语言的名称，
+    /// 模板有对应语言的版本时使用该版本
+    pub fn template(
+        &self,
+        name: Option<&str>,
+        language: &str,
+    ) -> Result<Arc<PromptTemplate>, AppError> {
+        let locale = PromptLocale::for_language(language);
+        self.templates.get(name, locale).ok_or_else(|| {
+            let available: Vec<String> = self
+                .templates
+                .list()
+                .into_iter()
+                .map(|template| template.name)
+                .collect();
+            AppError::ValidationError(format!(
+                "unknown prompt template '{}', available: {}",
+                name.unwrap_or_default(),
+                available.join(", ")
+            ))
+        })
+    }
+
+    /// 回答语言的名称，None 时使用默认语言
+    pub fn language(&self, language: Option<&str>) -> Result<String, AppError> {
+        let language = language
+            .map(str::trim)
+            .filter(|language| !language.is_empty())
+            .unwrap_or(&self.default_language);
+        validate_language(language).map_err(AppError::ValidationError)?;
+        Ok(language_name(language))
+    }
+
+    pub fn templates(&self) -> &PromptTemplates {
+        &self.templates
+    }
+
+    pub fn default_language(&self) -> &str {
+        &self.default_language
+    }
+
+    /// 分析错误日志；相同的 trace、日志、模型和提示词直接返回缓存结果，
+    /// `force_refresh` 时跳过缓存重新分析，新结果仍会写入缓存
+    pub async fn analyze_error_logs(&self, request: AnalysisRequest) -> Result<Analysis, AppError> {
+        if request.logs.is_empty() {
+            return Ok(Analysis {
+                analysis: no_logs(&request.language).to_string(),
+                context: AiContextSummary::default(),
+                cached_at: None,
+            });
         }
+
+        let (chat, context) = self.prepare_prompt(&request);
+        self.analyze_prepared(&request.subject, &chat, context, request.force_refresh)
+            .await
     }
 
-    pub async fn analyze_error_logs(
+    /// 执行已生成的首轮请求；相同的请求直接返回缓存结果
+    pub async fn analyze_prepared(
         &self,
-        logs: Vec<String>,
-        trace_id: &str,
-    ) -> Result<String, AppError> {
-        if logs.is_empty() {
-            return Ok("没有找到相关的错误日志可以分析。".to_string());
+        subject: &str,
+        chat: &ChatRequest,
+        context: AiContextSummary,
+        force_refresh: bool,
+    ) -> Result<Analysis, AppError> {
+        let cache_key = self.cache_key(subject, chat);
+        if let Some(cached) = self.lookup(&cache_key, force_refresh) {
+            info!("AI analysis for {} served from cache", subject);
+            return Ok(Analysis {
+                analysis: cached.analysis,
+                context: cached.context,
+                cached_at: Some(cached.created_at),
+            });
         }
 
-        // 限制日志数量，避免请求过大
-        let max_logs = 50;
-        let logs_to_analyze = if logs.len() > max_logs {
-            info!("Found {} logs, truncating to {} for AI analysis", logs.len(), max_logs);
-            logs[..max_logs].to_vec()
-        } else {
-            logs
-        };
+        // 调用AI API（超时由 ai_provider 的 COMPLETE_TIMEOUT 控制）
+        let response = self.call_ai_api(chat).await?.content;
 
-        info!("Starting AI analysis for trace_id: {}, logs count: {}", trace_id, logs_to_analyze.len());
+        info!("AI analysis completed for {}", subject);
 
-        // 构建提示词
-        let prompt = self.build_prompt(logs_to_analyze, trace_id);
+        if let Some(cache) = &self.cache {
+            cache
+                .insert(
+                    cache_key,
+                    CachedAnalysis {
+                        subject: subject.to_string(),
+                        model: self.model.clone(),
+                        analysis: response.clone(),
+                        context: context.clone(),
+                        created_at: Utc::now(),
+                    },
+                )
+                .await;
+        }
 
-        // 记录 prompt 大小
-        info!("Prompt size: {} characters", prompt.len());
+        Ok(Analysis {
+            analysis: response,
+            context,
+            cached_at: None,
+        })
+    }
 
-        // 调用AI API（超时时间已在 client 中设置为 180 秒）
-        let response = self.call_ai_api(&prompt).await?;
+    /// 流式分析：上游返回成功状态后立即返回，分析内容随生成逐段产出。
+    /// 丢弃返回的流即取消上游请求；完整生成的结果写入缓存，中途失败或取消的不缓存
+    pub async fn analyze_error_logs_stream(
+        &self,
+        request: AnalysisRequest,
+    ) -> Result<StreamingAnalysis, AppError> {
+        if request.logs.is_empty() {
+            let analysis = no_logs(&request.language).to_string();
+            let stream = stream::once(async move { Ok(analysis) }).boxed();
+            return Ok(StreamingAnalysis {
+                stream,
+                context: AiContextSummary::default(),
+                cached_at: None,
+            });
+        }
 
-        info!("AI analysis completed for trace_id: {}", trace_id);
+        let subject = request.subject.as_str();
+        let (chat, context) = self.prepare_prompt(&request);
+        let cache_key = self.cache_key(subject, &chat);
+        if let Some(cached) = self.lookup(&cache_key, request.force_refresh) {
+            info!("AI analysis for {} served from cache", subject);
+            let analysis = cached.analysis;
+            return Ok(StreamingAnalysis {
+                stream: stream::once(async move { Ok(analysis) }).boxed(),
+                context: cached.context,
+                cached_at: Some(cached.created_at),
+            });
+        }
 
-        Ok(response)
+        let stream = self.call_ai_api_stream(&chat).await?;
+        let stream = match &self.cache {
+            Some(cache) => {
+                let entry = CachedAnalysis {
+                    subject: subject.to_string(),
+                    model: self.model.clone(),
+                    analysis: String::new(),
+                    context: context.clone(),
+                    created_at: Utc::now(),
+                };
+                cache_on_completion(stream, cache.clone(), cache_key, entry)
+            }
+            None => stream,
+        };
+
+        Ok(StreamingAnalysis {
+            stream,
+            context,
+            cached_at: None,
+        })
     }
 
-    fn build_prompt(&self, logs: Vec<String>, trace_id: &str) -> String {
-        // 限制单个日志长度，避免 prompt 过长
-        let max_log_length = 500;
-        let truncated_logs: Vec<String> = logs
-            .into_iter()
-            .map(|log| {
-                if log.len() > max_log_length {
-                    format!("{}...(省略 {} 字符)", &log[..max_log_length], log.len() - max_log_length)
-                } else {
-                    log
-                }
+    fn cache_key(&self, subject: &str, chat: &ChatRequest) -> String {
+        AiAnalysisCache::key(subject, self.provider.kind().as_str(), PROMPT_VERSION, chat)
+    }
+
+    fn lookup(&self, key: &str, force_refresh: bool) -> Option<CachedAnalysis> {
+        let cache = self.cache.as_ref()?;
+        if force_refresh {
+            cache.observe_bypass();
+            return None;
+        }
+        cache.get(key)
+    }
+
+    /// 打包日志并按模板生成首轮请求
+    pub fn prepare_prompt(&self, request: &AnalysisRequest) -> (ChatRequest, AiContextSummary) {
+        // 在 token 预算内挑选日志：重复日志合并计数，超出预算的内容截断或省略
+        let packed = self.packer.pack(
+            request.logs.clone(),
+            PromptLocale::for_language(&request.language),
+        );
+        let summary = &packed.summary;
+
+        info!(
+            "Starting AI analysis for {}, template: {}, language: {}, logs: {}, distinct errors: {}, included: {}, ~{} tokens",
+            request.subject,
+            request.template.name,
+            request.language,
+            summary.total_logs,
+            summary.distinct_errors,
+            summary.included_errors,
+            summary.estimated_tokens
+        );
+
+        let logs = match packed.omission_note() {
+            Some(note) => format!("{}\n\n{}", packed.text, note),
+            None => packed.text.clone(),
+        };
+        let time_range = request
+            .time_range
+            .map(|(start, end)| {
+                format!(
+                    "{} ~ {}",
+                    start.format("%Y-%m-%d %H:%M:%S"),
+                    end.format("%Y-%m-%d %H:%M:%S")
+                )
             })
-            .collect();
+            .unwrap_or_default();
+        let template = &request.template;
+        let prompt = template.render(&TemplateVars {
+            scope: &request.scope,
+            trace_id: request.trace_id.as_deref().unwrap_or_default(),
+            logs: &logs,
+            services: &request.services.join(", "),
+            time_range: &time_range,
+            language: &request.language,
+        });
 
-        let logs_text = truncated_logs.join("\n\n---\n\n");
+        // 记录 prompt 大小
+        info!("Prompt size: {} characters", prompt.chars().count());
 
-        // 限制总日志字符数
-        let max_total_length = 15000;
-        let final_logs_text = if logs_text.len() > max_total_length {
-            format!("{}...(省略部分内容)", &logs_text[..max_total_length])
-        } else {
-            logs_text
+        let chat = ChatRequest {
+            model: self.model.clone(),
+            system: template.render_system(&request.language),
+            messages: vec![ChatMessage::user(prompt)],
+            tools: Vec::new(),
+            temperature: template.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            top_p: 0.9,
+            max_tokens: template.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
         };
+        (chat, packed.summary)
+    }
 
-        format!(
-            r#"你是一位资深的系统架构师和故障排查专家。请简洁分析以下错误日志。
+    /// 多轮对话，不经过模板与缓存，`messages` 的最后一条为用户消息
+    pub async fn chat(
+        &self,
+        system: String,
+        messages: Vec<ChatMessage>,
+        temperature: f32,
+        max_tokens: u32,
+    ) -> Result<String, AppError> {
+        let request = ChatRequest {
+            model: self.model.clone(),
+            system,
+            messages,
+            tools: Vec::new(),
+            temperature,
+            top_p: 0.9,
+            max_tokens,
+        };
+        Ok(self.call_ai_api(&request).await?.content)
+    }
 
-Trace ID: {}
+    /// 可调用工具的多轮对话，返回回复内容、请求的工具调用与 token 用量
+    pub async fn chat_with_tools(
+        &self,
+        system: String,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolSpec>,
+        temperature: f32,
+        max_tokens: u32,
+    ) -> Result<ChatResponse, AppError> {
+        let request = ChatRequest {
+            model: self.model.clone(),
+            system,
+            messages,
+            tools,
+            temperature,
+            top_p: 0.9,
+            max_tokens,
+        };
+        self.call_ai_api(&request).await
+    }
 
-日志内容：
-{}
+    /// 当前模型的 token 估算器
+    pub fn token_estimator(&self) -> TokenEstimator {
+        TokenEstimator::for_model(&self.model)
+    }
 
-要求：
-1. 简洁说明主要错误（1-2句话）
-2. 分析可能原因（2-3个关键点）
-3. 提供解决建议（2-3条）
-4. 控制在500字以内，使用中文"#,
-            trace_id, final_logs_text
-        )
+    /// 探测 AI 服务是否可达（不消耗 token）
+    pub async fn probe(&self) -> Result<(), AppError> {
+        self.provider.probe().await
     }
 
-    async fn call_ai_api(&self, prompt: &str) -> Result<String, AppError> {
-        // 这里以 OpenAI 格式为例，实际使用时可以根据需要调整为其他AI服务
-        let request_body = json!({
-            "model": self.model,
-            "messages": [
-                {
-                    "role": "system",
-                    "content": "你是一位专业的日志分析助手，擅长快速诊断系统错误和性能问题。回答简洁明了。"
-                },
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ],
-            "temperature": 0.3,
-            "max_tokens": 1500,  // 减少 token 数量，加快响应速度
-            "top_p": 0.9
-        });
+    async fn call_ai_api(&self, request: &ChatRequest) -> Result<ChatResponse, AppError> {
+        let start = Instant::now();
+        let result = self.provider.complete(request).await;
+        self.metrics
+            .observe_ai(&self.model, start.elapsed(), result.is_ok());
+        let response = result?;
 
-        // 构建API URL，自动处理路径问题
-        let url = if self.base_url.ends_with("/api/v1") {
-            // OpenRouter 等服务 base_url 包含 /api/v1
-            format!("{}/chat/completions", self.base_url.trim_end_matches('/'))
-        } else if self.base_url.ends_with("/api") {
-            // Anthropic 等服务 base_url 包含 /api
-            format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'))
-        } else if self.base_url.contains("openai.com") {
-            // OpenAI 官方服务
-            format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'))
-        } else {
-            // 通用情况，尝试添加 /v1/chat/completions
-            let base = self.base_url.trim_end_matches('/');
-            if base.ends_with("/v1") {
-                format!("{}/chat/completions", base)
-            } else {
-                format!("{}/v1/chat/completions", base)
+        // 记录 token 用量
+        if let Some(usage) = response.usage {
+            self.metrics
+                .add_ai_tokens(&self.model, usage.prompt_tokens, usage.completion_tokens);
+        }
+
+        Ok(response)
+    }
+
+    /// 流式调用，token 用量与耗时在流结束时记录
+    async fn call_ai_api_stream(&self, request: &ChatRequest) -> Result<AnalysisStream, AppError> {
+        let start = Instant::now();
+        let chunks = match self.provider.complete_stream(request).await {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                self.metrics.observe_ai(&self.model, start.elapsed(), false);
+                return Err(e);
             }
         };
 
-        log::debug!("Calling AI API at: {}", url);
+        let guard = StreamGuard {
+            finished: false,
+            start,
+            model: self.model.clone(),
+            metrics: self.metrics.clone(),
+        };
 
-        let mut request = self.client
-            .post(&url)
-            .json(&request_body);
+        Ok(stream::unfold((chunks, guard), |(mut chunks, mut guard)| async move {
+            loop {
+                if guard.finished {
+                    return None;
+                }
+                match chunks.next().await {
+                    Some(Ok(StreamChunk::Delta(delta))) => {
+                        return Some((Ok(delta), (chunks, guard)));
+                    }
+                    Some(Ok(StreamChunk::Usage(usage))) => {
+                        guard.metrics.add_ai_tokens(
+                            &guard.model,
+                            usage.prompt_tokens,
+                            usage.completion_tokens,
+                        );
+                    }
+                    Some(Err(e)) => {
+                        guard.finish(false);
+                        return Some((Err(e), (chunks, guard)));
+                    }
+                    None => {
+                        guard.finish(true);
+                        return None;
+                    }
+                }
+            }
+        })
+        .boxed())
+    }
+}
 
-        // 添加认证头
-        if let Some(api_key) = &self.api_key {
-            request = request.header("Authorization", format!("Bearer {}", api_key));
+/// 没有日志可分析时的结果，按回答语言选择中文或英文
+fn no_logs(language: &str) -> &'static str {
+    match PromptLocale::for_language(language) {
+        PromptLocale::Zh => "没有找到相关的错误日志可以分析。",
+        PromptLocale::En => "No relevant error logs were found to analyze.",
+    }
+}
+
+/// 累积流式输出，正常结束后写入缓存
+fn cache_on_completion(
+    stream: AnalysisStream,
+    cache: Arc<AiAnalysisCache>,
+    key: String,
+    entry: CachedAnalysis,
+) -> AnalysisStream {
+    stream::unfold(
+        (stream, Some((key, entry))),
+        move |(mut stream, mut pending)| {
+            let cache = cache.clone();
+            async move {
+                match stream.next().await {
+                    Some(Ok(delta)) => {
+                        if let Some((_, entry)) = &mut pending {
+                            entry.analysis.push_str(&delta);
+                        }
+                        Some((Ok(delta), (stream, pending)))
+                    }
+                    Some(Err(e)) => Some((Err(e), (stream, None))),
+                    None => {
+                        if let Some((key, mut entry)) = pending {
+                            entry.created_at = Utc::now();
+                            cache.insert(key, entry).await;
+                        }
+                        None
+                    }
+                }
+            }
+        },
+    )
+    .boxed()
+}
+
+/// 流式调用的耗时统计；未正常结束就被丢弃说明客户端已断开
+struct StreamGuard {
+    finished: bool,
+    start: Instant,
+    model: String,
+    metrics: Metrics,
+}
+
+impl StreamGuard {
+    fn finish(&mut self, success: bool) {
+        if !self.finished {
+            self.finished = true;
+            self.metrics
+                .observe_ai(&self.model, self.start.elapsed(), success);
         }
+    }
+}
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| AppError::QuickwitError(e.to_string()))?;
-
-        let status = response.status();
-        let response_text = response.text().await.unwrap_or_default();
-
-        if !status.is_success() {
-            log::error!("AI API error response: {}", response_text);
-            return Err(AppError::QuickwitError(format!(
-                "AI API error: {} - {}",
-                status,
-                response_text
-            )));
+impl Drop for StreamGuard {
+    /// 客户端断开时响应流被丢弃，上游请求随之关闭
+    fn drop(&mut self) {
+        if !self.finished {
+            info!(
+                "AI stream cancelled after {:?}, closing upstream request",
+                self.start.elapsed()
+            );
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AiCacheConfig;
+    use crate::services::ai_provider::fake::FakeProvider;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn force_refresh_bypasses_the_cache_but_stores_the_new_result() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let provider = Arc::new(FakeProvider::new(|_| ChatResponse {
+            content: format!("analysis {}", CALLS.fetch_add(1, Ordering::SeqCst) + 1),
+            tool_calls: Vec::new(),
+            usage: None,
+        }));
+        let store_path = std::env::temp_dir().join(format!(
+            "ai-analyzer-test-{:016x}.json",
+            rand::random::<u64>()
+        ));
+        let cache = AiAnalysisCache::open(
+            &AiCacheConfig {
+                store_path: store_path.display().to_string(),
+                ..Default::default()
+            },
+            Metrics::new(),
+        )
+        .unwrap()
+        .map(Arc::new);
+        let config: AiAnalyzerConfig = serde_json::from_value(serde_json::json!({
+            "base_url": "http://127.0.0.1:1",
+            "api_key": null,
+            "model": "gpt-4o-mini"
+        }))
+        .unwrap();
+        let analyzer = AiAnalyzerClient::new(&config, Metrics::new(), cache)
+            .unwrap()
+            .with_provider(provider.clone());
+
+        let chat = ChatRequest {
+            model: "gpt-4o-mini".to_string(),
+            system: "你是日志分析专家。".to_string(),
+            messages: vec![ChatMessage::user("分析以下日志")],
+            tools: Vec::new(),
+            temperature: 0.3,
+            top_p: 1.0,
+            max_tokens: 1500,
+        };
+        let analyze = |force_refresh| {
+            analyzer.analyze_prepared("trace-1", &chat, AiContextSummary::default(), force_refresh)
+        };
+
+        let first = analyze(false).await.unwrap();
+        assert_eq!(first.analysis, "analysis 1");
+        assert!(first.cached_at.is_none());
+
+        let cached = analyze(false).await.unwrap();
+        assert_eq!(cached.analysis, "analysis 1");
+        assert!(cached.cached_at.is_some());
+        assert_eq!(provider.requests().len(), 1);
+
+        let refreshed = analyze(true).await.unwrap();
+        assert_eq!(refreshed.analysis, "analysis 2");
+        assert!(refreshed.cached_at.is_none());
+        assert_eq!(provider.requests().len(), 2);
 
-        let ai_response: Value = serde_json::from_str(&response_text)
-            .map_err(|e| AppError::QuickwitError(format!("Failed to parse AI response: {}", e)))?;
-
-        // 提取AI响应内容
-        let content = ai_response
-            .get("choices")
-            .and_then(|choices| choices.as_array())
-            .and_then(|choices| choices.first())
-            .and_then(|choice| choice.get("message"))
-            .and_then(|message| message.get("content"))
-            .and_then(|content| content.as_str())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "无法解析AI响应".to_string());
-
-        Ok(content)
+        let cached = analyze(false).await.unwrap();
+        assert_eq!(cached.analysis, "analysis 2");
+        assert!(cached.cached_at.is_some());
+        assert_eq!(provider.requests().len(), 2);
+        std::fs::remove_file(&store_path).unwrap();
     }
 }
@@ -0,0 +1,246 @@
+use crate::config::AiCacheConfig;
+use crate::metrics::Metrics;
+use crate::models::query::AiContextSummary;
+use crate::models::time_range::parse_duration;
+use crate::services::ai_provider::ChatRequest;
+use crate::services::json_store::JsonStore;
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 指标中的缓存名
+const CACHE_NAME: &str = "ai_analysis";
+
+/// 缓存的一次分析结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAnalysis {
+    /// 分析对象，如 trace_id 或查询语句
+    #[serde(alias = "trace_id")]
+    pub subject: String,
+    pub model: String,
+    pub analysis: String,
+    pub context: AiContextSummary,
+    pub created_at: DateTime<Utc>,
+}
+
+/// AI 分析结果缓存，持久化到 JSON 文件，重启后仍然有效。
+///
+/// key 由分析对象、服务商、版本号和最终的请求（模型、提示词、生成参数）计算，
+/// 日志有新增或变化、换模型、换模板或回答语言后都会重新分析
+pub struct AiAnalysisCache {
+    store: JsonStore,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, CachedAnalysis>>,
+    metrics: Metrics,
+}
+
+impl AiAnalysisCache {
+    /// 未启用缓存时返回 None；从文件加载未过期的结果，文件不存在时从空缓存开始
+    pub fn open(config: &AiCacheConfig, metrics: Metrics) -> Result<Option<Self>, String> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let store_path = PathBuf::from(&config.store_path);
+        let mut entries: HashMap<String, CachedAnalysis> = match std::fs::read(&store_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| format!("failed to parse AI cache {}: {}", store_path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(format!(
+                    "failed to read AI cache {}: {}",
+                    store_path.display(),
+                    e
+                ))
+            }
+        };
+
+        let ttl = parse_duration(&config.ttl)?;
+        let now = Utc::now();
+        entries.retain(|_, entry| entry.created_at + ttl > now);
+        metrics.set_cache_entries(CACHE_NAME, entries.len());
+
+        Ok(Some(Self {
+            store: JsonStore::new(store_path),
+            ttl,
+            max_entries: config.max_entries,
+            entries: Mutex::new(entries),
+            metrics,
+        }))
+    }
+
+    pub fn key(subject: &str, provider: &str, prompt_version: &str, request: &ChatRequest) -> String {
+        let params = format!(
+            "{}/{}/{}",
+            request.temperature, request.top_p, request.max_tokens
+        );
+        let mut hasher = Sha256::new();
+        for part in [
+            subject,
+            provider,
+            prompt_version,
+            &request.model,
+            &request.system,
+            &params,
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        for message in &request.messages {
+            hasher.update(format!("{:?}", message.role).as_bytes());
+            hasher.update([0]);
+            hasher.update(message.content.as_bytes());
+            hasher.update([0]);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .take(16)
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// 查找未过期的结果
+    pub fn get(&self, key: &str) -> Option<CachedAnalysis> {
+        let found = self
+            .lock()
+            .get(key)
+            .filter(|entry| entry.created_at + self.ttl > Utc::now())
+            .cloned();
+        self.metrics
+            .observe_cache(CACHE_NAME, if found.is_some() { "hit" } else { "miss" });
+        found
+    }
+
+    pub fn observe_bypass(&self) {
+        self.metrics.observe_cache(CACHE_NAME, "bypass");
+    }
+
+    /// 写入结果并持久化；写文件失败只记录告警，不影响本次分析的返回
+    pub async fn insert(&self, key: String, entry: CachedAnalysis) {
+        let snapshot = {
+            let mut entries = self.lock();
+            let now = Utc::now();
+            entries.retain(|_, entry| entry.created_at + self.ttl > now);
+            entries.insert(key, entry);
+
+            // 超出上限时淘汰最早的结果
+            if entries.len() > self.max_entries {
+                let mut by_age: Vec<(DateTime<Utc>, String)> = entries
+                    .iter()
+                    .map(|(key, entry)| (entry.created_at, key.clone()))
+                    .collect();
+                by_age.sort();
+                let excess = entries.len() - self.max_entries;
+                for (_, key) in by_age.into_iter().take(excess) {
+                    entries.remove(&key);
+                }
+            }
+            self.metrics.set_cache_entries(CACHE_NAME, entries.len());
+            self.store.snapshot(&*entries)
+        };
+
+        let result = match snapshot {
+            Ok(snapshot) => self.store.write(snapshot).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            warn!(
+                "Failed to write AI cache {}: {}",
+                self.store.path().display(),
+                e
+            );
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CachedAnalysis>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ai_provider::ChatMessage;
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "gpt-4o-mini".to_string(),
+            system: "你是日志分析专家。".to_string(),
+            messages: vec![ChatMessage::user("分析以下日志")],
+            tools: Vec::new(),
+            temperature: 0.3,
+            top_p: 1.0,
+            max_tokens: 1500,
+        }
+    }
+
+    fn config() -> AiCacheConfig {
+        let store_path =
+            std::env::temp_dir().join(format!("ai-cache-test-{:016x}.json", rand::random::<u64>()));
+        AiCacheConfig {
+            store_path: store_path.display().to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn entry(analysis: &str) -> CachedAnalysis {
+        CachedAnalysis {
+            subject: "trace-1".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            analysis: analysis.to_string(),
+            context: AiContextSummary::default(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn key_changes_with_template_language_and_model() {
+        let key = |request: &ChatRequest| AiAnalysisCache::key("trace-1", "openai", "3", request);
+        let base = key(&request());
+        assert_eq!(base, key(&request()));
+
+        // 模板决定 system 与首轮消息，回答语言写在 system 中
+        let mut template = request();
+        template.messages = vec![ChatMessage::user("按详细模板分析以下日志")];
+        let mut language = request();
+        language.system = "你是日志分析专家。请用English回答。".to_string();
+        let mut model = request();
+        model.model = "gpt-4o".to_string();
+        let mut params = request();
+        params.temperature = 0.7;
+
+        for changed in [template, language, model, params] {
+            assert_ne!(base, key(&changed));
+        }
+        assert_ne!(
+            base,
+            AiAnalysisCache::key("trace-1", "anthropic", "3", &request())
+        );
+        assert_ne!(
+            base,
+            AiAnalysisCache::key("trace-1", "openai", "4", &request())
+        );
+    }
+
+    #[tokio::test]
+    async fn insert_persists_the_latest_entries() {
+        let config = config();
+        let cache = AiAnalysisCache::open(&config, Metrics::new())
+            .unwrap()
+            .unwrap();
+        cache.insert("a".to_string(), entry("first")).await;
+        cache.insert("a".to_string(), entry("second")).await;
+
+        let reopened = AiAnalysisCache::open(&config, Metrics::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(reopened.get("a").unwrap().analysis, "second");
+        std::fs::remove_file(&config.store_path).unwrap();
+    }
+}
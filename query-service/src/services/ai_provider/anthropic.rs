@@ -0,0 +1,406 @@
+use super::{
+    json_body, line_stream, parse_json_line, send, send_error, sse_data, status_error, AiProvider,
+    ChatRequest, ChatResponse, ChatRole, ChunkStream, LineEvent, StreamChunk, TokenUsage, ToolCall,
+};
+use crate::config::AiProviderKind;
+use crate::error::AppError;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration as StdDuration;
+
+/// Messages API 版本
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Anthropic Messages API
+pub struct AnthropicProvider {
+    /// API 根路径，以 /v1 结尾
+    api_base: String,
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl AnthropicProvider {
+    pub fn new(base_url: &str, api_key: Option<String>, client: Client) -> Self {
+        let base = base_url.trim_end_matches('/');
+        let api_base = if base.ends_with("/v1") {
+            base.to_string()
+        } else {
+            format!("{}/v1", base)
+        };
+
+        Self {
+            api_base,
+            api_key,
+            client,
+        }
+    }
+
+    fn post(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        // 较新的模型不允许同时指定 temperature 与 top_p，这里只传 temperature
+        let mut body = json!({
+            "model": request.model,
+            "system": request.system,
+            "messages": messages(request),
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature
+        });
+        if !request.tools.is_empty() {
+            body["tools"] = request
+                .tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "name": tool.name,
+                        "description": tool.description,
+                        "input_schema": tool.parameters
+                    })
+                })
+                .collect();
+        }
+        if stream {
+            body["stream"] = json!(true);
+        }
+
+        let url = format!("{}/messages", self.api_base);
+        log::debug!("Calling AI API at: {}", url);
+        self.authorize(self.client.post(&url).json(&body))
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request = request.header("anthropic-version", ANTHROPIC_VERSION);
+        match &self.api_key {
+            Some(api_key) => request.header("x-api-key", api_key),
+            None => request,
+        }
+    }
+}
+
+impl AiProvider for AnthropicProvider {
+    fn kind(&self) -> AiProviderKind {
+        AiProviderKind::Anthropic
+    }
+
+    fn complete<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChatResponse, AppError>> {
+        async move {
+            let response = send(self.post(request, false), false).await?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            let body = json_body(response).await?;
+
+            // 回复由多个内容块组成：文本块拼接为内容，tool_use 块为工具调用
+            let blocks = body["content"]
+                .as_array()
+                .ok_or_else(|| AppError::AiError("AI response contains no content".to_string()))?;
+            let content: String = blocks
+                .iter()
+                .filter(|block| block["type"] == "text")
+                .filter_map(|block| block["text"].as_str())
+                .collect();
+            let tool_calls = blocks
+                .iter()
+                .filter(|block| block["type"] == "tool_use")
+                .filter_map(|block| {
+                    Some(ToolCall {
+                        id: block["id"].as_str()?.to_string(),
+                        name: block["name"].as_str()?.to_string(),
+                        arguments: block["input"].clone(),
+                    })
+                })
+                .collect();
+
+            let usage = &body["usage"];
+            Ok(ChatResponse {
+                content,
+                tool_calls,
+                usage: usage.is_object().then(|| TokenUsage {
+                    prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+                    completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+                }),
+            })
+        }
+        .boxed()
+    }
+
+    fn complete_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChunkStream, AppError>> {
+        async move {
+            let response = send(
+                self.post(request, true)
+                    .header("Accept", "text/event-stream"),
+                true,
+            )
+            .await?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            Ok(line_stream(response, parse_line))
+        }
+        .boxed()
+    }
+
+    fn probe(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        async move {
+            // 请求模型列表，不消耗 token
+            let url = format!("{}/models", self.api_base);
+            let response = self
+                .authorize(self.client.get(&url).timeout(StdDuration::from_secs(10)))
+                .send()
+                .await
+                .map_err(send_error)?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// 转换为 Messages API 的消息：工具调用为 assistant 的 `tool_use` 块，
+/// 工具结果为 user 的 `tool_result` 块。同一方的连续消息合并为一条
+fn messages(request: &ChatRequest) -> Vec<Value> {
+    let mut messages: Vec<(&'static str, Vec<Value>)> = Vec::new();
+    for message in &request.messages {
+        let (role, blocks) = match message.role {
+            ChatRole::User => ("user", text_block(&message.content)),
+            ChatRole::Assistant => {
+                let mut blocks = text_block(&message.content);
+                blocks.extend(message.tool_calls.iter().map(|call| {
+                    json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.name,
+                        "input": call.arguments
+                    })
+                }));
+                ("assistant", blocks)
+            }
+            ChatRole::Tool => (
+                "user",
+                vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.tool_call_id,
+                    "content": message.content
+                })],
+            ),
+        };
+        match messages.last_mut() {
+            Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+            _ => messages.push((role, blocks)),
+        }
+    }
+
+    messages
+        .into_iter()
+        .map(|(role, blocks)| match blocks.as_slice() {
+            // 只有一段文本时沿用字符串形式
+            [block] if block["type"] == "text" => json!({ "role": role, "content": block["text"] }),
+            _ => json!({ "role": role, "content": blocks }),
+        })
+        .collect()
+}
+
+fn text_block(text: &str) -> Vec<Value> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({ "type": "text", "text": text })]
+    }
+}
+
+/// SSE：事件类型同时出现在 `event:` 行和 data 的 `type` 字段中，这里只看 data。
+/// 输入 token 数在 `message_start` 中返回，输出 token 数在 `message_delta` 中返回
+fn parse_line(line: &str) -> Vec<LineEvent> {
+    let Some(event) = sse_data(line).and_then(parse_json_line) else {
+        return Vec::new();
+    };
+
+    match event["type"].as_str() {
+        Some("content_block_delta") => match event["delta"]["text"].as_str() {
+            Some(text) if !text.is_empty() => {
+                vec![LineEvent::Chunk(StreamChunk::Delta(text.to_string()))]
+            }
+            _ => Vec::new(),
+        },
+        Some("message_start") => {
+            let usage = &event["message"]["usage"];
+            vec![LineEvent::Chunk(StreamChunk::Usage(TokenUsage {
+                prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+                completion_tokens: 0,
+            }))]
+        }
+        Some("message_delta") => {
+            let usage = &event["usage"];
+            vec![LineEvent::Chunk(StreamChunk::Usage(TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+            }))]
+        }
+        Some("message_stop") => vec![LineEvent::Done],
+        Some("error") => vec![LineEvent::Error(
+            error_message(&event).unwrap_or_else(|| event.to_string()),
+        )],
+        _ => Vec::new(),
+    }
+}
+
+/// 错误响应：`{"type": "error", "error": {"type": "overloaded_error", "message": "..."}}`
+fn error_message(body: &Value) -> Option<String> {
+    let error = body.get("error")?;
+    let message = error["message"].as_str()?;
+    Some(match error["type"].as_str() {
+        Some(error_type) => format!("{}: {}", error_type, message),
+        None => message.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ai_provider::{ChatMessage, ToolCall};
+
+    fn request(messages: Vec<ChatMessage>) -> ChatRequest {
+        ChatRequest {
+            model: "claude".to_string(),
+            system: String::new(),
+            messages,
+            tools: Vec::new(),
+            temperature: 0.0,
+            top_p: 1.0,
+            max_tokens: 1024,
+        }
+    }
+
+    fn search_call(id: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: "search_logs".to_string(),
+            arguments: json!({ "query": "level:ERROR" }),
+        }
+    }
+
+    #[test]
+    fn parse_line_maps_sse_events() {
+        let cases: Vec<(&str, Vec<LineEvent>)> = vec![
+            ("event: content_block_delta", vec![]),
+            (
+                r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+                vec![LineEvent::Chunk(StreamChunk::Delta("Hi".to_string()))],
+            ),
+            (
+                r#"data: {"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"usage":{"input_tokens":25,"output_tokens":1}}}"#,
+                vec![LineEvent::Chunk(StreamChunk::Usage(TokenUsage {
+                    prompt_tokens: 25,
+                    completion_tokens: 0,
+                }))],
+            ),
+            (
+                r#"data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":15}}"#,
+                vec![LineEvent::Chunk(StreamChunk::Usage(TokenUsage {
+                    prompt_tokens: 0,
+                    completion_tokens: 15,
+                }))],
+            ),
+            (r#"data: {"type":"ping"}"#, vec![]),
+            (r#"data: {"type":"message_stop"}"#, vec![LineEvent::Done]),
+            (
+                r#"data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+                vec![LineEvent::Error("overloaded_error: Overloaded".to_string())],
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_line(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn messages_merge_tool_results_into_one_user_turn() {
+        let messages = messages(&request(vec![
+            ChatMessage::user("为什么报错？"),
+            ChatMessage::assistant_tool_calls(
+                "",
+                vec![search_call("toolu_1"), search_call("toolu_2")],
+            ),
+            ChatMessage::tool_result("toolu_1", "3 hits"),
+            ChatMessage::tool_result("toolu_2", "0 hits"),
+            ChatMessage::assistant("数据库连接超时"),
+        ]));
+
+        assert_eq!(messages.len(), 4);
+        // 只有一段文本时为字符串
+        assert_eq!(
+            messages[0],
+            json!({ "role": "user", "content": "为什么报错？" })
+        );
+        // 空的说明文字不生成文本块
+        assert_eq!(
+            messages[1],
+            json!({
+                "role": "assistant",
+                "content": [
+                    { "type": "tool_use", "id": "toolu_1", "name": "search_logs", "input": { "query": "level:ERROR" } },
+                    { "type": "tool_use", "id": "toolu_2", "name": "search_logs", "input": { "query": "level:ERROR" } }
+                ]
+            })
+        );
+        assert_eq!(
+            messages[2],
+            json!({
+                "role": "user",
+                "content": [
+                    { "type": "tool_result", "tool_use_id": "toolu_1", "content": "3 hits" },
+                    { "type": "tool_result", "tool_use_id": "toolu_2", "content": "0 hits" }
+                ]
+            })
+        );
+        assert_eq!(
+            messages[3],
+            json!({ "role": "assistant", "content": "数据库连接超时" })
+        );
+    }
+
+    #[test]
+    fn messages_merge_consecutive_user_text() {
+        let messages = messages(&request(vec![
+            ChatMessage::user("第一段"),
+            ChatMessage::user("第二段"),
+        ]));
+        assert_eq!(
+            messages,
+            vec![json!({
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "第一段" },
+                    { "type": "text", "text": "第二段" }
+                ]
+            })]
+        );
+    }
+
+    #[test]
+    fn error_message_includes_type() {
+        let cases = [
+            (
+                json!({ "type": "error", "error": { "type": "invalid_request_error", "message": "max_tokens too large" } }),
+                Some("invalid_request_error: max_tokens too large"),
+            ),
+            (
+                json!({ "error": { "message": "Overloaded" } }),
+                Some("Overloaded"),
+            ),
+            (json!({ "error": "Overloaded" }), None),
+            (json!({ "type": "message" }), None),
+        ];
+        for (body, expected) in cases {
+            assert_eq!(error_message(&body).as_deref(), expected, "{}", body);
+        }
+    }
+}
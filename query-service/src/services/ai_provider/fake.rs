@@ -0,0 +1,53 @@
+//! 测试用的服务商：按脚本回复，不发送请求
+
+use super::{AiProvider, ChatRequest, ChatResponse, ChunkStream};
+use crate::config::AiProviderKind;
+use crate::error::AppError;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use std::sync::Mutex;
+
+/// 按脚本回复的服务商，记录收到的请求
+pub struct FakeProvider {
+    reply: fn(&ChatRequest) -> ChatResponse,
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+impl FakeProvider {
+    pub fn new(reply: fn(&ChatRequest) -> ChatResponse) -> Self {
+        Self {
+            reply,
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl AiProvider for FakeProvider {
+    fn kind(&self) -> AiProviderKind {
+        AiProviderKind::OpenAi
+    }
+
+    fn complete<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChatResponse, AppError>> {
+        self.requests.lock().unwrap().push(request.clone());
+        let response = (self.reply)(request);
+        async move { Ok(response) }.boxed()
+    }
+
+    fn complete_stream<'a>(
+        &'a self,
+        _request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChunkStream, AppError>> {
+        async { Err(AppError::AiError("streaming is not scripted".to_string())) }.boxed()
+    }
+
+    fn probe(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        async { Ok(()) }.boxed()
+    }
+}
@@ -0,0 +1,385 @@
+//! AI 服务接口抽象：不同服务商的鉴权、请求/响应格式与错误处理各自实现
+
+mod anthropic;
+#[cfg(test)]
+pub(crate) mod fake;
+mod ollama;
+mod openai;
+
+pub use anthropic::AnthropicProvider;
+pub use ollama::OllamaProvider;
+pub use openai::OpenAiProvider;
+
+use crate::config::{AiAnalyzerConfig, AiProviderKind};
+use crate::error::AppError;
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+/// 非流式调用的总超时，包括读取完整回复
+const COMPLETE_TIMEOUT: StdDuration = StdDuration::from_secs(180);
+
+/// 流式调用等待响应头以及两段数据之间的最长间隔；持续有输出时流的总时长不受限制
+const STREAM_IDLE_TIMEOUT: StdDuration = StdDuration::from_secs(120);
+
+/// 对话消息的发送方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+    /// 工具的执行结果
+    Tool,
+}
+
+/// 对话中的一条消息
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+    /// assistant 消息中请求的工具调用
+    pub tool_calls: Vec<ToolCall>,
+    /// tool 消息对应的工具调用 ID
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::User, content.into())
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(ChatRole::Assistant, content.into())
+    }
+
+    /// 模型请求调用工具的回复，`content` 为调用前的说明文字（可能为空）
+    pub fn assistant_tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            tool_calls,
+            ..Self::new(ChatRole::Assistant, content.into())
+        }
+    }
+
+    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(ChatRole::Tool, content.into())
+        }
+    }
+
+    fn new(role: ChatRole, content: String) -> Self {
+        Self {
+            role,
+            content,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+/// 提供给模型调用的工具，`parameters` 为参数的 JSON Schema
+#[derive(Debug, Clone)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+/// 模型请求的一次工具调用
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// 参数对象；无法解析为 JSON 时为原始字符串
+    pub arguments: Value,
+}
+
+/// 一次对话请求：系统提示词加按顺序排列的历史消息，最后一条为用户或工具消息
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub model: String,
+    pub system: String,
+    pub messages: Vec<ChatMessage>,
+    /// 可调用的工具，为空时不发送工具定义
+    pub tools: Vec<ToolSpec>,
+    pub temperature: f32,
+    pub top_p: f32,
+    pub max_tokens: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatResponse {
+    pub content: String,
+    /// 模型请求的工具调用，为空时 `content` 即最终回复
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: Option<TokenUsage>,
+}
+
+/// 流式响应中的一段输出
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamChunk {
+    /// 生成内容增量
+    Delta(String),
+    /// token 用量；部分服务商分多次返回，需要累加
+    Usage(TokenUsage),
+}
+
+pub type ChunkStream = BoxStream<'static, Result<StreamChunk, AppError>>;
+
+/// AI 服务商接口。实现负责拼接 URL、鉴权头、请求体映射和响应/错误解析，
+/// 指标与日志由调用方统一记录
+pub trait AiProvider: Send + Sync {
+    fn kind(&self) -> AiProviderKind;
+
+    /// 非流式调用，等待完整回复
+    fn complete<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChatResponse, AppError>>;
+
+    /// 流式调用：上游返回成功状态后即返回，丢弃返回的流即取消上游请求
+    fn complete_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChunkStream, AppError>>;
+
+    /// 探测服务是否可达（不消耗 token）
+    fn probe(&self) -> BoxFuture<'_, Result<(), AppError>>;
+}
+
+/// 按配置的 `provider` 创建服务商实现
+pub fn from_config(config: &AiAnalyzerConfig, client: Client) -> Arc<dyn AiProvider> {
+    // 配置文件中留空的 api_key 视为未配置
+    let api_key = config
+        .api_key
+        .clone()
+        .filter(|api_key| !api_key.trim().is_empty());
+
+    match config.provider {
+        AiProviderKind::OpenAi => Arc::new(OpenAiProvider::new(&config.base_url, api_key, client)),
+        AiProviderKind::Anthropic => {
+            Arc::new(AnthropicProvider::new(&config.base_url, api_key, client))
+        }
+        AiProviderKind::Ollama => Arc::new(OllamaProvider::new(&config.base_url, api_key, client)),
+    }
+}
+
+/// 以 system 消息开头的消息列表（OpenAI、Ollama 格式），`tool_call` 负责工具调用的格式
+pub(crate) fn messages_with_system(request: &ChatRequest, tool_call: fn(&ToolCall) -> Value) -> Value {
+    let mut messages = vec![json!({ "role": "system", "content": request.system })];
+    messages.extend(request.messages.iter().map(|message| {
+        let mut value = json!({ "role": message.role, "content": message.content });
+        if !message.tool_calls.is_empty() {
+            value["tool_calls"] = message.tool_calls.iter().map(tool_call).collect();
+        }
+        if let Some(tool_call_id) = &message.tool_call_id {
+            value["tool_call_id"] = json!(tool_call_id);
+        }
+        value
+    }));
+    Value::Array(messages)
+}
+
+/// function 形式的工具定义（OpenAI、Ollama 格式）
+pub(crate) fn function_tools(request: &ChatRequest) -> Value {
+    request
+        .tools
+        .iter()
+        .map(|tool| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters
+                }
+            })
+        })
+        .collect()
+}
+
+/// 解析流式响应中的一行
+#[derive(Debug, PartialEq)]
+pub(crate) enum LineEvent {
+    Chunk(StreamChunk),
+    /// 服务商声明的结束标记，之后的内容忽略
+    Done,
+    /// 流中途返回的错误
+    Error(String),
+}
+
+/// 把逐行输出的响应体（SSE 或 NDJSON）转为输出流，`parse_line` 负责单行的格式解析
+pub(crate) fn line_stream(
+    response: reqwest::Response,
+    parse_line: fn(&str) -> Vec<LineEvent>,
+) -> ChunkStream {
+    let state = LineState {
+        body: response.bytes_stream().boxed(),
+        buffer: Vec::new(),
+        pending: VecDeque::new(),
+        parse_line,
+        finished: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.pending.pop_front() {
+                return Some((item, state));
+            }
+            if state.finished {
+                return None;
+            }
+
+            let next = match tokio::time::timeout(STREAM_IDLE_TIMEOUT, state.body.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    state.finished = true;
+                    let error = AppError::AiError(format!(
+                        "AI API stream timed out: no data for {}s",
+                        STREAM_IDLE_TIMEOUT.as_secs()
+                    ));
+                    return Some((Err(error), state));
+                }
+            };
+            match next {
+                Some(Ok(chunk)) => {
+                    state.buffer.extend_from_slice(&chunk);
+                    state.drain_lines();
+                }
+                Some(Err(e)) => {
+                    state.finished = true;
+                    return Some((Err(send_error(e)), state));
+                }
+                None => {
+                    // 处理末尾没有换行的最后一行
+                    state.buffer.push(b'\n');
+                    state.drain_lines();
+                    state.finished = true;
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+struct LineState {
+    body: BoxStream<'static, reqwest::Result<actix_web::web::Bytes>>,
+    /// 尚未读到换行的半行数据
+    buffer: Vec<u8>,
+    /// 已解析、待产出的输出
+    pending: VecDeque<Result<StreamChunk, AppError>>,
+    parse_line: fn(&str) -> Vec<LineEvent>,
+    finished: bool,
+}
+
+impl LineState {
+    fn drain_lines(&mut self) {
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            if self.finished {
+                continue;
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            for event in (self.parse_line)(line) {
+                match event {
+                    LineEvent::Chunk(chunk) => self.pending.push_back(Ok(chunk)),
+                    LineEvent::Done => self.finished = true,
+                    LineEvent::Error(message) => {
+                        self.pending
+                            .push_back(Err(AppError::AiError(format!("AI API error: {}", message))));
+                        self.finished = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// SSE 的 `data:` 行内容，其他行（`event:`、注释等）返回 None
+pub(crate) fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim)
+}
+
+/// 流式响应中的一行 JSON；无法解析的行跳过
+pub(crate) fn parse_json_line(data: &str) -> Option<Value> {
+    match serde_json::from_str(data) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::debug!("Skipping unparsable AI stream line: {}", e);
+            None
+        }
+    }
+}
+
+/// 发送请求。非流式请求限制总时长；流式请求只限制等待响应头的时长，
+/// 读取时由 `line_stream` 限制两段数据之间的间隔，生成时间长的回复不会被中断
+pub(crate) async fn send(
+    request: reqwest::RequestBuilder,
+    stream: bool,
+) -> Result<reqwest::Response, AppError> {
+    if !stream {
+        return request
+            .timeout(COMPLETE_TIMEOUT)
+            .send()
+            .await
+            .map_err(send_error);
+    }
+    tokio::time::timeout(STREAM_IDLE_TIMEOUT, request.send())
+        .await
+        .map_err(|_| {
+            AppError::AiError(format!(
+                "AI API request timed out: no response within {}s",
+                STREAM_IDLE_TIMEOUT.as_secs()
+            ))
+        })?
+        .map_err(send_error)
+}
+
+/// 请求发送或读取失败
+pub(crate) fn send_error(e: reqwest::Error) -> AppError {
+    if e.is_timeout() {
+        AppError::AiError(format!("AI API request timed out: {}", e))
+    } else {
+        AppError::AiError(e.to_string())
+    }
+}
+
+/// 非 2xx 响应转为错误，`error_message` 从服务商的错误响应体中提取可读的错误信息
+pub(crate) async fn status_error(
+    response: reqwest::Response,
+    error_message: fn(&Value) -> Option<String>,
+) -> AppError {
+    let status = response.status();
+    let response_text = response.text().await.unwrap_or_default();
+    log::error!("AI API error response: {}", response_text);
+
+    let message = serde_json::from_str::<Value>(&response_text)
+        .ok()
+        .as_ref()
+        .and_then(error_message)
+        .unwrap_or(response_text);
+    AppError::AiError(format!("AI API error: {} - {}", status, message))
+}
+
+/// 解析成功响应的 JSON 响应体
+pub(crate) async fn json_body(response: reqwest::Response) -> Result<Value, AppError> {
+    let response_text = response.text().await.map_err(send_error)?;
+    serde_json::from_str::<Value>(&response_text)
+        .map_err(|e| AppError::AiError(format!("Failed to parse AI response: {}", e)))
+}
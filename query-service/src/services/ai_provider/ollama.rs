@@ -0,0 +1,259 @@
+use super::{
+    function_tools, json_body, line_stream, messages_with_system, parse_json_line, send,
+    send_error, status_error, AiProvider, ChatRequest, ChatResponse, ChunkStream, LineEvent,
+    StreamChunk, TokenUsage, ToolCall,
+};
+use crate::config::AiProviderKind;
+use crate::error::AppError;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration as StdDuration;
+
+/// Ollama 原生接口（/api/chat），本地部署通常不需要鉴权
+pub struct OllamaProvider {
+    base_url: String,
+    /// 经反向代理访问时可能需要的 Bearer token
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: &str, api_key: Option<String>, client: Client) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            client,
+        }
+    }
+
+    fn post(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages_with_system(request, tool_call),
+            "stream": stream,
+            "options": {
+                "temperature": request.temperature,
+                "top_p": request.top_p,
+                "num_predict": request.max_tokens
+            }
+        });
+        if !request.tools.is_empty() {
+            body["tools"] = function_tools(request);
+        }
+
+        let url = format!("{}/api/chat", self.base_url);
+        log::debug!("Calling AI API at: {}", url);
+        self.authorize(self.client.post(&url).json(&body))
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+}
+
+impl AiProvider for OllamaProvider {
+    fn kind(&self) -> AiProviderKind {
+        AiProviderKind::Ollama
+    }
+
+    fn complete<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChatResponse, AppError>> {
+        async move {
+            let response = send(self.post(request, false), false).await?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            let body = json_body(response).await?;
+            if let Some(message) = error_message(&body) {
+                return Err(AppError::AiError(format!("AI API error: {}", message)));
+            }
+
+            let content = body["message"]["content"]
+                .as_str()
+                .ok_or_else(|| {
+                    AppError::AiError("AI response contains no message content".to_string())
+                })?
+                .to_string();
+            // 工具调用没有 ID，按顺序编号
+            let tool_calls = body["message"]["tool_calls"]
+                .as_array()
+                .map(|calls| {
+                    calls
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(index, call)| {
+                            Some(ToolCall {
+                                id: format!("call_{}", index),
+                                name: call["function"]["name"].as_str()?.to_string(),
+                                arguments: call["function"]["arguments"].clone(),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Ok(ChatResponse {
+                content,
+                tool_calls,
+                usage: usage(&body),
+            })
+        }
+        .boxed()
+    }
+
+    fn complete_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChunkStream, AppError>> {
+        async move {
+            let response = send(self.post(request, true), true).await?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            Ok(line_stream(response, parse_line))
+        }
+        .boxed()
+    }
+
+    fn probe(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        async move {
+            // 列出本地模型，不触发模型加载
+            let url = format!("{}/api/tags", self.base_url);
+            let response = self
+                .authorize(self.client.get(&url).timeout(StdDuration::from_secs(10)))
+                .send()
+                .await
+                .map_err(send_error)?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// 工具调用：`{"function": {"name", "arguments"}}`，arguments 为对象
+fn tool_call(call: &ToolCall) -> Value {
+    json!({ "function": { "name": call.name, "arguments": call.arguments } })
+}
+
+/// NDJSON：每行一个 `{"message": {"content": "..."}, "done": false}`，
+/// 最后一行 `done: true` 并带有 token 统计
+fn parse_line(line: &str) -> Vec<LineEvent> {
+    let Some(chunk) = parse_json_line(line) else {
+        return Vec::new();
+    };
+    if let Some(message) = error_message(&chunk) {
+        return vec![LineEvent::Error(message)];
+    }
+
+    let mut events = Vec::new();
+    if let Some(content) = chunk["message"]["content"].as_str() {
+        if !content.is_empty() {
+            events.push(LineEvent::Chunk(StreamChunk::Delta(content.to_string())));
+        }
+    }
+    if chunk["done"].as_bool() == Some(true) {
+        if let Some(usage) = usage(&chunk) {
+            events.push(LineEvent::Chunk(StreamChunk::Usage(usage)));
+        }
+        events.push(LineEvent::Done);
+    }
+    events
+}
+
+/// token 统计在响应顶层的 `prompt_eval_count` / `eval_count`
+fn usage(body: &Value) -> Option<TokenUsage> {
+    if body.get("prompt_eval_count").is_none() && body.get("eval_count").is_none() {
+        return None;
+    }
+    Some(TokenUsage {
+        prompt_tokens: body["prompt_eval_count"].as_u64().unwrap_or(0),
+        completion_tokens: body["eval_count"].as_u64().unwrap_or(0),
+    })
+}
+
+/// 错误响应：`{"error": "model 'xxx' not found"}`
+fn error_message(body: &Value) -> Option<String> {
+    body.get("error")?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_maps_ndjson_events() {
+        let cases: Vec<(&str, Vec<LineEvent>)> = vec![
+            (
+                r#"{"model":"qwen2.5","created_at":"2024-05-01T08:00:00Z","message":{"role":"assistant","content":"Hi"},"done":false}"#,
+                vec![LineEvent::Chunk(StreamChunk::Delta("Hi".to_string()))],
+            ),
+            (
+                r#"{"model":"qwen2.5","created_at":"2024-05-01T08:00:01Z","message":{"role":"assistant","content":""},"done_reason":"stop","done":true,"total_duration":1200000000,"prompt_eval_count":26,"eval_count":12}"#,
+                vec![
+                    LineEvent::Chunk(StreamChunk::Usage(TokenUsage {
+                        prompt_tokens: 26,
+                        completion_tokens: 12,
+                    })),
+                    LineEvent::Done,
+                ],
+            ),
+            (
+                r#"{"model":"qwen2.5","message":{"role":"assistant","content":""},"done":true}"#,
+                vec![LineEvent::Done],
+            ),
+            (
+                r#"{"error":"model 'qwen9' not found"}"#,
+                vec![LineEvent::Error("model 'qwen9' not found".to_string())],
+            ),
+            ("", vec![]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_line(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn usage_requires_eval_counts() {
+        assert_eq!(
+            usage(&json!({ "done": true, "eval_count": 7 })),
+            Some(TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 7
+            })
+        );
+        assert_eq!(usage(&json!({ "done": true })), None);
+    }
+
+    #[test]
+    fn error_message_takes_string_errors() {
+        assert_eq!(
+            error_message(&json!({ "error": "model 'qwen9' not found" })).as_deref(),
+            Some("model 'qwen9' not found")
+        );
+        assert_eq!(error_message(&json!({ "error": { "message": "x" } })), None);
+        assert_eq!(error_message(&json!({ "done": false })), None);
+    }
+
+    #[test]
+    fn tool_call_keeps_object_arguments() {
+        let value = tool_call(&ToolCall {
+            id: "call_0".to_string(),
+            name: "search_logs".to_string(),
+            arguments: json!({ "query": "level:ERROR" }),
+        });
+        assert_eq!(
+            value,
+            json!({ "function": { "name": "search_logs", "arguments": { "query": "level:ERROR" } } })
+        );
+    }
+}
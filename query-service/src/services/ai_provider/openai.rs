@@ -0,0 +1,322 @@
+use super::{
+    function_tools, json_body, line_stream, messages_with_system, parse_json_line, send,
+    send_error, sse_data, status_error, AiProvider, ChatRequest, ChatResponse, ChunkStream,
+    LineEvent, StreamChunk, TokenUsage, ToolCall,
+};
+use crate::config::AiProviderKind;
+use crate::error::AppError;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration as StdDuration;
+
+/// OpenAI Chat Completions 及兼容接口（OpenRouter、vLLM、LM Studio 等）
+pub struct OpenAiProvider {
+    /// API 根路径，以 /v1 结尾
+    api_base: String,
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl OpenAiProvider {
+    pub fn new(base_url: &str, api_key: Option<String>, client: Client) -> Self {
+        // base_url 可以是服务根地址（https://api.openai.com）或已包含 /v1（https://openrouter.ai/api/v1）
+        let base = base_url.trim_end_matches('/');
+        let api_base = if base.ends_with("/v1") {
+            base.to_string()
+        } else {
+            format!("{}/v1", base)
+        };
+
+        Self {
+            api_base,
+            api_key,
+            client,
+        }
+    }
+
+    fn post(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages_with_system(request, tool_call),
+            "temperature": request.temperature,
+            "max_tokens": request.max_tokens,
+            "top_p": request.top_p
+        });
+        if !request.tools.is_empty() {
+            body["tools"] = function_tools(request);
+        }
+        if stream {
+            body["stream"] = json!(true);
+            // 要求在最后一个 chunk 中返回 token 用量
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        let url = format!("{}/chat/completions", self.api_base);
+        log::debug!("Calling AI API at: {}", url);
+        self.authorize(self.client.post(&url).json(&body))
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+}
+
+impl AiProvider for OpenAiProvider {
+    fn kind(&self) -> AiProviderKind {
+        AiProviderKind::OpenAi
+    }
+
+    fn complete<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChatResponse, AppError>> {
+        async move {
+            let response = send(self.post(request, false), false).await?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            let body = json_body(response).await?;
+
+            let message = &body["choices"][0]["message"];
+            let tool_calls: Vec<ToolCall> = message["tool_calls"]
+                .as_array()
+                .map(|calls| calls.iter().filter_map(parse_tool_call).collect())
+                .unwrap_or_default();
+            // 请求工具调用时 content 可能为 null
+            let content = match message["content"].as_str() {
+                Some(content) => content.to_string(),
+                None if !tool_calls.is_empty() => String::new(),
+                None => {
+                    return Err(AppError::AiError(
+                        "AI response contains no message content".to_string(),
+                    ))
+                }
+            };
+
+            Ok(ChatResponse {
+                content,
+                tool_calls,
+                usage: usage(&body["usage"]),
+            })
+        }
+        .boxed()
+    }
+
+    fn complete_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+    ) -> BoxFuture<'a, Result<ChunkStream, AppError>> {
+        async move {
+            let response = send(
+                self.post(request, true)
+                    .header("Accept", "text/event-stream"),
+                true,
+            )
+            .await?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            Ok(line_stream(response, parse_line))
+        }
+        .boxed()
+    }
+
+    fn probe(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        async move {
+            // 请求模型列表，不消耗 token
+            let url = format!("{}/models", self.api_base);
+            let response = self
+                .authorize(self.client.get(&url).timeout(StdDuration::from_secs(10)))
+                .send()
+                .await
+                .map_err(send_error)?;
+            if !response.status().is_success() {
+                return Err(status_error(response, error_message).await);
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// SSE：`data: {chunk}`，以 `data: [DONE]` 结束
+fn parse_line(line: &str) -> Vec<LineEvent> {
+    let Some(data) = sse_data(line) else {
+        return Vec::new();
+    };
+    if data == "[DONE]" {
+        return vec![LineEvent::Done];
+    }
+    let Some(chunk) = parse_json_line(data) else {
+        return Vec::new();
+    };
+    if let Some(message) = error_message(&chunk) {
+        return vec![LineEvent::Error(message)];
+    }
+
+    let mut events = Vec::new();
+    if let Some(delta) = chunk["choices"][0]["delta"]["content"].as_str() {
+        if !delta.is_empty() {
+            events.push(LineEvent::Chunk(StreamChunk::Delta(delta.to_string())));
+        }
+    }
+    if let Some(usage) = usage(&chunk["usage"]) {
+        events.push(LineEvent::Chunk(StreamChunk::Usage(usage)));
+    }
+    events
+}
+
+/// 工具调用：`{"id", "type": "function", "function": {"name", "arguments"}}`，arguments 为 JSON 字符串
+fn tool_call(call: &ToolCall) -> Value {
+    json!({
+        "id": call.id,
+        "type": "function",
+        "function": { "name": call.name, "arguments": call.arguments.to_string() }
+    })
+}
+
+fn parse_tool_call(value: &Value) -> Option<ToolCall> {
+    let function = &value["function"];
+    let arguments = match &function["arguments"] {
+        Value::String(text) => {
+            serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.clone()))
+        }
+        arguments => arguments.clone(),
+    };
+    Some(ToolCall {
+        id: value["id"].as_str()?.to_string(),
+        name: function["name"].as_str()?.to_string(),
+        arguments,
+    })
+}
+
+fn usage(value: &Value) -> Option<TokenUsage> {
+    value.is_object().then(|| TokenUsage {
+        prompt_tokens: value["prompt_tokens"].as_u64().unwrap_or(0),
+        completion_tokens: value["completion_tokens"].as_u64().unwrap_or(0),
+    })
+}
+
+/// 错误响应：`{"error": {"message": "..."}}`，部分兼容服务返回 `{"error": "..."}`
+fn error_message(body: &Value) -> Option<String> {
+    let error = body.get("error")?;
+    error["message"]
+        .as_str()
+        .or_else(|| error.as_str())
+        .map(str::to_string)
+        .or_else(|| Some(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(text: &str) -> LineEvent {
+        LineEvent::Chunk(StreamChunk::Delta(text.to_string()))
+    }
+
+    #[test]
+    fn parse_line_maps_sse_events() {
+        let usage = TokenUsage {
+            prompt_tokens: 12,
+            completion_tokens: 3,
+        };
+        let cases: Vec<(&str, Vec<LineEvent>)> = vec![
+            (
+                r#"data: {"id":"c1","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"role":"assistant","content":"Hi"},"finish_reason":null}]}"#,
+                vec![delta("Hi")],
+            ),
+            (
+                r#"data: {"id":"c1","choices":[{"index":0,"delta":{},"finish_reason":"stop"}],"usage":null}"#,
+                vec![],
+            ),
+            (
+                r#"data: {"id":"c1","choices":[],"usage":{"prompt_tokens":12,"completion_tokens":3,"total_tokens":15}}"#,
+                vec![LineEvent::Chunk(StreamChunk::Usage(usage))],
+            ),
+            ("data: [DONE]", vec![LineEvent::Done]),
+            (
+                r#"data: {"error":{"message":"Rate limit reached","type":"requests"}}"#,
+                vec![LineEvent::Error("Rate limit reached".to_string())],
+            ),
+            (": keep-alive", vec![]),
+            ("", vec![]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_line(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn parse_tool_call_decodes_string_arguments() {
+        let call = parse_tool_call(&json!({
+            "id": "call_1",
+            "type": "function",
+            "function": { "name": "search_logs", "arguments": "{\"query\":\"level:ERROR\"}" }
+        }))
+        .unwrap();
+        assert_eq!(call.id, "call_1");
+        assert_eq!(call.name, "search_logs");
+        assert_eq!(call.arguments, json!({ "query": "level:ERROR" }));
+
+        // 参数不是合法 JSON 时保留原字符串
+        let call = parse_tool_call(&json!({
+            "id": "call_2",
+            "function": { "name": "search_logs", "arguments": "{\"query\":" }
+        }))
+        .unwrap();
+        assert_eq!(call.arguments, json!("{\"query\":"));
+
+        assert!(parse_tool_call(&json!({ "function": { "name": "search_logs" } })).is_none());
+    }
+
+    #[test]
+    fn tool_call_encodes_arguments_as_string() {
+        let value = tool_call(&ToolCall {
+            id: "call_1".to_string(),
+            name: "search_logs".to_string(),
+            arguments: json!({ "query": "level:ERROR" }),
+        });
+        assert_eq!(
+            value["function"]["arguments"],
+            json!("{\"query\":\"level:ERROR\"}")
+        );
+        assert_eq!(
+            parse_tool_call(&value).unwrap().arguments,
+            json!({ "query": "level:ERROR" })
+        );
+    }
+
+    #[test]
+    fn error_message_and_usage_variants() {
+        let cases = [
+            (
+                json!({ "error": { "message": "Invalid API key", "type": "invalid_request_error" } }),
+                Some("Invalid API key"),
+            ),
+            (
+                json!({ "error": "model not found" }),
+                Some("model not found"),
+            ),
+            (json!({ "error": { "code": 500 } }), Some("{\"code\":500}")),
+            (json!({ "choices": [] }), None),
+        ];
+        for (body, expected) in cases {
+            assert_eq!(error_message(&body).as_deref(), expected, "{}", body);
+        }
+
+        assert_eq!(
+            usage(&json!({ "prompt_tokens": 5 })),
+            Some(TokenUsage {
+                prompt_tokens: 5,
+                completion_tokens: 0
+            })
+        );
+        assert_eq!(usage(&Value::Null), None);
+    }
+}
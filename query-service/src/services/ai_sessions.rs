@@ -0,0 +1,483 @@
+use crate::config::AiSessionsConfig;
+use crate::error::AppError;
+use crate::models::ai_sessions::{AiSessionResponse, AiSessionSummary, AiSessionTurn};
+use crate::models::query::{AiContextSummary, SearchRequest};
+use crate::services::ai_provider::ChatMessage;
+use crate::services::json_store::{JsonStore, Snapshot};
+use crate::services::redaction::Placeholders;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+/// 持久化的会话。问答与首轮提示词都是脱敏后的文本，展示时用 `placeholders` 还原
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSession {
+    pub id: String,
+    pub title: String,
+    pub trace_id: Option<String>,
+    pub search: Option<SearchRequest>,
+    pub template: String,
+    /// 回答语言的名称
+    pub language: String,
+    pub system: String,
+    /// 首轮的用户消息，包含打包后的日志
+    pub prompt: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub context: AiContextSummary,
+    pub turns: Vec<AiSessionTurn>,
+    pub placeholders: Placeholders,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl StoredSession {
+    /// 随机生成的会话 ID
+    pub fn new_id() -> String {
+        format!("{:016x}", rand::random::<u64>())
+    }
+
+    /// 发送给 AI 的历史消息：首轮提示词，之后依次为回答与追问
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        let mut messages = vec![ChatMessage::user(&self.prompt)];
+        for turn in &self.turns {
+            if let Some(question) = &turn.question {
+                messages.push(ChatMessage::user(question));
+            }
+            messages.push(ChatMessage::assistant(&turn.answer));
+        }
+        messages
+    }
+
+    /// 追问次数，不含初始分析
+    pub fn follow_ups(&self) -> usize {
+        self.turns
+            .iter()
+            .filter(|turn| turn.question.is_some())
+            .count()
+    }
+
+    pub fn to_response(&self) -> AiSessionResponse {
+        let turns = self
+            .turns
+            .iter()
+            .map(|turn| AiSessionTurn {
+                question: turn
+                    .question
+                    .as_ref()
+                    .map(|question| self.placeholders.rehydrate(question)),
+                answer: self.placeholders.rehydrate(&turn.answer),
+                created_at: turn.created_at,
+            })
+            .collect();
+        AiSessionResponse {
+            id: self.id.clone(),
+            title: self.placeholders.rehydrate(&self.title),
+            trace_id: self.trace_id.clone(),
+            search: self.search.clone(),
+            template: self.template.clone(),
+            language: self.language.clone(),
+            context: self.context.clone(),
+            turns,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    fn summary(&self) -> AiSessionSummary {
+        AiSessionSummary {
+            id: self.id.clone(),
+            title: self.placeholders.rehydrate(&self.title),
+            turns: self.turns.len(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+/// AI 多轮分析会话，持久化到 JSON 文件，其他人可以通过会话 ID 打开并继续追问
+pub struct AiSessionStore {
+    store: JsonStore,
+    max_sessions: usize,
+    max_turns: usize,
+    sessions: Mutex<HashMap<String, StoredSession>>,
+    /// 每个会话的追问锁
+    turn_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl AiSessionStore {
+    /// 从文件加载已有的会话，文件不存在时从空数据开始
+    pub fn open(config: &AiSessionsConfig) -> Result<Self, String> {
+        let store_path = PathBuf::from(&config.store_path);
+        let sessions = match std::fs::read(&store_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                format!(
+                    "failed to parse AI session store {}: {}",
+                    store_path.display(),
+                    e
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(format!(
+                    "failed to read AI session store {}: {}",
+                    store_path.display(),
+                    e
+                ))
+            }
+        };
+
+        Ok(Self {
+            store: JsonStore::new(store_path),
+            max_sessions: config.max_sessions,
+            max_turns: config.max_turns,
+            sessions: Mutex::new(sessions),
+            turn_locks: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 会话已达到追问次数上限时返回错误
+    pub fn check_follow_up(&self, session: &StoredSession) -> Result<(), AppError> {
+        if session.follow_ups() >= self.max_turns {
+            return Err(AppError::ValidationError(format!(
+                "session {} reached the limit of {} follow-up questions",
+                session.id, self.max_turns
+            )));
+        }
+        Ok(())
+    }
+
+    /// 最近更新的会话及会话总数
+    pub fn list(&self, limit: usize) -> (Vec<AiSessionSummary>, usize) {
+        let sessions = self.lock();
+        let mut summaries: Vec<&StoredSession> = sessions.values().collect();
+        summaries.sort_by(|a, b| {
+            b.updated_at
+                .cmp(&a.updated_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        let total = summaries.len();
+        let summaries = summaries
+            .into_iter()
+            .take(limit)
+            .map(StoredSession::summary)
+            .collect();
+        (summaries, total)
+    }
+
+    /// 获取会话的追问锁。追问在持有锁期间完成脱敏、调用 AI 与保存，
+    /// 同一会话的并发追问依次执行，不会分配冲突的占位符或超出追问次数
+    pub async fn lock_turns(&self, id: &str) -> OwnedMutexGuard<()> {
+        let lock = self
+            .turn_locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(id.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    pub fn get(&self, id: &str) -> Option<StoredSession> {
+        self.lock().get(id).cloned()
+    }
+
+    /// 保存新会话，超出上限时删除最久未更新的会话
+    pub async fn insert(&self, session: StoredSession) -> Result<(), AppError> {
+        let snapshot = {
+            let mut sessions = self.lock();
+            sessions.insert(session.id.clone(), session);
+            if sessions.len() > self.max_sessions {
+                let mut by_age: Vec<(DateTime<Utc>, String)> = sessions
+                    .values()
+                    .map(|session| (session.updated_at, session.id.clone()))
+                    .collect();
+                by_age.sort();
+                let excess = sessions.len() - self.max_sessions;
+                for (_, id) in by_age.into_iter().take(excess) {
+                    sessions.remove(&id);
+                    self.remove_turn_lock(&id);
+                }
+            }
+            self.snapshot(&sessions)?
+        };
+        self.persist(snapshot).await
+    }
+
+    /// 追加一轮问答并更新占位符（追问中可能出现新的敏感信息）
+    pub async fn append_turn(
+        &self,
+        id: &str,
+        turn: AiSessionTurn,
+        placeholders: Placeholders,
+    ) -> Result<StoredSession, AppError> {
+        let (session, snapshot) = {
+            let mut sessions = self.lock();
+            let session = sessions
+                .get_mut(id)
+                .ok_or_else(|| AppError::NotFound(format!("session {} not found", id)))?;
+            if turn.question.is_some() {
+                self.check_follow_up(session)?;
+            }
+            session.updated_at = turn.created_at;
+            session.turns.push(turn);
+            session.placeholders = placeholders;
+            let session = session.clone();
+            (session, self.snapshot(&sessions)?)
+        };
+
+        self.persist(snapshot).await?;
+        Ok(session)
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), AppError> {
+        let snapshot = {
+            let mut sessions = self.lock();
+            if sessions.remove(id).is_none() {
+                return Err(AppError::NotFound(format!("session {} not found", id)));
+            }
+            self.remove_turn_lock(id);
+            self.snapshot(&sessions)?
+        };
+        self.persist(snapshot).await
+    }
+
+    /// 在会话锁内序列化，保证快照顺序与修改顺序一致
+    fn snapshot(&self, sessions: &HashMap<String, StoredSession>) -> Result<Snapshot, AppError> {
+        self.store
+            .snapshot(sessions)
+            .map_err(|e| AppError::StorageError(e.to_string()))
+    }
+
+    async fn persist(&self, snapshot: Snapshot) -> Result<(), AppError> {
+        self.store.write(snapshot).await.map_err(|e| {
+            AppError::StorageError(format!(
+                "failed to write AI session store {}: {}",
+                self.store.path().display(),
+                e
+            ))
+        })
+    }
+
+    /// 会话删除后不再需要追问锁；正在等待该锁的追问保存时会得到 404
+    fn remove_turn_lock(&self, id: &str) {
+        self.turn_locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, StoredSession>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactionConfig;
+    use crate::services::ai_provider::ChatRole;
+    use crate::services::redaction::Redactor;
+    use actix_web::ResponseError;
+    use chrono::Duration;
+
+    fn store(max_sessions: usize, max_turns: usize) -> AiSessionStore {
+        let store_path = std::env::temp_dir().join(format!(
+            "ai-sessions-test-{:016x}.json",
+            rand::random::<u64>()
+        ));
+        AiSessionStore::open(&AiSessionsConfig {
+            store_path: store_path.display().to_string(),
+            max_sessions,
+            max_turns,
+        })
+        .unwrap()
+    }
+
+    fn session(id: &str, updated_at: DateTime<Utc>) -> StoredSession {
+        StoredSession {
+            id: id.to_string(),
+            title: "查询：level:ERROR".to_string(),
+            trace_id: None,
+            search: None,
+            template: "default".to_string(),
+            language: "中文".to_string(),
+            system: "你是日志分析专家。".to_string(),
+            prompt: "分析以下日志".to_string(),
+            temperature: 0.3,
+            max_tokens: 1500,
+            context: AiContextSummary::default(),
+            turns: vec![AiSessionTurn {
+                question: None,
+                answer: "初始分析".to_string(),
+                created_at: updated_at,
+            }],
+            placeholders: Placeholders::default(),
+            created_at: updated_at,
+            updated_at,
+        }
+    }
+
+    fn follow_up(question: &str, answer: &str) -> AiSessionTurn {
+        AiSessionTurn {
+            question: Some(question.to_string()),
+            answer: answer.to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn remove(store: AiSessionStore) {
+        std::fs::remove_file(store.store.path()).ok();
+    }
+
+    #[test]
+    fn messages_follow_the_turn_order() {
+        let mut session = session("a", Utc::now());
+        session
+            .turns
+            .push(follow_up("为什么超时？", "下游支付服务响应慢"));
+        session
+            .turns
+            .push(follow_up("影响哪些用户？", "所有结账请求"));
+
+        let messages = session.messages();
+        let messages: Vec<(ChatRole, &str)> = messages
+            .iter()
+            .map(|message| (message.role, message.content.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (ChatRole::User, "分析以下日志"),
+                (ChatRole::Assistant, "初始分析"),
+                (ChatRole::User, "为什么超时？"),
+                (ChatRole::Assistant, "下游支付服务响应慢"),
+                (ChatRole::User, "影响哪些用户？"),
+                (ChatRole::Assistant, "所有结账请求"),
+            ]
+        );
+        assert_eq!(session.follow_ups(), 2);
+    }
+
+    #[tokio::test]
+    async fn follow_ups_stop_at_max_turns() {
+        let store = store(10, 2);
+        store.insert(session("a", Utc::now())).await.unwrap();
+
+        for index in 0..2 {
+            let session = store.get("a").unwrap();
+            store.check_follow_up(&session).unwrap();
+            store
+                .append_turn(
+                    "a",
+                    follow_up(&format!("问题 {}", index), "回答"),
+                    session.placeholders,
+                )
+                .await
+                .unwrap();
+        }
+
+        let session = store.get("a").unwrap();
+        assert_eq!(session.follow_ups(), 2);
+        assert!(matches!(
+            store.check_follow_up(&session),
+            Err(AppError::ValidationError(_))
+        ));
+        let result = store
+            .append_turn("a", follow_up("问题 3", "回答"), Placeholders::default())
+            .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+        assert_eq!(store.get("a").unwrap().turns.len(), 3);
+        remove(store);
+    }
+
+    #[tokio::test]
+    async fn placeholders_survive_the_store_and_are_rehydrated() {
+        let redactor = Redactor::new(&RedactionConfig::default()).unwrap();
+        let mut redaction = redactor.session();
+        let mut first = session("a", Utc::now());
+        first.title = redaction.redact("查询：user:alice@example.com");
+        first.prompt = redaction.redact("login failed for alice@example.com");
+        first.placeholders = redaction.into_placeholders();
+        assert_eq!(first.title, "查询：user:<EMAIL_1>");
+
+        let store = store(10, 5);
+        store.insert(first).await.unwrap();
+        let reopened = AiSessionStore::open(&AiSessionsConfig {
+            store_path: store.store.path().display().to_string(),
+            max_sessions: 10,
+            max_turns: 5,
+        })
+        .unwrap();
+
+        // 追问沿用保存的占位符，已出现的值使用原占位符，新值继续编号
+        let session = reopened.get("a").unwrap();
+        let mut redaction = redactor.resume(session.placeholders.clone());
+        let question = redaction.redact("alice@example.com 和 bob@example.com 都失败了吗？");
+        assert_eq!(question, "<EMAIL_1> 和 <EMAIL_2> 都失败了吗？");
+        let session = reopened
+            .append_turn(
+                "a",
+                follow_up(&question, "<EMAIL_2> 没有失败"),
+                redaction.into_placeholders(),
+            )
+            .await
+            .unwrap();
+
+        let response = session.to_response();
+        assert_eq!(response.title, "查询：user:alice@example.com");
+        assert_eq!(
+            response.turns[1].question.as_deref(),
+            Some("alice@example.com 和 bob@example.com 都失败了吗？")
+        );
+        assert_eq!(response.turns[1].answer, "bob@example.com 没有失败");
+        // 文件中的问答与标题只有占位符
+        let stored: HashMap<String, StoredSession> =
+            serde_json::from_slice(&std::fs::read(reopened.store.path()).unwrap()).unwrap();
+        assert_eq!(stored["a"].title, "查询：user:<EMAIL_1>");
+        assert_eq!(
+            stored["a"].turns[1].question.as_deref(),
+            Some("<EMAIL_1> 和 <EMAIL_2> 都失败了吗？")
+        );
+        remove(store);
+    }
+
+    #[tokio::test]
+    async fn insert_evicts_the_least_recently_updated_sessions() {
+        let store = store(2, 5);
+        let now = Utc::now();
+        store
+            .insert(session("old", now - Duration::hours(2)))
+            .await
+            .unwrap();
+        store
+            .insert(session("mid", now - Duration::hours(1)))
+            .await
+            .unwrap();
+        store.insert(session("new", now)).await.unwrap();
+
+        let (sessions, total) = store.list(10);
+        assert_eq!(total, 2);
+        let ids: Vec<&str> = sessions.iter().map(|session| session.id.as_str()).collect();
+        assert_eq!(ids, vec!["new", "mid"]);
+        assert!(store.get("old").is_none());
+        remove(store);
+    }
+
+    #[tokio::test]
+    async fn deleting_an_unknown_session_is_not_found() {
+        let store = store(10, 5);
+        store.insert(session("a", Utc::now())).await.unwrap();
+
+        let error = store.delete("missing").await.unwrap_err();
+        assert_eq!(error.status_code(), actix_web::http::StatusCode::NOT_FOUND);
+
+        store.delete("a").await.unwrap();
+        let error = store.delete("a").await.unwrap_err();
+        assert_eq!(error.status_code(), actix_web::http::StatusCode::NOT_FOUND);
+        remove(store);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config::CacheConfig;
+use crate::metrics::Metrics;
+use crate::models::query::CacheStatus;
+use crate::models::time_range::parse_duration;
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use serde_json::Value;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 指标中的缓存名
+const CACHE_NAME: &str = "query";
+
+/// 结束时间早于当前时间超过该时长的窗口视为已完全过去（留出 Quickwit 提交新数据的时间）
+const SETTLED_AFTER_SECS: i64 = 60;
+
+struct CacheEntry {
+    value: Arc<Value>,
+    inserted_at: Instant,
+    ttl: Duration,
+}
+
+/// Quickwit 查询结果的进程内 LRU 缓存，key 为操作名加规范化后的请求体。
+///
+/// 时间窗口已完全过去的查询结果基本不会再变化，缓存 `ttl`；
+/// 窗口接近当前时间的查询可能还有新数据写入，只缓存较短的 `recent_ttl`
+pub struct QueryCache {
+    entries: Mutex<LruCache<String, CacheEntry>>,
+    ttl: Duration,
+    recent_ttl: Duration,
+    metrics: Metrics,
+}
+
+impl QueryCache {
+    /// 未启用缓存时返回 None。配置已在启动时校验，这里解析失败时使用 0（不缓存）
+    pub fn from_config(config: &CacheConfig, metrics: Metrics) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let ttl = |value: &str| {
+            parse_duration(value)
+                .ok()
+                .and_then(|duration| duration.to_std().ok())
+                .unwrap_or_default()
+        };
+
+        Some(Self {
+            entries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(config.max_entries).unwrap_or(NonZeroUsize::MIN),
+            )),
+            ttl: ttl(&config.ttl),
+            recent_ttl: ttl(&config.recent_ttl),
+            metrics,
+        })
+    }
+
+    /// 缓存 key。serde_json 的对象按 key 排序序列化，字段顺序不同的相同请求得到相同的 key
+    pub fn key(operation: &str, body: &Value) -> String {
+        format!("{}:{}", operation, body)
+    }
+
+    /// 按查询窗口的结束时间决定缓存时长
+    pub fn ttl_for(&self, end_time: DateTime<Utc>) -> Duration {
+        if is_settled(end_time, Utc::now()) {
+            self.ttl
+        } else {
+            self.recent_ttl
+        }
+    }
+
+    /// 相对时间窗口随当前时间移动，每次查询的请求体都不同，缓存无法命中。
+    /// 窗口接近当前时间时，起点向下、终点向上对齐到 `recent_ttl` 的整数倍，
+    /// 同一周期内的相同查询得到相同的窗口。只用于计算缓存 key，发给 Quickwit 的请求不变
+    pub(crate) fn align_window_at(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> (DateTime<Utc>, DateTime<Utc>) {
+        let bucket = i64::try_from(self.recent_ttl.as_micros()).unwrap_or(0);
+        if bucket == 0 || is_settled(end_time, now) {
+            return (start_time, end_time);
+        }
+
+        let floor = |time: DateTime<Utc>| time.timestamp_micros().div_euclid(bucket) * bucket;
+        let start = floor(start_time);
+        let mut end = floor(end_time);
+        if end < end_time.timestamp_micros() {
+            end += bucket;
+        }
+        match (
+            DateTime::from_timestamp_micros(start),
+            DateTime::from_timestamp_micros(end),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => (start_time, end_time),
+        }
+    }
+
+    /// 查找未过期的结果，返回结果与缓存状态；过期的条目会被移除
+    pub fn get(&self, key: &str) -> Option<(Arc<Value>, CacheStatus)> {
+        let mut entries = self.lock();
+        let found = match entries.get(key) {
+            Some(entry) if entry.inserted_at.elapsed() < entry.ttl => {
+                let age = entry.inserted_at.elapsed();
+                Some((
+                    entry.value.clone(),
+                    CacheStatus::Hit {
+                        age_secs: age.as_secs(),
+                        ttl_secs: (entry.ttl - age).as_secs(),
+                    },
+                ))
+            }
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        };
+        self.metrics.set_cache_entries(CACHE_NAME, entries.len());
+        drop(entries);
+
+        self.metrics
+            .observe_cache(CACHE_NAME, if found.is_some() { "hit" } else { "miss" });
+        found
+    }
+
+    pub fn insert(&self, key: String, value: Arc<Value>, ttl: Duration) {
+        if ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.lock();
+        entries.put(
+            key,
+            CacheEntry {
+                value,
+                inserted_at: Instant::now(),
+                ttl,
+            },
+        );
+        self.metrics.set_cache_entries(CACHE_NAME, entries.len());
+    }
+
+    pub fn observe_bypass(&self) {
+        self.metrics.observe_cache(CACHE_NAME, "bypass");
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruCache<String, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 窗口是否已完全过去
+fn is_settled(end_time: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    end_time < now - chrono::Duration::seconds(SETTLED_AFTER_SECS)
+}
@@ -0,0 +1,181 @@
+use log::{info, warn};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+enum State {
+    /// 正常放行，记录连续失败次数
+    Closed { failures: u32 },
+    /// 熔断中，到期前直接拒绝
+    Open { until: Instant },
+    /// 熔断到期后放行一个探测请求，探测结果决定恢复还是继续熔断
+    HalfOpen { probe_started: Instant },
+}
+
+/// 熔断器：连续失败达到阈值后在 `cooldown` 内快速失败，避免下游故障时请求堆积
+pub struct CircuitBreaker {
+    name: &'static str,
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            name,
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// 是否放行请求；拒绝时返回剩余熔断时长
+    pub fn allow(&self) -> Result<(), Duration> {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.lock();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } => {
+                if now < until {
+                    return Err(until - now);
+                }
+                *state = State::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            // 探测请求被取消时不会上报结果，超过 cooldown 后重新放行一个探测请求
+            State::HalfOpen { probe_started }
+                if now.saturating_duration_since(probe_started) >= self.cooldown =>
+            {
+                *state = State::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            State::HalfOpen { .. } => Err(Duration::ZERO),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.lock();
+        if matches!(*state, State::HalfOpen { .. } | State::Open { .. }) {
+            info!("{} circuit closed", self.name);
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now())
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut state = self.lock();
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            // 探测失败立即重新熔断
+            State::HalfOpen { .. } => self.failure_threshold,
+            State::Open { .. } => return,
+        };
+
+        if failures >= self.failure_threshold {
+            warn!(
+                "{} circuit opened after {} consecutive failures, failing fast for {:?}",
+                self.name, failures, self.cooldown
+            );
+            *state = State::Open {
+                until: now + self.cooldown,
+            };
+        } else {
+            *state = State::Closed { failures };
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(*self.lock(), State::Closed { .. })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    fn open_breaker(start: Instant) -> CircuitBreaker {
+        let breaker = CircuitBreaker::new("test", 3, COOLDOWN);
+        for _ in 0..3 {
+            assert!(breaker.allow_at(start).is_ok());
+            breaker.record_failure_at(start);
+        }
+        breaker
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let start = Instant::now();
+        let breaker = CircuitBreaker::new("test", 3, COOLDOWN);
+        breaker.record_failure_at(start);
+        breaker.record_failure_at(start);
+        // 成功清零连续失败次数
+        breaker.record_success();
+        breaker.record_failure_at(start);
+        breaker.record_failure_at(start);
+        assert!(!breaker.is_open());
+
+        breaker.record_failure_at(start);
+        assert!(breaker.is_open());
+        let remaining = breaker
+            .allow_at(start + Duration::from_secs(10))
+            .unwrap_err();
+        assert_eq!(remaining, Duration::from_secs(20));
+    }
+
+    #[test]
+    fn successful_probe_closes_the_circuit() {
+        let start = Instant::now();
+        let breaker = open_breaker(start);
+
+        // 到期后只放行一个探测请求
+        let probe = start + COOLDOWN;
+        assert!(breaker.allow_at(probe).is_ok());
+        assert_eq!(breaker.allow_at(probe), Err(Duration::ZERO));
+
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(breaker.allow_at(probe).is_ok());
+    }
+
+    #[test]
+    fn failed_probe_reopens_the_circuit() {
+        let start = Instant::now();
+        let breaker = open_breaker(start);
+
+        let probe = start + COOLDOWN;
+        assert!(breaker.allow_at(probe).is_ok());
+        breaker.record_failure_at(probe);
+
+        assert!(breaker.is_open());
+        assert_eq!(
+            breaker.allow_at(probe + Duration::from_secs(1)),
+            Err(COOLDOWN - Duration::from_secs(1))
+        );
+        assert!(breaker.allow_at(probe + COOLDOWN).is_ok());
+    }
+
+    #[test]
+    fn abandoned_probe_is_replaced_after_cooldown() {
+        let start = Instant::now();
+        let breaker = open_breaker(start);
+
+        let probe = start + COOLDOWN;
+        assert!(breaker.allow_at(probe).is_ok());
+        // 探测请求没有上报结果
+        assert!(breaker.allow_at(probe + COOLDOWN / 2).is_err());
+        assert!(breaker.allow_at(probe + COOLDOWN).is_ok());
+        assert!(breaker.allow_at(probe + COOLDOWN).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::models::query::{LogHit, StackFrame};
+use crate::services::patterns::normalize_message;
+use crate::services::stack_trace::StackTraceParser;
+use regex::{NoExpand, Regex};
+use sha2::{Digest, Sha256};
+use std::sync::LazyLock;
+
+static ADDRESS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b0x[0-9a-f]+\b").expect("valid regex"));
+static GENERATED_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\d+(?:/\S+)?").expect("valid regex"));
+
+/// 参与指纹计算的业务帧数
+const MAX_FINGERPRINT_FRAMES: usize = 5;
+
+/// 标题最大字符数
+const MAX_TITLE_CHARS: usize = 200;
+
+/// 错误指纹：相同指纹的错误归为同一个 issue
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    /// 指纹哈希（SHA-256 前 16 位十六进制）
+    pub id: String,
+
+    /// 归一化后的异常行或消息
+    pub title: String,
+
+    /// 异常类型（如 java.lang.NullPointerException、ValueError、panic）
+    pub error_type: Option<String>,
+
+    /// 最靠近出错位置的业务帧
+    pub culprit: Option<String>,
+
+    /// 参与指纹计算的帧（函数与文件名，不含行号）
+    pub frames: Vec<String>,
+}
+
+/// 根据堆栈计算错误指纹。
+///
+/// 取异常类型与最上层的若干业务帧计算哈希，帧只保留函数与文件名，去掉行号、内存地址和匿名类编号，
+/// 因此同一处代码抛出的错误即使消息内容或行号不同也会得到相同的指纹。
+/// 没有堆栈或无法解析出帧时使用归一化后的消息
+pub fn fingerprint(hit: &LogHit, parser: &StackTraceParser) -> Fingerprint {
+    let parsed = hit
+        .stack_trace
+        .as_deref()
+        .filter(|stack_trace| !stack_trace.trim().is_empty())
+        .map(|stack_trace| parser.parse(stack_trace));
+
+    let Some(parsed) = parsed.filter(|parsed| !parsed.frames.is_empty()) else {
+        let title = normalize_message(&hit.message);
+        return Fingerprint {
+            id: hash(&["message", &title]),
+            title: truncate(&title),
+            error_type: None,
+            culprit: None,
+            frames: Vec::new(),
+        };
+    };
+
+    let title = normalize_message(parsed.header.as_deref().unwrap_or(&hit.message));
+    let error_type = parsed.error_type().map(str::to_string);
+
+    let in_app: Vec<&StackFrame> = parsed.frames.iter().filter(|frame| frame.in_app).collect();
+    let culprit = in_app.first().map(|frame| frame.to_string());
+    // 没有业务帧时退化为最上层的若干帧
+    let candidates = if in_app.is_empty() {
+        parsed.frames.iter().collect()
+    } else {
+        in_app
+    };
+    let frames: Vec<String> = candidates
+        .into_iter()
+        .take(MAX_FINGERPRINT_FRAMES)
+        .map(frame_signature)
+        .collect();
+
+    // 异常消息不参与指纹，避免消息中的变量把同一错误拆成多个 issue
+    let mut parts: Vec<&str> = vec!["stack", error_type.as_deref().unwrap_or("")];
+    parts.extend(frames.iter().map(String::as_str));
+
+    Fingerprint {
+        id: hash(&parts),
+        title: truncate(&title),
+        error_type,
+        culprit,
+        frames,
+    }
+}
+
+/// 帧的稳定标识：函数名加文件名，去掉地址和编译器生成的编号；
+/// 只取文件名，部署目录不同不影响指纹
+fn frame_signature(frame: &StackFrame) -> String {
+    let function = frame.function.as_deref().unwrap_or("<unknown>");
+    let function = ADDRESS.replace_all(function, "");
+    let function = GENERATED_ID.replace_all(&function, NoExpand("$"));
+
+    match frame.file.as_deref() {
+        Some(file) => {
+            let file_name = file.rsplit(['/', '\\']).next().unwrap_or(file);
+            format!("{} @ {}", function, file_name)
+        }
+        None => function.to_string(),
+    }
+}
+
+fn hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_TITLE_CHARS) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hit(message: &str, stack_trace: Option<&str>) -> LogHit {
+        serde_json::from_value(json!({
+            "timestamp": "2026-10-18T10:00:00Z",
+            "message": message,
+            "level": "ERROR",
+            "service": "order",
+            "env": "prod",
+            "stack_trace": stack_trace,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fingerprint_ignores_messages_line_numbers_and_addresses() {
+        let parser = StackTraceParser::new(Vec::new());
+        let trace = |message: &str, line: u32, address: &str| {
+            format!(
+                "panic: {}\n\ngoroutine 1 [running]:\nmain.(*Server).handle({})\n\t/app/server.go:{} +0x1d\nmain.main()\n\t/srv/build/main.go:10 +0x25",
+                message, address, line
+            )
+        };
+
+        let a = fingerprint(
+            &hit(
+                "order 1",
+                Some(&trace("order 1 not found", 42, "0xc000010000")),
+            ),
+            &parser,
+        );
+        let b = fingerprint(
+            &hit(
+                "order 2",
+                Some(&trace("order 2 not found", 57, "0xc0000a2000")),
+            ),
+            &parser,
+        );
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.error_type.as_deref(), Some("panic"));
+        assert_eq!(
+            a.culprit.as_deref(),
+            Some("main.(*Server).handle (/app/server.go:42)")
+        );
+        assert_eq!(
+            a.frames,
+            vec!["main.(*Server).handle @ server.go", "main.main @ main.go"]
+        );
+        assert_eq!(a.title, "panic: order <NUM> not found");
+
+        let other = fingerprint(
+            &hit(
+                "order 1",
+                Some("panic: order 1 not found\n\ngoroutine 1 [running]:\nmain.main()\n\t/app/main.go:10 +0x25"),
+            ),
+            &parser,
+        );
+        assert_ne!(a.id, other.id);
+    }
+
+    #[test]
+    fn messages_without_stack_trace_use_normalized_message() {
+        let parser = StackTraceParser::new(Vec::new());
+        let a = fingerprint(
+            &hit("timeout after 30s calling 10.0.0.1:8080", None),
+            &parser,
+        );
+        let b = fingerprint(
+            &hit("timeout after 45s calling 10.0.0.2:8080", Some(" ")),
+            &parser,
+        );
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.title, "timeout after <NUM>s calling <IP>");
+        assert!(a.frames.is_empty());
+
+        let c = fingerprint(&hit("connection refused", None), &parser);
+        assert_ne!(a.id, c.id);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::models::query::{FieldHighlight, HighlightRange};
+use serde_json::Value;
+
+/// 支持高亮的字段
+pub const HIGHLIGHT_FIELDS: &[&str] = &["message", "stack_trace"];
+
+/// 本地生成片段时匹配词前后保留的字符数
+const FRAGMENT_CONTEXT_CHARS: usize = 60;
+
+/// 本地生成片段的最大数量
+const MAX_FRAGMENTS: usize = 3;
+
+/// 查询关键字，字段限定的条件只保留针对高亮字段的部分
+pub struct Highlighter {
+    terms: Vec<Vec<char>>,
+}
+
+impl Highlighter {
+    /// 从 Lucene 查询字符串中提取用于高亮的关键词。
+    /// 忽略布尔运算符、范围条件、其他字段上的条件（如 `level:ERROR`）以及取反的条件
+    /// （`-message:foo`、`NOT foo`、`NOT (a OR b)`），去掉引号与通配符
+    pub fn from_query(query: &str) -> Self {
+        let mut terms: Vec<Vec<char>> = Vec::new();
+        // 每层括号是否处于取反中
+        let mut negated_groups = vec![false];
+        let mut negate_next = false;
+
+        for token in tokenize(query) {
+            let in_negated_group = negated_groups.last().copied().unwrap_or(false);
+            match token.as_str() {
+                "(" => {
+                    negated_groups.push(in_negated_group || negate_next);
+                    negate_next = false;
+                    continue;
+                }
+                ")" => {
+                    if negated_groups.len() > 1 {
+                        negated_groups.pop();
+                    }
+                    continue;
+                }
+                "NOT" | "-" => {
+                    negate_next = true;
+                    continue;
+                }
+                "AND" | "OR" | "+" => continue,
+                _ => {}
+            }
+            let negated = in_negated_group || negate_next || token.starts_with('-');
+            negate_next = false;
+            if negated {
+                continue;
+            }
+
+            let clause = token.trim_start_matches('+');
+            let (field, value) = match clause.split_once(':') {
+                Some((field, value)) if !field.starts_with('"') => (Some(field), value),
+                _ => (None, clause),
+            };
+            if let Some(field) = field {
+                if !HIGHLIGHT_FIELDS.contains(&field) {
+                    continue;
+                }
+            }
+            if value.starts_with('[') || value.starts_with('{') {
+                continue;
+            }
+
+            let value = value.trim_matches(|c: char| c == '"' || c == '*' || c == '?');
+            if value.is_empty() {
+                continue;
+            }
+
+            let term: Vec<char> = value.chars().flat_map(char::to_lowercase).collect();
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+
+        Self { terms }
+    }
+
+    /// 计算字段中所有匹配的位置（字符偏移，左闭右开），重叠的区间会合并
+    pub fn ranges(&self, text: &str) -> Vec<HighlightRange> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut ranges: Vec<HighlightRange> = Vec::new();
+
+        for term in &self.terms {
+            if term.len() > chars.len() {
+                continue;
+            }
+            for start in 0..=chars.len() - term.len() {
+                let matched = term
+                    .iter()
+                    .zip(&chars[start..])
+                    .all(|(t, c)| c.to_lowercase().next() == Some(*t));
+                if matched {
+                    ranges.push(HighlightRange {
+                        start,
+                        end: start + term.len(),
+                    });
+                }
+            }
+        }
+
+        ranges.sort_by_key(|r| (r.start, r.end));
+        let mut merged: Vec<HighlightRange> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    /// 生成字段高亮：优先使用 Quickwit 返回的片段，没有时用本地匹配结果生成
+    pub fn highlight(&self, text: &str, quickwit_snippets: Option<&Value>) -> Option<FieldHighlight> {
+        let ranges = self.ranges(text);
+
+        let snippets: Vec<String> = quickwit_snippets
+            .and_then(|s| s.as_array())
+            .map(|fragments| {
+                fragments
+                    .iter()
+                    .filter_map(|f| f.as_str())
+                    .filter(|f| !f.is_empty())
+                    .map(|f| f.to_string())
+                    .collect()
+            })
+            .filter(|fragments: &Vec<String>| !fragments.is_empty())
+            .unwrap_or_else(|| local_fragments(text, &ranges));
+
+        if ranges.is_empty() && snippets.is_empty() {
+            return None;
+        }
+
+        Some(FieldHighlight { snippets, ranges })
+    }
+}
+
+/// 按空白切分查询，括号单独作为一项；引号与范围条件（`[a TO b]`）内不切分
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut range_depth = 0usize;
+
+    for c in query.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            '[' | '{' if !in_quotes => {
+                range_depth += 1;
+                current.push(c);
+            }
+            ']' | '}' if !in_quotes => {
+                range_depth = range_depth.saturating_sub(1);
+                current.push(c);
+            }
+            _ if in_quotes || range_depth > 0 => current.push(c),
+            '(' | ')' => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// 以匹配位置为中心截取片段，匹配词用 `<b>` 包裹，其余文本做 HTML 转义，与 Quickwit 片段格式一致
+fn local_fragments(text: &str, ranges: &[HighlightRange]) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+
+    for range in ranges {
+        let start = range.start.saturating_sub(FRAGMENT_CONTEXT_CHARS);
+        let end = (range.end + FRAGMENT_CONTEXT_CHARS).min(chars.len());
+        match windows.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => windows.push((start, end)),
+        }
+    }
+
+    windows
+        .into_iter()
+        .take(MAX_FRAGMENTS)
+        .map(|(start, end)| {
+            let mut fragment = String::new();
+            if start > 0 {
+                fragment.push('…');
+            }
+            let mut pos = start;
+            for range in ranges.iter().filter(|r| r.start >= start && r.end <= end) {
+                fragment.push_str(&escape_html(&chars[pos..range.start]));
+                fragment.push_str("<b>");
+                fragment.push_str(&escape_html(&chars[range.start..range.end]));
+                fragment.push_str("</b>");
+                pos = range.end;
+            }
+            fragment.push_str(&escape_html(&chars[pos..end]));
+            if end < chars.len() {
+                fragment.push('…');
+            }
+            fragment
+        })
+        .collect()
+}
+
+fn escape_html(chars: &[char]) -> String {
+    let mut escaped = String::with_capacity(chars.len());
+    for c in chars {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(*c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(query: &str) -> Vec<String> {
+        Highlighter::from_query(query)
+            .terms
+            .iter()
+            .map(|term| term.iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn extracts_terms_for_highlight_fields_only() {
+        assert_eq!(
+            terms(
+                r#"level:ERROR AND (message:Timeout OR "connection reset") AND stack_trace:Pool*"#
+            ),
+            vec!["timeout", "connection reset", "pool"]
+        );
+        assert_eq!(
+            terms("timestamp:[2026-10-18T00:00:00Z TO *] AND +db"),
+            vec!["db"]
+        );
+    }
+
+    #[test]
+    fn negated_clauses_are_not_highlighted() {
+        assert_eq!(terms("error -message:foo"), vec!["error"]);
+        assert_eq!(terms("error AND NOT message:foo"), vec!["error"]);
+        assert_eq!(terms("NOT (foo OR bar) AND baz"), vec!["baz"]);
+        assert_eq!(terms("-(foo bar) baz"), vec!["baz"]);
+        assert_eq!(terms("-\"foo bar\" baz"), vec!["baz"]);
+    }
+
+    #[test]
+    fn ranges_merge_overlaps_and_fragments_escape_html() {
+        let highlighter = Highlighter::from_query("time timeout");
+        let text = "<db> timeout after 30s";
+        assert_eq!(
+            highlighter.ranges(text),
+            vec![HighlightRange { start: 5, end: 12 }]
+        );
+
+        let highlight = highlighter.highlight(text, None).unwrap();
+        assert_eq!(
+            highlight.snippets,
+            vec!["&lt;db&gt; <b>timeout</b> after 30s"]
+        );
+        assert!(highlighter.highlight("all good", None).is_none());
+    }
+}
@@ -0,0 +1,498 @@
+use crate::config::IssuesConfig;
+use crate::error::AppError;
+use crate::models::issues::{Issue, IssueOccurrence, IssueScanSummary, IssueStatus, IssuesQuery};
+use crate::models::query::{LogHit, SearchRequest};
+use crate::models::time_range::parse_duration;
+use crate::services::fingerprint::fingerprint;
+use crate::services::json_store::{JsonStore, Snapshot};
+use crate::services::quickwit::QuickwitClient;
+use crate::services::stack_trace::StackTraceParser;
+use crate::AppState;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 扫描的结束时间落后当前时间的时长，等待 Quickwit 提交最近写入的日志
+const SCAN_LAG_SECS: i64 = 10;
+
+/// 扫描时每页拉取的日志数
+const SCAN_PAGE_SIZE: usize = 1000;
+
+/// 单次扫描最多拉取的页数，超出的日志留给下一次扫描
+const MAX_SCAN_PAGES: usize = 50;
+
+/// 持久化到文件的数据
+#[derive(Default, Serialize, Deserialize)]
+struct IssueData {
+    /// 已扫描到的时间点
+    scanned_until: Option<DateTime<Utc>>,
+    issues: BTreeMap<String, Issue>,
+}
+
+/// 错误 issue 跟踪：定期扫描 ERROR 日志，按堆栈指纹聚合并持久化到 JSON 文件。
+///
+/// 扫描窗口首尾相接，每条日志只计入一次；晚于扫描延迟才写入 Quickwit 的日志不会被计入
+pub struct IssueTracker {
+    store: JsonStore,
+    scan_interval: std::time::Duration,
+    initial_lookback: Duration,
+    data: Mutex<IssueData>,
+    /// 后台扫描与手动触发的扫描互斥
+    scan_lock: tokio::sync::Mutex<()>,
+}
+
+impl IssueTracker {
+    /// 从文件加载已有的 issue，文件不存在时从空数据开始
+    pub fn open(config: &IssuesConfig) -> Result<Self, String> {
+        let store_path = PathBuf::from(&config.store_path);
+        let data = match std::fs::read(&store_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                format!(
+                    "failed to parse issue store {}: {}",
+                    store_path.display(),
+                    e
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => IssueData::default(),
+            Err(e) => {
+                return Err(format!(
+                    "failed to read issue store {}: {}",
+                    store_path.display(),
+                    e
+                ))
+            }
+        };
+
+        Ok(Self {
+            store: JsonStore::new(store_path),
+            scan_interval: parse_duration(&config.scan_interval)?
+                .to_std()
+                .map_err(|_| "issues.scan_interval must be positive".to_string())?,
+            initial_lookback: parse_duration(&config.initial_lookback)?,
+            data: Mutex::new(data),
+            scan_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn scanned_until(&self) -> Option<DateTime<Utc>> {
+        self.lock().scanned_until
+    }
+
+    /// 按过滤条件返回 issue（最近出现的在前）及满足条件的总数
+    pub fn list(&self, query: &IssuesQuery) -> (Vec<Issue>, usize) {
+        let data = self.lock();
+        let mut issues: Vec<&Issue> = data
+            .issues
+            .values()
+            .filter(|issue| query.status.is_none_or(|status| issue.status == status))
+            .filter(|issue| {
+                query
+                    .regressed
+                    .is_none_or(|regressed| issue.regressed == regressed)
+            })
+            .filter(|issue| {
+                issue.occurrences.iter().any(|occurrence| {
+                    query
+                        .service
+                        .as_ref()
+                        .is_none_or(|s| &occurrence.service == s)
+                        && query.env.as_ref().is_none_or(|e| &occurrence.env == e)
+                })
+            })
+            .collect();
+        issues.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then_with(|| a.id.cmp(&b.id)));
+
+        let total = issues.len();
+        let issues = issues.into_iter().take(query.limit).cloned().collect();
+        (issues, total)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Issue> {
+        self.lock().issues.get(id).cloned()
+    }
+
+    /// 修改 issue 状态并清除回归标记
+    pub async fn set_status(&self, id: &str, status: IssueStatus) -> Result<Issue, AppError> {
+        let (issue, snapshot) = {
+            let mut data = self.lock();
+            let issue = data
+                .issues
+                .get_mut(id)
+                .ok_or_else(|| AppError::NotFound(format!("issue {} not found", id)))?;
+
+            issue.status = status;
+            issue.regressed = false;
+            issue.resolved_at = (status == IssueStatus::Resolved).then(Utc::now);
+            let issue = issue.clone();
+            (issue, self.snapshot(&data)?)
+        };
+
+        self.persist(snapshot).await?;
+        Ok(issue)
+    }
+
+    /// 扫描上次扫描之后的 ERROR 日志并计入对应的 issue
+    pub async fn scan(
+        &self,
+        quickwit: &QuickwitClient,
+        parser: &StackTraceParser,
+    ) -> Result<IssueScanSummary, AppError> {
+        let _guard = self.scan_lock.lock().await;
+
+        let end_time = Utc::now() - Duration::seconds(SCAN_LAG_SECS);
+        let start_time = self
+            .scanned_until()
+            .unwrap_or(end_time - self.initial_lookback);
+        if start_time >= end_time {
+            return Ok(IssueScanSummary::default());
+        }
+
+        let mut hits = Vec::new();
+        let mut truncated = false;
+        for page in 1..=MAX_SCAN_PAGES {
+            let req = error_search(start_time, end_time, page);
+            let response = quickwit.search_uncached(&req, start_time, end_time).await?;
+            let fetched = response.hits.len();
+            hits.extend(response.hits);
+
+            if fetched < SCAN_PAGE_SIZE || hits.len() as u64 >= response.total {
+                break;
+            }
+            if page == MAX_SCAN_PAGES {
+                truncated = true;
+                warn!(
+                    "Issue scan hit the limit of {} events, the remaining {} events in {} ~ {} are left for the next scan",
+                    hits.len(),
+                    response.total - hits.len() as u64,
+                    start_time,
+                    end_time
+                );
+            }
+        }
+
+        let scanned_until = if truncated {
+            truncate_to_progress(&mut hits, end_time)
+        } else {
+            end_time
+        };
+        let mut summary = self.record(&hits, parser, scanned_until).await?;
+        summary.start_time = Some(start_time);
+        summary.end_time = Some(scanned_until);
+        Ok(summary)
+    }
+
+    /// 把日志计入 issue，并把扫描进度推进到 `scanned_until`
+    async fn record(
+        &self,
+        hits: &[LogHit],
+        parser: &StackTraceParser,
+        scanned_until: DateTime<Utc>,
+    ) -> Result<IssueScanSummary, AppError> {
+        let (summary, snapshot) = self.apply(hits, parser, scanned_until)?;
+        self.persist(snapshot).await?;
+
+        if summary.events > 0 {
+            info!(
+                "Issue scan: {} events, {} new issues, {} regressions",
+                summary.events,
+                summary.new_issues,
+                summary.regressions.len()
+            );
+        }
+        Ok(summary)
+    }
+
+    /// 在锁内更新 issue 并序列化
+    fn apply(
+        &self,
+        hits: &[LogHit],
+        parser: &StackTraceParser,
+        scanned_until: DateTime<Utc>,
+    ) -> Result<(IssueScanSummary, Snapshot), AppError> {
+        let mut data = self.lock();
+        let mut summary = IssueScanSummary {
+            events: hits.len(),
+            ..Default::default()
+        };
+
+        for hit in hits {
+            let fingerprint = fingerprint(hit, parser);
+            let timestamp = hit.timestamp.with_timezone(&Utc);
+
+            let issue = data
+                .issues
+                .entry(fingerprint.id.clone())
+                .or_insert_with(|| {
+                    summary.new_issues += 1;
+                    Issue {
+                        id: fingerprint.id.clone(),
+                        title: fingerprint.title.clone(),
+                        error_type: fingerprint.error_type.clone(),
+                        culprit: fingerprint.culprit.clone(),
+                        frames: fingerprint.frames.clone(),
+                        status: IssueStatus::Open,
+                        regressed: false,
+                        first_seen: timestamp,
+                        last_seen: timestamp,
+                        count: 0,
+                        occurrences: Vec::new(),
+                        resolved_at: None,
+                        regressed_at: None,
+                        regression_count: 0,
+                        latest_event: hit.clone(),
+                    }
+                });
+
+            // 已解决的 issue 在解决之后再次出现视为回归，重新打开
+            if issue.status == IssueStatus::Resolved
+                && issue
+                    .resolved_at
+                    .is_none_or(|resolved_at| timestamp > resolved_at)
+            {
+                issue.status = IssueStatus::Open;
+                issue.regressed = true;
+                issue.regressed_at = Some(timestamp);
+                issue.regression_count += 1;
+                warn!("Issue {} regressed: {}", issue.id, issue.title);
+                summary.regressions.push(issue.id.clone());
+            }
+
+            issue.count += 1;
+            issue.first_seen = issue.first_seen.min(timestamp);
+            if timestamp >= issue.last_seen {
+                issue.last_seen = timestamp;
+                issue.latest_event = hit.clone();
+            }
+            record_occurrence(&mut issue.occurrences, hit, timestamp);
+        }
+
+        data.scanned_until = Some(scanned_until);
+        Ok((summary, self.snapshot(&data)?))
+    }
+
+    /// 在数据锁内序列化，保证快照顺序与修改顺序一致
+    fn snapshot(&self, data: &IssueData) -> Result<Snapshot, AppError> {
+        self.store
+            .snapshot(data)
+            .map_err(|e| AppError::StorageError(e.to_string()))
+    }
+
+    async fn persist(&self, snapshot: Snapshot) -> Result<(), AppError> {
+        self.store.write(snapshot).await.map_err(|e| {
+            AppError::StorageError(format!(
+                "failed to write issue store {}: {}",
+                self.store.path().display(),
+                e
+            ))
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, IssueData> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn record_occurrence(
+    occurrences: &mut Vec<IssueOccurrence>,
+    hit: &LogHit,
+    timestamp: DateTime<Utc>,
+) {
+    match occurrences
+        .iter_mut()
+        .find(|occurrence| occurrence.service == hit.service && occurrence.env == hit.env)
+    {
+        Some(occurrence) => {
+            occurrence.count += 1;
+            occurrence.first_seen = occurrence.first_seen.min(timestamp);
+            occurrence.last_seen = occurrence.last_seen.max(timestamp);
+        }
+        None => occurrences.push(IssueOccurrence {
+            service: hit.service.clone(),
+            env: hit.env.clone(),
+            count: 1,
+            first_seen: timestamp,
+            last_seen: timestamp,
+        }),
+    }
+}
+
+/// 扫描达到页数上限时的扫描进度：下一次扫描从最后一条日志的时间开始（含该时间），
+/// 因此去掉与最后一条时间相同的日志，留给下一次扫描计入，避免重复计数。
+/// 全部日志时间相同时只能全部计入，并跳过该时间点上剩余的日志
+fn truncate_to_progress(hits: &mut Vec<LogHit>, end_time: DateTime<Utc>) -> DateTime<Utc> {
+    let Some(last) = hits.last().map(|hit| hit.timestamp.with_timezone(&Utc)) else {
+        return end_time;
+    };
+    let consumed = hits
+        .iter()
+        .rposition(|hit| hit.timestamp.with_timezone(&Utc) < last)
+        .map_or(0, |index| index + 1);
+    if consumed == 0 {
+        warn!(
+            "Issue scan: more than {} events at {}, the rest at this time are skipped",
+            hits.len(),
+            last
+        );
+        return last + Duration::microseconds(1);
+    }
+    hits.truncate(consumed);
+    last
+}
+
+/// 按时间正序拉取窗口内的 ERROR 日志
+fn error_search(start_time: DateTime<Utc>, end_time: DateTime<Utc>, page: usize) -> SearchRequest {
+    SearchRequest {
+        query: "level:ERROR".to_string(),
+        filters: HashMap::new(),
+        time_range_type: "absolute".to_string(),
+        relative_time_key: None,
+        relative_end_key: None,
+        time_zone: None,
+        start_time: Some(start_time),
+        end_time: Some(end_time),
+        page,
+        page_size: SCAN_PAGE_SIZE,
+        sort_by: "timestamp".to_string(),
+        sort_desc: false,
+        sort: Vec::new(),
+        highlight: false,
+        frames: false,
+        no_cache: false,
+    }
+}
+
+/// 后台定期扫描 ERROR 日志。每次扫描使用当前的 Quickwit 客户端，配置重载后自动生效
+pub fn spawn_issue_scanner(state: AppState) {
+    let interval = state.issues.scan_interval;
+    actix_rt::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = state
+                .issues
+                .scan(&state.quickwit(), &state.stack_trace_parser())
+                .await {
+                warn!("Issue scan failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hit(timestamp: &str, message: &str) -> LogHit {
+        serde_json::from_value(json!({
+            "timestamp": timestamp,
+            "message": message,
+            "level": "ERROR",
+            "service": "order",
+            "env": "prod",
+            "stack_trace": format!(
+                "java.lang.IllegalStateException: {}\n\tat com.acme.OrderService.find(OrderService.java:42)",
+                message
+            ),
+        }))
+        .unwrap()
+    }
+
+    fn tracker() -> IssueTracker {
+        let store_path =
+            std::env::temp_dir().join(format!("issues-test-{:016x}.json", rand::random::<u64>()));
+        IssueTracker::open(&IssuesConfig {
+            store_path: store_path.display().to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn truncated_scan_resumes_at_last_consumed_timestamp() {
+        let end_time = utc("2026-10-18T10:05:00Z");
+        let mut hits = vec![
+            hit("2026-10-18T10:00:00Z", "a"),
+            hit("2026-10-18T10:00:01Z", "b"),
+            hit("2026-10-18T10:00:02Z", "c"),
+            hit("2026-10-18T10:00:02Z", "d"),
+        ];
+        assert_eq!(
+            truncate_to_progress(&mut hits, end_time),
+            utc("2026-10-18T10:00:02Z")
+        );
+        assert_eq!(hits.len(), 2);
+
+        let mut same_time = vec![
+            hit("2026-10-18T10:00:02Z", "c"),
+            hit("2026-10-18T10:00:02Z", "d"),
+        ];
+        assert_eq!(
+            truncate_to_progress(&mut same_time, end_time),
+            utc("2026-10-18T10:00:02.000001Z")
+        );
+        assert_eq!(same_time.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn resolved_issue_regresses_when_seen_again() {
+        let tracker = tracker();
+        let parser = StackTraceParser::new(Vec::new());
+
+        let first = hit("2026-10-18T10:00:00Z", "order 1 not found");
+        let summary = tracker
+            .record(
+                std::slice::from_ref(&first),
+                &parser,
+                utc("2026-10-18T10:01:00Z"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(summary.new_issues, 1);
+        let id = fingerprint(&first, &parser).id;
+
+        tracker
+            .set_status(&id, IssueStatus::Resolved)
+            .await
+            .unwrap();
+        // 解决之前发生的日志不算回归
+        let summary = tracker
+            .record(
+                &[hit("2026-10-18T10:00:30Z", "order 2 not found")],
+                &parser,
+                utc("2026-10-18T10:02:00Z"),
+            )
+            .await
+            .unwrap();
+        assert!(summary.regressions.is_empty());
+
+        let later = Utc::now() + Duration::minutes(1);
+        let summary = tracker
+            .record(
+                &[hit(&later.to_rfc3339(), "order 3 not found")],
+                &parser,
+                later,
+            )
+            .await
+            .unwrap();
+        assert_eq!(summary.regressions, vec![id.clone()]);
+
+        let issue = tracker.get(&id).unwrap();
+        assert_eq!(issue.status, IssueStatus::Open);
+        assert!(issue.regressed);
+        assert_eq!(issue.regression_count, 1);
+        assert_eq!(issue.count, 3);
+        assert_eq!(tracker.scanned_until(), Some(later));
+
+        std::fs::remove_file(tracker.store.path()).ok();
+    }
+}
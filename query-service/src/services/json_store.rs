@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 序列化好的一份数据快照，`generation` 按序列化的先后递增
+pub struct Snapshot {
+    bytes: Vec<u8>,
+    generation: u64,
+}
+
+/// JSON 文件存储：调用方在自己的锁内序列化，写文件放到阻塞线程池，
+/// 不占用请求线程。先写临时文件再重命名，较早的快照不会覆盖已写入的较新快照
+pub struct JsonStore {
+    path: Arc<PathBuf>,
+    next_generation: AtomicU64,
+    /// 已写入文件的最新快照编号，同时保证同一时刻只有一个写入
+    written: Arc<Mutex<u64>>,
+}
+
+impl JsonStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path: Arc::new(path),
+            next_generation: AtomicU64::new(1),
+            written: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 序列化数据。需要在保护数据的锁内调用，快照的顺序才与数据的修改顺序一致
+    pub fn snapshot<T: serde::Serialize>(&self, value: &T) -> serde_json::Result<Snapshot> {
+        let bytes = serde_json::to_vec(value)?;
+        let generation = self.next_generation.fetch_add(1, Ordering::SeqCst);
+        Ok(Snapshot { bytes, generation })
+    }
+
+    /// 在阻塞线程池中写入快照
+    pub async fn write(&self, snapshot: Snapshot) -> std::io::Result<()> {
+        let path = self.path.clone();
+        let written = self.written.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut written = written.lock().unwrap_or_else(|e| e.into_inner());
+            if snapshot.generation <= *written {
+                return Ok(());
+            }
+            write_file(&path, &snapshot.bytes)?;
+            *written = snapshot.generation;
+            Ok(())
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path)
+}
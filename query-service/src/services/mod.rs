@@ -1,2 +1,18 @@
 pub mod quickwit;
 pub mod ai_analyzer;
+pub mod ai_provider;
+pub mod highlight;
+pub mod patterns;
+pub mod fingerprint;
+pub mod issues;
+pub mod stack_trace;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod prompt_packer;
+pub mod ai_cache;
+pub mod redaction;
+pub mod prompt_template;
+pub mod query_translator;
+pub mod ai_sessions;
+pub mod ai_agent;
+pub mod json_store;
@@ -0,0 +1,230 @@
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+static UUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b")
+        .expect("valid regex")
+});
+static IP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}(?::\d{1,5})?\b").expect("valid regex")
+});
+static HEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:0x[0-9a-f]+|[0-9a-f]{6,})\b").expect("valid regex"));
+static NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d+(?:\.\d+)?").expect("valid regex"));
+
+/// 将日志消息归一化为模板：UUID、IP、十六进制 ID 和数字替换为占位符，空白合并。
+/// 先替换结构化的值，再替换剩余的数字
+pub fn normalize_message(message: &str) -> String {
+    let normalized = UUID.replace_all(message, "<UUID>");
+    let normalized = IP.replace_all(&normalized, "<IP>");
+    // 纯字母（如 facade）或纯数字的值交给后续规则处理，这里只替换同时包含数字和字母或带 0x 前缀的值
+    let normalized = HEX.replace_all(&normalized, |caps: &Captures| {
+        let value = &caps[0];
+        let has_digit = value.chars().any(|c| c.is_ascii_digit());
+        let has_letter = value.chars().any(|c| c.is_ascii_alphabetic());
+        if value.to_ascii_lowercase().starts_with("0x") || (has_digit && has_letter) {
+            "<HEX>".to_string()
+        } else {
+            value.to_string()
+        }
+    });
+    let normalized = NUMBER.replace_all(&normalized, "<NUM>");
+    normalized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 模板中表示可变部分的通配符
+pub const WILDCARD: &str = "<*>";
+
+/// Drain 前缀树使用的前缀 token 数
+const DRAIN_DEPTH: usize = 2;
+
+/// 默认相似度阈值：模板与消息相同位置 token 一致的比例达到该值时归为同一模式
+pub const DEFAULT_SIMILARITY: f64 = 0.5;
+
+/// 聚类结果：模板与属于该模板的消息下标
+#[derive(Debug, Clone)]
+pub struct PatternCluster {
+    pub template: String,
+    pub members: Vec<usize>,
+}
+
+struct Cluster {
+    tokens: Vec<String>,
+    members: Vec<usize>,
+}
+
+/// Drain 风格的日志模板聚类。
+///
+/// 消息先经 `normalize_message` 替换变量，再按 token 数和前缀 token 分组；
+/// 组内与已有模板逐位置比较，相似度达到阈值则合并，不同的位置替换为 `<*>`
+pub struct Drain {
+    similarity: f64,
+    clusters: Vec<Cluster>,
+    groups: HashMap<(usize, Vec<String>), Vec<usize>>,
+    next_member: usize,
+}
+
+impl Drain {
+    pub fn new(similarity: f64) -> Self {
+        Self {
+            similarity,
+            clusters: Vec::new(),
+            groups: HashMap::new(),
+            next_member: 0,
+        }
+    }
+
+    /// 加入一条消息，消息下标按加入顺序从 0 递增
+    pub fn add(&mut self, message: &str) {
+        let member = self.next_member;
+        self.next_member += 1;
+
+        let normalized = normalize_message(message);
+        let tokens: Vec<String> = normalized.split(' ').map(str::to_string).collect();
+        let group_key = (tokens.len(), group_prefix(&tokens));
+        let group = self.groups.entry(group_key).or_default();
+
+        let best = group
+            .iter()
+            .map(|&index| (index, similarity(&self.clusters[index].tokens, &tokens)))
+            .filter(|(_, score)| *score >= self.similarity)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index);
+
+        match best {
+            Some(index) => {
+                let cluster = &mut self.clusters[index];
+                for (template_token, token) in cluster.tokens.iter_mut().zip(&tokens) {
+                    if template_token != token {
+                        *template_token = WILDCARD.to_string();
+                    }
+                }
+                cluster.members.push(member);
+            }
+            None => {
+                group.push(self.clusters.len());
+                self.clusters.push(Cluster {
+                    tokens,
+                    members: vec![member],
+                });
+            }
+        }
+    }
+
+    /// 按消息数量从多到少返回所有模式
+    pub fn into_clusters(self) -> Vec<PatternCluster> {
+        let mut clusters: Vec<PatternCluster> = self
+            .clusters
+            .into_iter()
+            .map(|cluster| PatternCluster {
+                template: cluster.tokens.join(" "),
+                members: cluster.members,
+            })
+            .collect();
+        clusters.sort_by(|a, b| {
+            b.members
+                .len()
+                .cmp(&a.members.len())
+                .then_with(|| a.template.cmp(&b.template))
+        });
+        clusters
+    }
+}
+
+/// 前缀树的分组 key：取前几个 token，含占位符的 token 统一视为通配符
+fn group_prefix(tokens: &[String]) -> Vec<String> {
+    tokens
+        .iter()
+        .take(DRAIN_DEPTH)
+        .map(|token| {
+            if token.contains('<') {
+                WILDCARD.to_string()
+            } else {
+                token.clone()
+            }
+        })
+        .collect()
+}
+
+/// 相同位置 token 一致的比例，模板中的通配符不计入
+fn similarity(template: &[String], tokens: &[String]) -> f64 {
+    if tokens.is_empty() {
+        return 1.0;
+    }
+    let same = template
+        .iter()
+        .zip(tokens)
+        .filter(|(t, token)| *t != WILDCARD && t == token)
+        .count();
+    same as f64 / tokens.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cluster(messages: &[&str], similarity: f64) -> Vec<(String, Vec<usize>)> {
+        let mut drain = Drain::new(similarity);
+        for message in messages {
+            drain.add(message);
+        }
+        drain
+            .into_clusters()
+            .into_iter()
+            .map(|cluster| (cluster.template, cluster.members))
+            .collect()
+    }
+
+    #[test]
+    fn variable_tokens_are_masked() {
+        assert_eq!(
+            normalize_message(
+                "user 42 from 10.0.0.1:8080 req 3f2a9c1b-7d4e-4f6a-9b8c-0123456789ab \
+                 id 0xdeadbeef hash a1b2c3d4e5 facade   took 12.5ms"
+            ),
+            "user <NUM> from <IP> req <UUID> id <HEX> hash <HEX> facade took <NUM>ms"
+        );
+        assert_eq!(normalize_message("retry 3 of 5"), "retry <NUM> of <NUM>");
+    }
+
+    const TIMEOUTS: &[&str] = &[
+        "Connection to db-1 timed out after 30s",
+        "User alice logged in",
+        "Connection to db-2 timed out after 45s",
+        "Connection to cache timed out after 5s",
+    ];
+
+    #[test]
+    fn similar_messages_merge_into_wildcard_template() {
+        assert_eq!(
+            cluster(TIMEOUTS, DEFAULT_SIMILARITY),
+            vec![
+                (
+                    "Connection to <*> timed out after <NUM>s".to_string(),
+                    vec![0, 2, 3]
+                ),
+                ("User alice logged in".to_string(), vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn similarity_threshold_controls_merging() {
+        // 6/7 的 token 相同，阈值 0.9 时不合并
+        let clusters = cluster(TIMEOUTS, 0.9);
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(
+            clusters[0],
+            (
+                "Connection to db-<NUM> timed out after <NUM>s".to_string(),
+                vec![0, 2]
+            )
+        );
+
+        // token 数不同的消息不会合并
+        let clusters = cluster(&["disk full", "disk full on /dev/sda1"], 0.0);
+        assert_eq!(clusters.len(), 2);
+    }
+}
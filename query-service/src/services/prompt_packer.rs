@@ -0,0 +1,368 @@
+use crate::models::query::AiContextSummary;
+use crate::services::prompt_template::PromptLocale;
+use std::collections::HashMap;
+
+/// 单条错误日志（含堆栈摘要之外的部分）最多占用的 token 数
+const MAX_HEADER_TOKENS: usize = 300;
+
+/// 单个堆栈摘要最多占用的 token 数
+const MAX_STACK_TOKENS: usize = 400;
+
+/// 剩余预算不足该值时不再放入新的内容，避免只剩半行
+const MIN_SECTION_TOKENS: usize = 30;
+
+const SECTION_SEPARATOR: &str = "\n\n---\n\n";
+
+/// 截断处的标记
+fn truncated_marker(locale: PromptLocale) -> &'static str {
+    match locale {
+        PromptLocale::Zh => "…(已截断)",
+        PromptLocale::En => "…(truncated)",
+    }
+}
+
+/// 按模型估算 token 数。
+///
+/// 不加载分词器，按字符类别粗略估算：ASCII 字符按每 token 若干字符计算，
+/// 中文等非 ASCII 字符按每字符若干 token 计算。系数取偏大的值，宁可少放一些日志也不超出上下文
+#[derive(Debug, Clone, Copy)]
+pub struct TokenEstimator {
+    ascii_chars_per_token: f64,
+    non_ascii_tokens_per_char: f64,
+}
+
+impl TokenEstimator {
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_ascii_lowercase();
+        let (ascii_chars_per_token, non_ascii_tokens_per_char) =
+            if ["gpt-4o", "gpt-4.1", "gpt-5", "o1", "o3", "o4"]
+                .iter()
+                .any(|name| model.contains(name))
+            {
+                // o200k 词表对中文更友好
+                (4.0, 0.8)
+            } else if model.contains("gpt") {
+                (4.0, 1.2)
+            } else if ["qwen", "deepseek", "glm", "yi-", "baichuan"]
+                .iter()
+                .any(|name| model.contains(name))
+            {
+                // 针对中文优化的词表，常见汉字多为单 token 甚至多字一 token
+                (3.5, 0.8)
+            } else {
+                // Claude、Llama 及未知模型
+                (3.5, 1.2)
+            };
+
+        Self {
+            ascii_chars_per_token,
+            non_ascii_tokens_per_char,
+        }
+    }
+
+    pub fn estimate(&self, text: &str) -> usize {
+        text.chars().map(|c| self.char_cost(c)).sum::<f64>().ceil() as usize
+    }
+
+    fn char_cost(&self, c: char) -> f64 {
+        if c.is_ascii() {
+            1.0 / self.ascii_chars_per_token
+        } else {
+            self.non_ascii_tokens_per_char
+        }
+    }
+
+    /// 截断到 `max_tokens` 以内，只在字符边界截断，末尾加上 `locale` 语言的截断标记；
+    /// 返回是否发生了截断
+    pub fn truncate(&self, text: &str, max_tokens: usize, locale: PromptLocale) -> (String, bool) {
+        if self.estimate(text) <= max_tokens {
+            return (text.to_string(), false);
+        }
+
+        let marker = truncated_marker(locale);
+        let limit = max_tokens.saturating_sub(self.estimate(marker)) as f64;
+        let mut cost = 0.0;
+        let mut end = 0;
+        for (index, c) in text.char_indices() {
+            cost += self.char_cost(c);
+            if cost > limit {
+                break;
+            }
+            end = index + c.len_utf8();
+        }
+        (format!("{}{}", &text[..end], marker), true)
+    }
+}
+
+/// 待放入 prompt 的一条日志
+#[derive(Debug, Clone)]
+pub struct PromptEntry {
+    /// 去重 key，相同 key 的日志视为同一个错误（如错误指纹）
+    pub key: String,
+    /// 代表的日志条数，调用方已合并的日志（如同一模式的日志）大于 1，
+    /// 此时由 header 自行说明数量，只有按 key 再次合并的日志才会标注重复计数
+    pub count: usize,
+    /// 时间、级别、服务与消息
+    pub header: String,
+    /// 堆栈摘要
+    pub stack: Option<String>,
+}
+
+/// 打包结果：放入 prompt 的日志文本与省略情况
+#[derive(Debug, Clone)]
+pub struct PackedContext {
+    pub text: String,
+    pub summary: AiContextSummary,
+    /// 说明文字的语言
+    pub locale: PromptLocale,
+}
+
+impl PackedContext {
+    /// 提示模型哪些内容被省略，没有省略时返回 None
+    pub fn omission_note(&self) -> Option<String> {
+        let summary = &self.summary;
+        let zh = self.locale == PromptLocale::Zh;
+        let mut parts = Vec::new();
+        if summary.duplicate_logs > 0 {
+            parts.push(if zh {
+                format!("{} 条重复日志已合并计数", summary.duplicate_logs)
+            } else {
+                format!(
+                    "{} duplicate logs were merged into counts",
+                    summary.duplicate_logs
+                )
+            });
+        }
+        if summary.omitted_errors > 0 {
+            parts.push(if zh {
+                format!("另有 {} 类错误未列出", summary.omitted_errors)
+            } else {
+                format!(
+                    "{} more kinds of errors are not listed",
+                    summary.omitted_errors
+                )
+            });
+        }
+        if summary.truncated_logs > 0 {
+            parts.push(if zh {
+                format!("{} 条日志内容被截断", summary.truncated_logs)
+            } else {
+                format!("{} logs were truncated", summary.truncated_logs)
+            });
+        }
+        if summary.omitted_stack_traces > 0 {
+            parts.push(if zh {
+                format!("{} 个堆栈未列出", summary.omitted_stack_traces)
+            } else {
+                format!(
+                    "{} stack traces are not listed",
+                    summary.omitted_stack_traces
+                )
+            });
+        }
+        (!parts.is_empty()).then(|| {
+            if zh {
+                format!("（受上下文长度限制：{}）", parts.join("，"))
+            } else {
+                format!("(Limited by context length: {})", parts.join(", "))
+            }
+        })
+    }
+}
+
+/// 在 token 预算内挑选放入 prompt 的日志。
+///
+/// 先按 key 合并重复日志，然后按顺序为每类错误放入日志行，预算有余时再依次补充堆栈摘要。
+/// 因此预算紧张时优先保留不同的错误，其次是堆栈的开头部分，重复日志只保留计数
+pub struct ContextPacker {
+    estimator: TokenEstimator,
+    budget: usize,
+}
+
+struct Group {
+    entry: PromptEntry,
+    count: usize,
+}
+
+struct Section {
+    header: String,
+    stack: Option<String>,
+}
+
+impl ContextPacker {
+    pub fn new(estimator: TokenEstimator, budget: usize) -> Self {
+        Self { estimator, budget }
+    }
+
+    /// `locale` 为重复计数、截断标记等说明文字的语言
+    pub fn pack(&self, entries: Vec<PromptEntry>, locale: PromptLocale) -> PackedContext {
+        let total_logs = entries.iter().map(|entry| entry.count).sum();
+        let mut groups: Vec<Group> = Vec::new();
+        let mut index_by_key: HashMap<String, usize> = HashMap::new();
+        for entry in entries {
+            match index_by_key.get(&entry.key) {
+                Some(&index) => groups[index].count += entry.count,
+                None => {
+                    index_by_key.insert(entry.key.clone(), groups.len());
+                    let count = entry.count;
+                    groups.push(Group { entry, count });
+                }
+            }
+        }
+
+        let separator_tokens = self.estimator.estimate(SECTION_SEPARATOR);
+        let mut remaining = self.budget;
+        let mut summary = AiContextSummary {
+            total_logs,
+            distinct_errors: groups.len(),
+            ..Default::default()
+        };
+
+        // 第一轮：每类错误放入日志行
+        let mut sections: Vec<(Section, &Group)> = Vec::new();
+        for group in &groups {
+            let available = remaining.saturating_sub(separator_tokens);
+            if available < MIN_SECTION_TOKENS {
+                summary.omitted_errors += 1;
+                continue;
+            }
+
+            // 重复计数放在截断之后，保证不会被截掉
+            let merged = group.count - group.entry.count;
+            let count_note = (merged > 0).then(|| match locale {
+                PromptLocale::Zh => format!("\n(相同错误共 {} 条)", group.count),
+                PromptLocale::En => format!("\n({} occurrences of this error)", group.count),
+            });
+            let count_tokens = count_note
+                .as_deref()
+                .map_or(0, |note| self.estimator.estimate(note));
+            let (mut header, truncated) = self.estimator.truncate(
+                &group.entry.header,
+                MAX_HEADER_TOKENS
+                    .min(available)
+                    .saturating_sub(count_tokens),
+                locale,
+            );
+            if truncated {
+                summary.truncated_logs += 1;
+            }
+            if let Some(note) = count_note {
+                header.push_str(&note);
+            }
+
+            remaining = available - self.estimator.estimate(&header).min(available);
+            summary.included_errors += 1;
+            summary.duplicate_logs += merged;
+            sections.push((
+                Section {
+                    header,
+                    stack: None,
+                },
+                group,
+            ));
+        }
+
+        // 第二轮：按顺序补充堆栈摘要
+        for (section, group) in &mut sections {
+            let Some(stack) = &group.entry.stack else {
+                continue;
+            };
+            // 堆栈与日志行之间的换行
+            let available = remaining.saturating_sub(1);
+            if available < MIN_SECTION_TOKENS {
+                summary.omitted_stack_traces += 1;
+                continue;
+            }
+
+            let (stack, truncated) =
+                self.estimator
+                    .truncate(stack, MAX_STACK_TOKENS.min(available), locale);
+            if truncated {
+                summary.truncated_logs += 1;
+            }
+            remaining = available - self.estimator.estimate(&stack).min(available);
+            section.stack = Some(stack);
+        }
+
+        let text = sections
+            .iter()
+            .map(|(section, _)| match &section.stack {
+                Some(stack) => format!("{}\n{}", section.header, stack),
+                None => section.header.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(SECTION_SEPARATOR);
+        summary.estimated_tokens = self.estimator.estimate(&text);
+
+        PackedContext {
+            text,
+            summary,
+            locale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, header: &str, stack: Option<&str>) -> PromptEntry {
+        PromptEntry {
+            key: key.to_string(),
+            count: 1,
+            header: header.to_string(),
+            stack: stack.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn truncate_respects_char_boundaries() {
+        let estimator = TokenEstimator::for_model("claude-sonnet-4-5");
+        let text = "数据库连接超时，".repeat(100);
+        let (truncated, was_truncated) = estimator.truncate(&text, 50, PromptLocale::Zh);
+        assert!(was_truncated);
+        assert!(truncated.ends_with("…(已截断)"));
+        assert!(estimator.estimate(&truncated) <= 50);
+    }
+
+    #[test]
+    fn duplicates_are_collapsed_and_distinct_errors_kept_first() {
+        let packer = ContextPacker::new(TokenEstimator::for_model("gpt-4"), 120);
+        let stack = "at com.example.Foo.bar(Foo.java:10)\n".repeat(50);
+        let packed = packer.pack(
+            vec![
+                entry("a", "ERROR 订单创建失败", Some(&stack)),
+                entry("a", "ERROR 订单创建失败", Some(&stack)),
+                entry("b", "ERROR 库存扣减失败", Some(&stack)),
+            ],
+            PromptLocale::Zh,
+        );
+
+        assert_eq!(packed.summary.total_logs, 3);
+        assert_eq!(packed.summary.included_errors, 2);
+        assert_eq!(packed.summary.duplicate_logs, 1);
+        assert!(packed.text.contains("相同错误共 2 条"));
+        assert!(packed.text.contains("库存扣减失败"));
+        assert!(packed.summary.estimated_tokens <= 120);
+        assert!(packed.omission_note().is_some());
+    }
+
+    #[test]
+    fn notes_follow_the_locale() {
+        let packer = ContextPacker::new(TokenEstimator::for_model("gpt-4"), 60);
+        let packed = packer.pack(
+            vec![
+                entry("a", &"ERROR order failed ".repeat(20), None),
+                entry("a", "ERROR order failed", None),
+                entry("b", "ERROR stock failed", None),
+            ],
+            PromptLocale::En,
+        );
+
+        assert!(packed.text.contains("…(truncated)"));
+        assert!(packed.text.contains("(2 occurrences of this error)"));
+        let note = packed.omission_note().unwrap();
+        assert!(note.starts_with("(Limited by context length: "), "{}", note);
+        assert!(note.is_ascii(), "{}", note);
+    }
+}
@@ -0,0 +1,366 @@
+use config::{Config as ConfigBuilder, File, FileFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// 模板中可用的变量，以 `{{name}}` 引用
+pub const TEMPLATE_VARIABLES: [&str; 6] = [
+    "scope",
+    "trace_id",
+    "logs",
+    "services",
+    "time_range",
+    "language",
+];
+
+/// 模板目录中没有同名文件时使用的内置模板名
+pub const BUILTIN_TEMPLATE: &str = "default";
+
+/// 内置模板，与仓库中 config/prompts 下的同名文件是同一份内容
+const BUILTIN_FILES: [(&str, &str); 2] = [
+    ("default", include_str!("../../config/prompts/default.yaml")),
+    (
+        "default.en",
+        include_str!("../../config/prompts/default.en.yaml"),
+    ),
+];
+
+/// 提示词中固定文字（日志标题、省略说明等）与固定回复的语言：
+/// 回答语言为中文时使用中文，其他语言使用英文
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PromptLocale {
+    Zh,
+    En,
+}
+
+impl PromptLocale {
+    /// `language` 为 `language_name` 返回的语言名
+    pub fn for_language(language: &str) -> Self {
+        let language = language.trim();
+        if language.contains("中文") || language.eq_ignore_ascii_case("chinese") {
+            Self::Zh
+        } else {
+            Self::En
+        }
+    }
+
+    /// 模板文件名中的语言后缀，如 `default.en.yaml` 中的 `en`
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "zh" => Some(Self::Zh),
+            "en" => Some(Self::En),
+            _ => None,
+        }
+    }
+}
+
+/// 提示词模板，从模板目录中的 YAML/TOML/JSON 文件加载，文件名（不含扩展名）即模板名
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptTemplate {
+    #[serde(skip)]
+    pub name: String,
+
+    /// 模板说明，在模板列表中展示
+    #[serde(default)]
+    pub description: String,
+
+    /// 系统提示词
+    pub system: String,
+
+    /// 用户提示词，必须包含 `{{logs}}`
+    pub prompt: String,
+
+    /// 覆盖默认的 temperature
+    #[serde(default)]
+    pub temperature: Option<f32>,
+
+    /// 覆盖默认的最大输出 token 数
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+/// 渲染模板所需的变量
+pub struct TemplateVars<'a> {
+    /// 分析范围的说明：单个 trace 时为 `Trace ID: ...`，搜索结果时为查询语句与采样情况
+    pub scope: &'a str,
+    /// 分析单个 trace 时的 trace_id，否则为空
+    pub trace_id: &'a str,
+    pub logs: &'a str,
+    pub services: &'a str,
+    pub time_range: &'a str,
+    pub language: &'a str,
+}
+
+impl PromptTemplate {
+    pub fn render(&self, vars: &TemplateVars) -> String {
+        // logs 最后替换，日志内容中出现的 `{{...}}` 不会被当作变量
+        self.prompt
+            .replace("{{scope}}", vars.scope)
+            .replace("{{trace_id}}", vars.trace_id)
+            .replace("{{services}}", vars.services)
+            .replace("{{time_range}}", vars.time_range)
+            .replace("{{language}}", vars.language)
+            .replace("{{logs}}", vars.logs)
+    }
+
+    pub fn render_system(&self, language: &str) -> String {
+        self.system.replace("{{language}}", language)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.prompt.contains("{{logs}}") {
+            return Err("prompt must contain {{logs}}".to_string());
+        }
+        for text in [&self.system, &self.prompt] {
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(len) = rest[start..].find("}}") else {
+                    break;
+                };
+                let name = rest[start + 2..start + len].trim();
+                if !TEMPLATE_VARIABLES.contains(&name) {
+                    return Err(format!(
+                        "unknown variable {{{{{}}}}}, available: {}",
+                        name,
+                        TEMPLATE_VARIABLES.join(", ")
+                    ));
+                }
+                rest = &rest[start + len + 2..];
+            }
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err("temperature must be between 0 and 2".to_string());
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err("max_tokens must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 模板列表中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub description: String,
+}
+
+/// 已加载的提示词模板
+pub struct PromptTemplates {
+    templates: BTreeMap<String, Arc<PromptTemplate>>,
+    /// 按语言区分的版本，如 `default.en.yaml`，回答语言匹配时代替同名模板
+    localized: BTreeMap<(String, PromptLocale), Arc<PromptTemplate>>,
+    default_template: String,
+}
+
+impl PromptTemplates {
+    /// 加载目录中的模板，目录不存在时只有内置模板；任一模板无效时返回错误。
+    /// 文件名为 `<name>.<zh|en>.<ext>` 的模板是 `<name>` 的语言版本，没有同名模板时也作为该模板
+    pub fn load(dir: &str, default_template: &str) -> Result<Self, String> {
+        let mut templates = Self {
+            templates: BTreeMap::new(),
+            localized: BTreeMap::new(),
+            default_template: default_template.to_string(),
+        };
+        for (stem, source) in BUILTIN_FILES {
+            let template = parse(stem, File::from_str(source, FileFormat::Yaml))
+                .map_err(|e| format!("invalid builtin prompt template {}: {}", stem, e))?;
+            templates.insert(stem, template);
+        }
+
+        let dir = Path::new(dir);
+        if dir.is_dir() {
+            let entries = std::fs::read_dir(dir)
+                .map_err(|e| format!("failed to read prompt templates {}: {}", dir.display(), e))?;
+            let mut paths: Vec<_> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| matches!(ext, "yaml" | "yml" | "toml" | "json"))
+                })
+                .collect();
+            paths.sort();
+
+            for path in paths {
+                let stem = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid prompt template {}: file name is not valid UTF-8",
+                            path.display()
+                        )
+                    })?;
+                let template = parse(stem, File::from(path.as_path()))
+                    .map_err(|e| format!("invalid prompt template {}: {}", path.display(), e))?;
+                templates.insert(stem, template);
+            }
+        }
+
+        // 只有语言版本的模板也可以按名称使用
+        for ((name, _), template) in &templates.localized {
+            if !templates.templates.contains_key(name) {
+                templates.templates.insert(name.clone(), template.clone());
+            }
+        }
+
+        if !templates.templates.contains_key(default_template) {
+            return Err(format!(
+                "ai_analyzer.default_template '{}' not found in {}",
+                default_template,
+                dir.display()
+            ));
+        }
+        Ok(templates)
+    }
+
+    fn insert(&mut self, stem: &str, template: PromptTemplate) {
+        let template = Arc::new(template);
+        match split_stem(stem) {
+            (name, Some(locale)) => {
+                self.localized.insert((name.to_string(), locale), template);
+            }
+            (name, None) => {
+                self.templates.insert(name.to_string(), template);
+            }
+        }
+    }
+
+    /// 按名称查找模板，None 时返回默认模板；有 `locale` 对应的语言版本时返回该版本
+    pub fn get(&self, name: Option<&str>, locale: PromptLocale) -> Option<Arc<PromptTemplate>> {
+        let name = name.unwrap_or(&self.default_template);
+        self.localized
+            .get(&(name.to_string(), locale))
+            .or_else(|| self.templates.get(name))
+            .cloned()
+    }
+
+    pub fn default_template(&self) -> &str {
+        &self.default_template
+    }
+
+    pub fn list(&self) -> Vec<TemplateInfo> {
+        self.templates
+            .values()
+            .map(|template| TemplateInfo {
+                name: template.name.clone(),
+                description: template.description.clone(),
+            })
+            .collect()
+    }
+}
+
+/// 解析模板文件，`stem` 为不含扩展名的文件名，语言后缀不计入模板名
+fn parse<T>(stem: &str, source: File<T, FileFormat>) -> Result<PromptTemplate, String>
+where
+    File<T, FileFormat>: config::Source + Send + Sync + 'static,
+{
+    let mut template: PromptTemplate = ConfigBuilder::builder()
+        .add_source(source)
+        .build()
+        .and_then(|config| config.try_deserialize())
+        .map_err(|e| e.to_string())?;
+    template.name = split_stem(stem).0.to_string();
+    template.validate()?;
+    Ok(template)
+}
+
+/// 拆分文件名中的模板名与语言后缀，如 `default.en` 拆分为 `default` 与英文
+fn split_stem(stem: &str) -> (&str, Option<PromptLocale>) {
+    stem.rsplit_once('.')
+        .and_then(|(name, suffix)| Some((name, Some(PromptLocale::from_suffix(suffix)?))))
+        .unwrap_or((stem, None))
+}
+
+/// 回答语言：常见语言代码转换为语言名，其他值原样使用（如 "Deutsch"）
+pub fn language_name(language: &str) -> String {
+    match language.to_ascii_lowercase().as_str() {
+        "zh" | "zh-cn" | "zh-hans" => "中文".to_string(),
+        "zh-tw" | "zh-hant" => "繁體中文".to_string(),
+        "en" | "en-us" | "en-gb" => "English".to_string(),
+        "ja" => "日本語".to_string(),
+        "ko" => "한국어".to_string(),
+        _ => language.to_string(),
+    }
+}
+
+/// 校验请求中的语言参数，避免借此向提示词注入指令
+pub fn validate_language(language: &str) -> Result<(), String> {
+    let language = language.trim();
+    if language.is_empty() || language.chars().count() > 32 {
+        return Err("language must be 1-32 characters".to_string());
+    }
+    if language
+        .chars()
+        .any(|c| c.is_control() || matches!(c, '{' | '}'))
+    {
+        return Err("language contains invalid characters".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_follows_the_answer_language() {
+        assert_eq!(
+            PromptLocale::for_language(&language_name("zh")),
+            PromptLocale::Zh
+        );
+        assert_eq!(
+            PromptLocale::for_language(&language_name("zh-tw")),
+            PromptLocale::Zh
+        );
+        assert_eq!(
+            PromptLocale::for_language(&language_name("en")),
+            PromptLocale::En
+        );
+        assert_eq!(PromptLocale::for_language("Deutsch"), PromptLocale::En);
+    }
+
+    #[test]
+    fn builtin_template_has_an_english_version() {
+        let templates = PromptTemplates::load("does-not-exist", BUILTIN_TEMPLATE).unwrap();
+        let zh = templates.get(None, PromptLocale::Zh).unwrap();
+        let en = templates.get(None, PromptLocale::En).unwrap();
+        assert_eq!(zh.name, BUILTIN_TEMPLATE);
+        assert_eq!(en.name, BUILTIN_TEMPLATE);
+        assert!(zh.prompt.contains("日志内容"));
+        assert!(en.prompt.is_ascii(), "{}", en.prompt);
+        assert_eq!(templates.list().len(), 1);
+    }
+
+    #[test]
+    fn localized_files_override_by_language() {
+        let dir = std::env::temp_dir().join(format!("prompts-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let template = |text: &str| {
+            format!(
+                "description: \"{text}\"\nsystem: \"{text}\"\nprompt: \"{text} {{{{logs}}}}\"\n"
+            )
+        };
+        std::fs::write(dir.join("brief.en.yaml"), template("brief")).unwrap();
+        std::fs::write(dir.join("default.en.yaml"), template("custom")).unwrap();
+
+        let templates = PromptTemplates::load(dir.to_str().unwrap(), BUILTIN_TEMPLATE).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // 目录中的英文版本代替内置的英文版本，中文仍使用内置模板
+        let en = templates.get(None, PromptLocale::En).unwrap();
+        assert_eq!(en.system, "custom");
+        let zh = templates.get(None, PromptLocale::Zh).unwrap();
+        assert!(zh.prompt.contains("日志内容"));
+        // 只有英文版本的模板对所有语言可用
+        let brief = templates.get(Some("brief"), PromptLocale::Zh).unwrap();
+        assert_eq!(brief.name, "brief");
+        assert_eq!(brief.system, "brief");
+        let names: Vec<String> = templates.list().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["brief", "default"]);
+    }
+}
@@ -0,0 +1,378 @@
+use crate::error::AppError;
+use crate::models::query::{AiQueryResponse, SearchRequest, SEARCH_FIELDS};
+use crate::services::ai_analyzer::AiAnalyzerClient;
+use crate::services::ai_provider::ChatMessage;
+use crate::services::prompt_template::PromptLocale;
+use chrono::DateTime;
+use chrono_tz::Tz;
+use log::warn;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// 问题的最大长度（字符数）
+pub const MAX_QUESTION_CHARS: usize = 500;
+
+/// AI 输出无效时带上错误原因重新生成，最多尝试的次数
+const MAX_ATTEMPTS: usize = 2;
+
+/// 生成查询条件的最大输出 token 数
+const MAX_OUTPUT_TOKENS: u32 = 500;
+
+/// level 字段的取值
+const LEVELS: &[&str] = &["ERROR", "WARN", "INFO", "DEBUG"];
+
+fn system_prompt(locale: PromptLocale) -> &'static str {
+    match locale {
+        PromptLocale::Zh => "你是日志查询助手，负责把用户的问题转换为 Quickwit 日志搜索条件。只输出一个 JSON 对象，不要输出其他内容。",
+        PromptLocale::En => "You are a log query assistant that converts the user's question into Quickwit log search conditions. Output a single JSON object and nothing else.",
+    }
+}
+
+/// 查询语句中的字段引用，如 `level:ERROR` 中的 `level`
+static FIELD_REF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[\s(])[+-]?([A-Za-z_][A-Za-z0-9_.]*):").expect("valid regex")
+});
+
+/// 转换所需的上下文
+pub struct QueryContext<'a> {
+    /// 最近 24 小时出现过的服务
+    pub services: &'a [String],
+    /// 当前时间，用于理解 "10 点以来"、"昨天" 等说法
+    pub now: DateTime<Tz>,
+    /// 请求中的时区，原样放入生成的搜索请求
+    pub time_zone: Option<&'a str>,
+    /// 说明文字的语言名称
+    pub language: &'a str,
+}
+
+/// AI 输出的搜索条件
+#[derive(Debug, Deserialize)]
+struct GeneratedQuery {
+    #[serde(default)]
+    query: String,
+    #[serde(default)]
+    filters: HashMap<String, Value>,
+    #[serde(default)]
+    time_range_type: Option<String>,
+    #[serde(default)]
+    relative_time_key: Option<String>,
+    #[serde(default)]
+    relative_end_key: Option<String>,
+    #[serde(default)]
+    start_time: Option<String>,
+    #[serde(default)]
+    end_time: Option<String>,
+    #[serde(default)]
+    explanation: String,
+}
+
+/// 把自然语言问题转换为搜索请求。AI 的输出无法解析或校验不通过时，
+/// 带上错误原因重新生成一次
+pub async fn translate(
+    analyzer: &AiAnalyzerClient,
+    question: &str,
+    ctx: &QueryContext<'_>,
+) -> Result<AiQueryResponse, AppError> {
+    let mut last_error: Option<String> = None;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let prompt = build_prompt(question, ctx, last_error.as_deref());
+        let content = analyzer
+            .chat(
+                system_prompt(PromptLocale::for_language(ctx.language)).to_string(),
+                vec![ChatMessage::user(prompt)],
+                0.0,
+                MAX_OUTPUT_TOKENS,
+            )
+            .await?;
+        match parse_response(&content, ctx) {
+            Ok(response) => return Ok(response),
+            Err(e) => {
+                warn!(
+                    "AI returned an invalid query (attempt {}/{}): {}",
+                    attempt, MAX_ATTEMPTS, e
+                );
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(AppError::AiError(format!(
+        "AI returned an invalid query: {}",
+        last_error.unwrap_or_default()
+    )))
+}
+
+fn build_prompt(question: &str, ctx: &QueryContext, last_error: Option<&str>) -> String {
+    let locale = PromptLocale::for_language(ctx.language);
+    let services = match (ctx.services.is_empty(), locale) {
+        (false, _) => ctx.services.join(", "),
+        (true, PromptLocale::Zh) => "（未知）".to_string(),
+        (true, PromptLocale::En) => "(unknown)".to_string(),
+    };
+    let fields = SEARCH_FIELDS.join(", ");
+    let levels = LEVELS.join(", ");
+    let now = ctx.now.format("%Y-%m-%d %H:%M:%S %:z %A");
+    let tz = ctx.now.timezone().name();
+    let language = ctx.language;
+    let mut prompt = match locale {
+        PromptLocale::Zh => format!(
+            r#"把下面的问题转换为日志搜索条件。
+
+问题：{question}
+
+可用字段：{fields}
+level 取值：{levels}
+已知服务（service 字段）：{services}
+当前时间：{now}（时区 {tz}）
+
+输出格式（JSON）：
+{{
+  "query": "Lucene 查询语句，如 level:ERROR AND message:timeout，没有条件时为 *",
+  "filters": {{"字段": "值"}},
+  "time_range_type": "relative 或 absolute",
+  "relative_time_key": "相对起点：时长如 15m、2h、1d，或日期运算如 now/d（今天零点）、now/d+10h（今天 10 点）、now-1d/d（昨天零点）",
+  "relative_end_key": "相对终点，日期运算如 now/d，默认为 now",
+  "start_time": "绝对起点，RFC 3339 格式并带时区偏移",
+  "end_time": "绝对终点，RFC 3339 格式并带时区偏移",
+  "explanation": "使用 {language} 简要说明查询条件"
+}}
+
+要求：
+1. 只使用可用字段；filters 为精确匹配，服务名必须取自已知服务，无法确定时在 query 中匹配 message
+2. 问题没有提到时间时使用最近 15 分钟（relative_time_key 为 15m）
+3. 不要添加问题中没有的条件，不使用的字段输出 null"#
+        ),
+        PromptLocale::En => format!(
+            r#"Convert the question below into log search conditions.
+
+Question: {question}
+
+Available fields: {fields}
+level values: {levels}
+Known services (service field): {services}
+Current time: {now} (time zone {tz})
+
+Output format (JSON):
+{{
+  "query": "Lucene query, e.g. level:ERROR AND message:timeout, * when there is no condition",
+  "filters": {{"field": "value"}},
+  "time_range_type": "relative or absolute",
+  "relative_time_key": "relative start: a duration such as 15m, 2h, 1d, or date math such as now/d (midnight today), now/d+10h (10:00 today), now-1d/d (midnight yesterday)",
+  "relative_end_key": "relative end, date math such as now/d, defaults to now",
+  "start_time": "absolute start, RFC 3339 with a time zone offset",
+  "end_time": "absolute end, RFC 3339 with a time zone offset",
+  "explanation": "a short description of the conditions in {language}"
+}}
+
+Rules:
+1. Use only the available fields; filters are exact matches and service names must come from the known services, otherwise match message in query
+2. When the question does not mention a time, use the last 15 minutes (relative_time_key 15m)
+3. Do not add conditions the question does not ask for, and output null for unused fields"#
+        ),
+    };
+    if let Some(error) = last_error {
+        prompt.push_str(&match locale {
+            PromptLocale::Zh => format!("\n\n上一次的输出无效：{}\n请修正后重新输出。", error),
+            PromptLocale::En => format!(
+                "\n\nThe previous output was invalid: {}\nFix it and output again.",
+                error
+            ),
+        });
+    }
+    prompt
+}
+
+/// 解析并校验 AI 的输出；格式或校验错误返回错误原因，可疑但合法的条件作为提醒返回
+fn parse_response(content: &str, ctx: &QueryContext) -> Result<AiQueryResponse, String> {
+    // 兼容 ```json 代码块或 JSON 前后的说明文字
+    let json_text = content
+        .find('{')
+        .zip(content.rfind('}'))
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| &content[start..=end])
+        .ok_or("output is not a JSON object")?;
+    let generated: GeneratedQuery =
+        serde_json::from_str(json_text).map_err(|e| format!("invalid JSON: {}", e))?;
+
+    let mut filters = HashMap::new();
+    for (field, value) in generated.filters {
+        if !SEARCH_FIELDS.contains(&field.as_str()) {
+            return Err(format!(
+                "unknown filter field '{}', available: {}",
+                field,
+                SEARCH_FIELDS.join(", ")
+            ));
+        }
+        let value = match value {
+            Value::Null => continue,
+            Value::String(value) => value,
+            Value::Number(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+            _ => return Err(format!("filter '{}' must be a string", field)),
+        };
+        if !value.is_empty() {
+            filters.insert(field, value);
+        }
+    }
+
+    let query = match generated.query.trim() {
+        "" => "*".to_string(),
+        query => query.to_string(),
+    };
+    let time_range_type = generated.time_range_type.unwrap_or_else(|| {
+        if generated.start_time.is_some() {
+            "absolute".to_string()
+        } else {
+            "relative".to_string()
+        }
+    });
+    let time_range = if time_range_type == "relative" {
+        json!({
+            "relative_time_key": generated.relative_time_key.unwrap_or_else(|| "15m".to_string()),
+            "relative_end_key": generated.relative_end_key.filter(|key| key != "now"),
+        })
+    } else {
+        json!({
+            "start_time": generated.start_time,
+            "end_time": generated.end_time,
+        })
+    };
+
+    // 经过反序列化补齐分页、排序等默认值
+    let mut search = json!({
+        "query": query,
+        "filters": filters,
+        "time_range_type": time_range_type,
+        "time_zone": ctx.time_zone,
+    });
+    if let (Some(search), Some(time_range)) = (search.as_object_mut(), time_range.as_object()) {
+        search.extend(time_range.clone());
+    }
+    let search: SearchRequest =
+        serde_json::from_value(search).map_err(|e| format!("invalid time range: {}", e))?;
+    search.validate()?;
+
+    let warnings = check_query(&search, ctx.services);
+    Ok(AiQueryResponse {
+        search,
+        explanation: generated.explanation.trim().to_string(),
+        warnings,
+    })
+}
+
+/// 检查合法但可能有误的条件：未知字段、最近没有日志的服务、未知的日志级别
+fn check_query(search: &SearchRequest, services: &[String]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for capture in FIELD_REF.captures_iter(&search.query) {
+        let field = &capture[1];
+        if !SEARCH_FIELDS.contains(&field) {
+            let warning = format!("query references unknown field '{}'", field);
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+    if let Some(service) = search.filters.get("service") {
+        if !services.is_empty() && !services.contains(service) {
+            warnings.push(format!(
+                "service '{}' has no logs in the last 24 hours",
+                service
+            ));
+        }
+    }
+    if let Some(level) = search.filters.get("level") {
+        if !LEVELS.contains(&level.as_str()) {
+            warnings.push(format!(
+                "unknown level '{}', expected one of {}",
+                level,
+                LEVELS.join(", ")
+            ));
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn context(services: &[String]) -> QueryContext<'_> {
+        QueryContext {
+            services,
+            now: Utc
+                .with_ymd_and_hms(2026, 10, 18, 2, 30, 0)
+                .unwrap()
+                .with_timezone(&Tz::Asia__Shanghai),
+            time_zone: Some("Asia/Shanghai"),
+            language: "中文",
+        }
+    }
+
+    #[test]
+    fn fenced_output_becomes_validated_search_request() {
+        let services = vec!["payment".to_string(), "checkout".to_string()];
+        let content = r#"```json
+{"query": "message:timeout AND host:web-1", "filters": {"service": "payment", "env": "prod", "level": "fatal"},
+ "time_range_type": "relative", "relative_time_key": "now-1d/d+10h", "relative_end_key": null,
+ "start_time": null, "end_time": null, "explanation": "昨天 10 点以来 prod 环境 payment 的超时日志"}
+```"#;
+        let response = parse_response(content, &context(&services)).unwrap();
+
+        assert_eq!(response.search.query, "message:timeout AND host:web-1");
+        assert_eq!(response.search.filters["service"], "payment");
+        assert_eq!(response.search.time_range_type, "relative");
+        assert_eq!(
+            response.search.relative_time_key.as_deref(),
+            Some("now-1d/d+10h")
+        );
+        assert_eq!(response.search.time_zone.as_deref(), Some("Asia/Shanghai"));
+        assert_eq!(response.search.page, 1);
+        assert_eq!(response.warnings.len(), 1);
+        assert!(response.warnings[0].contains("fatal"));
+    }
+
+    #[test]
+    fn invalid_fields_and_time_ranges_are_rejected() {
+        let services = Vec::new();
+        let unknown_field = r#"{"query": "*", "filters": {"customer": "acme"}}"#;
+        assert!(parse_response(unknown_field, &context(&services))
+            .unwrap_err()
+            .contains("customer"));
+
+        let bad_range =
+            r#"{"query": "*", "time_range_type": "relative", "relative_time_key": "yesterday"}"#;
+        assert!(parse_response(bad_range, &context(&services)).is_err());
+
+        let unknown = parse_response(
+            r#"{"query": "status:500 AND level:ERROR"}"#,
+            &context(&services),
+        )
+        .unwrap();
+        assert_eq!(
+            unknown.warnings,
+            vec!["query references unknown field 'status'".to_string()]
+        );
+        assert_eq!(unknown.search.relative_time_key.as_deref(), Some("15m"));
+    }
+
+    #[test]
+    fn prompt_follows_the_requested_language() {
+        let services = Vec::new();
+        let english = QueryContext {
+            language: "English",
+            ..context(&services)
+        };
+        let prompt = build_prompt("errors since 10am", &english, Some("bad JSON"));
+        assert!(prompt.starts_with("Convert the question below"));
+        assert!(prompt.contains("(unknown)"));
+        assert!(
+            prompt.ends_with("The previous output was invalid: bad JSON\nFix it and output again.")
+        );
+
+        let chinese = build_prompt("10 点以来的错误", &context(&services), None);
+        assert!(chinese.starts_with("把下面的问题转换为日志搜索条件"));
+    }
+}
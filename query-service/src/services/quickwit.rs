@@ -1,33 +1,193 @@
+use crate::config::QuickwitConfig;
 use crate::error::AppError;
-use crate::models::query::{LogHit, SearchRequest, SearchResponse};
-use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use crate::metrics::Metrics;
+use crate::models::query::{
+    AggregationRequest, AggregationResponse, CacheStatus, FieldHighlight, LogHit, SearchRequest,
+    SearchResponse, SortKey, SortOrder, TermBucket,
+};
+use crate::models::time_range::parse_duration;
+use crate::services::cache::QueryCache;
+use crate::services::circuit_breaker::CircuitBreaker;
+use crate::services::highlight::{Highlighter, HIGHLIGHT_FIELDS};
+use chrono::{DateTime, Duration as ChronoDuration, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use futures_util::future::try_join_all;
 use log::warn;
-use reqwest::Client;
+use rand::Rng;
+use reqwest::{Client, StatusCode};
 use serde_json::{json, Value};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Duration as StdDuration;
 
+/// 单次退避的上限
+const MAX_RETRY_BACKOFF: StdDuration = StdDuration::from_secs(5);
+
+/// 采样时每页拉取的日志数（Quickwit 单页上限）
+const SAMPLE_PAGE_SIZE: usize = 1000;
+
+/// 分层采样时时间窗口等分的段数
+const SAMPLE_SLICES: i64 = 10;
+
+/// 时间窗口内的采样日志
+pub struct LogSample {
+    /// 采样的日志，按时间倒序
+    pub hits: Vec<LogHit>,
+    /// 每条日志代表的匹配日志数（所在时间段的匹配数 / 该段的采样数），全部取回时为 1
+    pub weights: Vec<f64>,
+    /// 匹配总数
+    pub total: u64,
+}
+
+impl LogSample {
+    /// 按采样权重估算一组日志（`hits` 中的下标）在全部匹配日志中的数量
+    pub fn estimate(&self, members: &[usize]) -> u64 {
+        members
+            .iter()
+            .map(|&index| self.weights[index])
+            .sum::<f64>()
+            .round() as u64
+    }
+}
+
+/// 单次搜索请求失败的处理方式
+enum Failure {
+    /// 连接失败、429 或 5xx，搜索是幂等的，可以重试
+    Retryable(AppError),
+    /// 超时或响应无法解析：计入熔断但不重试，超时重试只会让调用方等得更久
+    Fatal(AppError),
+    /// Quickwit 拒绝了查询（4xx）：Quickwit 本身正常，不计入熔断
+    Rejected(AppError),
+}
+
+impl Failure {
+    /// 请求未得到响应：超时不重试，连接失败等可以重试
+    fn send(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Failure::Fatal(send_error(e))
+        } else {
+            Failure::Retryable(send_error(e))
+        }
+    }
+
+    /// 非 2xx 响应：429 与 5xx 可以重试，其余 4xx 为查询被拒绝
+    fn status(status: StatusCode, error: AppError) -> Self {
+        if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+            Failure::Rejected(error)
+        } else {
+            Failure::Retryable(error)
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct QuickwitClient {
     base_url: String,
     index_id: String,
     client: Client,
+    metrics: Metrics,
+    cache: Option<Arc<QueryCache>>,
+    timeout: StdDuration,
+    max_retries: u32,
+    retry_backoff: StdDuration,
+    breaker: Arc<CircuitBreaker>,
 }
 
 impl QuickwitClient {
-    pub fn new(base_url: String, index_id: String) -> Self {
+    /// 配置已在启动时校验，这里解析失败时使用默认值
+    pub fn new(config: &QuickwitConfig, metrics: Metrics, cache: Option<QueryCache>) -> Self {
+        let duration = |value: &str, default: StdDuration| {
+            parse_duration(value)
+                .ok()
+                .and_then(|duration| duration.to_std().ok())
+                .unwrap_or(default)
+        };
+        let timeout = duration(&config.timeout, StdDuration::from_secs(30));
+
         let client = Client::builder()
-            .timeout(StdDuration::from_secs(30))
+            .timeout(timeout)
             .build()
             .expect("Failed to build HTTP client");
 
+        metrics.set_quickwit_circuit_open(false);
+
         Self {
-            base_url,
-            index_id,
+            base_url: config.base_url.clone(),
+            index_id: config.index_id.clone(),
             client,
+            metrics,
+            cache: cache.map(Arc::new),
+            timeout,
+            max_retries: config.max_retries,
+            retry_backoff: duration(&config.retry_backoff, StdDuration::from_millis(200)),
+            breaker: Arc::new(CircuitBreaker::new(
+                "Quickwit",
+                config.breaker_failure_threshold,
+                duration(&config.breaker_cooldown, StdDuration::from_secs(30)),
+            )),
+        }
+    }
+
+    /// 替换查询缓存，沿用连接与熔断状态；只有缓存配置变化的重载使用
+    pub fn with_cache(&self, cache: Option<QueryCache>) -> Self {
+        Self {
+            base_url: self.base_url.clone(),
+            index_id: self.index_id.clone(),
+            client: self.client.clone(),
+            metrics: self.metrics.clone(),
+            cache: cache.map(Arc::new),
+            timeout: self.timeout,
+            max_retries: self.max_retries,
+            retry_backoff: self.retry_backoff,
+            breaker: self.breaker.clone(),
         }
     }
 
+    /// 检查 Quickwit 节点是否就绪
+    pub async fn check_health(&self) -> Result<(), AppError> {
+        let url = format!("{}/health/readyz", self.base_url);
+        let response = self
+            .client
+            .get(&url)
+            .timeout(StdDuration::from_secs(5))
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        if !response.status().is_success() {
+            return Err(AppError::QuickwitUnavailable(format!(
+                "Quickwit is not ready: {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// 检查配置的索引是否存在
+    pub async fn check_index(&self) -> Result<(), AppError> {
+        let url = format!("{}/api/v1/indexes/{}", self.base_url, self.index_id);
+        let response = self
+            .client
+            .get(&url)
+            .timeout(StdDuration::from_secs(5))
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(AppError::IndexNotFound(format!(
+                "index '{}' does not exist",
+                self.index_id
+            )));
+        }
+        if !response.status().is_success() {
+            return Err(status_error(response).await);
+        }
+
+        Ok(())
+    }
+
     pub async fn list_services(&self) -> Result<Vec<String>, AppError> {
         let end_time = Utc::now();
         let start_time = end_time - ChronoDuration::days(1);
@@ -47,26 +207,9 @@ impl QuickwitClient {
             }
         });
 
-        let url = format!("{}/api/v1/{}/search", self.base_url, self.index_id);
-        let response = self
-            .client
-            .post(&url)
-            .json(&query)
-            .send()
-            .await
-            .map_err(|e| AppError::QuickwitError(e.to_string()))?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AppError::QuickwitError(error_text));
-        }
+        let qw_response = self.post_search("list_services", &query).await?;
 
-        let qw_response: Value = response
-            .json()
-            .await
-            .map_err(|e| AppError::QuickwitError(e.to_string()))?;
-
-        if let Some(buckets) = extract_buckets(&qw_response) {
+        if let Some(buckets) = extract_buckets(&qw_response, "services") {
             let mut services: Vec<String> = buckets
                 .iter()
                 .filter_map(|bucket| bucket.get("key").and_then(|k| k.as_str()))
@@ -90,12 +233,18 @@ impl QuickwitClient {
                 filters: Default::default(),
                 time_range_type: "absolute".to_string(),
                 relative_time_key: None,
+                relative_end_key: None,
+                time_zone: None,
                 start_time: Some(start_time),
                 end_time: Some(end_time),
                 page,
                 page_size,
                 sort_by: "timestamp".to_string(),
                 sort_desc: true,
+                sort: Vec::new(),
+                highlight: false,
+                frames: false,
+                no_cache: false,
             };
 
             let response = self.search(&fallback_req, start_time, end_time).await?;
@@ -129,36 +278,381 @@ impl QuickwitClient {
         req: &SearchRequest,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
+    ) -> Result<SearchResponse, AppError> {
+        self.search_with(req, start_time, end_time, true).await
+    }
+
+    /// 不读写查询缓存的搜索，用于后台任务，避免挤出用户查询的缓存
+    pub async fn search_uncached(
+        &self,
+        req: &SearchRequest,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<SearchResponse, AppError> {
+        self.search_with(req, start_time, end_time, false).await
+    }
+
+    async fn search_with(
+        &self,
+        req: &SearchRequest,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        use_cache: bool,
     ) -> Result<SearchResponse, AppError> {
         // 构建查询
-        let query = self.build_query(req, start_time, end_time);
+        let (query, key) = self.request_with_key(
+            "search",
+            req,
+            start_time,
+            end_time,
+            Utc::now(),
+            |start, end| self.build_query(req, start, end),
+        );
 
         // 发送请求
-        let url = format!("{}/api/v1/{}/search", self.base_url, self.index_id);
         let start = std::time::Instant::now();
+        let (qw_response, cache) = self
+            .cached_search(
+                "search",
+                &query,
+                use_cache.then_some(key),
+                end_time,
+                req.no_cache,
+            )
+            .await?;
+
+        let took_ms = start.elapsed().as_millis() as u64;
+
+        // 转换响应
+        let mut response = self.convert_response(&qw_response, req, took_ms)?;
+        response.cache = cache;
+        Ok(response)
+    }
+
+    /// 采样时间窗口内的 `sample_size` 条日志（时间戳按 `tz` 渲染）。匹配数不超过采样数时全部取回；
+    /// 否则把窗口等分为若干段，按各段的匹配数分配采样数，每段取最近的日志，
+    /// 避免日志集中在窗口末尾时只采到最后几分钟
+    pub async fn sample(
+        &self,
+        req: &SearchRequest,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        sample_size: usize,
+        tz: Tz,
+    ) -> Result<LogSample, AppError> {
+        let slices = split_window(start_time, end_time, SAMPLE_SLICES);
+        let totals: Vec<u64> = try_join_all(slices.iter().map(|&(start, end)| async move {
+            let count_req = req.for_window(start, end, 1, 0);
+            Ok::<_, AppError>(self.search(&count_req, start, end).await?.total)
+        }))
+        .await?;
+        let total: u64 = totals.iter().sum();
+
+        if total <= sample_size as u64 {
+            let (hits, _) = self
+                .recent(req, start_time, end_time, sample_size, tz)
+                .await?;
+            let weights = vec![1.0; hits.len()];
+            return Ok(LogSample {
+                hits,
+                weights,
+                total,
+            });
+        }
+
+        // 从最近的时间段开始，拼接后整体仍按时间倒序
+        let quotas = allocate_samples(sample_size, &totals);
+        let parts = try_join_all(
+            slices
+                .iter()
+                .zip(&quotas)
+                .rev()
+                .filter(|(_, &quota)| quota > 0)
+                .map(|(&(start, end), &quota)| self.recent(req, start, end, quota, tz)),
+        )
+        .await?;
+
+        let mut hits = Vec::new();
+        let mut weights = Vec::new();
+        for (part, slice_total) in parts {
+            let weight = slice_total as f64 / part.len().max(1) as f64;
+            weights.extend(std::iter::repeat_n(weight, part.len()));
+            hits.extend(part);
+        }
+        Ok(LogSample {
+            hits,
+            weights,
+            total,
+        })
+    }
+
+    /// 分页拉取时间窗口内最近的 `size` 条日志，同时返回窗口内的匹配总数
+    async fn recent(
+        &self,
+        req: &SearchRequest,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        size: usize,
+        tz: Tz,
+    ) -> Result<(Vec<LogHit>, u64), AppError> {
+        let mut hits: Vec<LogHit> = Vec::new();
+        let mut total = 0;
+        let mut page = 1;
+        while hits.len() < size {
+            let page_size = SAMPLE_PAGE_SIZE.min(size - hits.len());
+            let page_req = req.for_window(start_time, end_time, page, page_size);
+            let response = self
+                .search(&page_req, start_time, end_time)
+                .await?
+                .with_time_zone(tz);
+            total = response.total;
+
+            let fetched = response.hits.len();
+            hits.extend(response.hits);
+            if fetched < page_size || hits.len() as u64 >= total {
+                break;
+            }
+            page += 1;
+        }
+        Ok((hits, total))
+    }
+
+    /// 词项聚合：统计匹配日志在 `field` 上各取值的数量
+    pub async fn aggregate(
+        &self,
+        req: &AggregationRequest,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<AggregationResponse, AppError> {
+        let (query, key) = self.request_with_key(
+            "aggregate",
+            &req.search,
+            start_time,
+            end_time,
+            Utc::now(),
+            |start, end| self.aggregate_body(req, start, end),
+        );
+
+        let start = std::time::Instant::now();
+        let (qw_response, cache) = self
+            .cached_search(
+                "aggregate",
+                &query,
+                Some(key),
+                end_time,
+                req.search.no_cache,
+            )
+            .await?;
+        let took_ms = start.elapsed().as_millis() as u64;
+
+        let buckets = extract_buckets(&qw_response, "terms")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|bucket| {
+                Some(TermBucket {
+                    key: bucket.get("key")?.clone(),
+                    doc_count: bucket.get("doc_count")?.as_u64()?,
+                })
+            })
+            .collect();
+
+        Ok(AggregationResponse {
+            total: qw_response["num_hits"].as_u64().unwrap_or(0),
+            buckets,
+            took_ms,
+            cache,
+        })
+    }
 
+    /// 词项聚合的请求体：只返回聚合结果，不返回日志
+    fn aggregate_body(
+        &self,
+        req: &AggregationRequest,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Value {
+        let mut query = self.build_query(&req.search, start_time, end_time);
+        if let Some(body) = query.as_object_mut() {
+            body.remove("sort_by");
+            body.remove("snippet_fields");
+            body.insert("max_hits".to_string(), json!(0));
+            body.insert("start_offset".to_string(), json!(0));
+            body.insert(
+                "aggs".to_string(),
+                json!({
+                    "terms": {
+                        "terms": {
+                            "field": req.field,
+                            "size": req.size
+                        }
+                    }
+                }),
+            );
+        }
+        query
+    }
+
+    /// 发给 Quickwit 的请求体与缓存 key。请求体始终使用请求的时间窗口；
+    /// 相对时间窗口的 key 按对齐到缓存周期的窗口计算，使连续的相同查询可以命中缓存
+    fn request_with_key(
+        &self,
+        operation: &str,
+        req: &SearchRequest,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        now: DateTime<Utc>,
+        build: impl Fn(DateTime<Utc>, DateTime<Utc>) -> Value,
+    ) -> (Value, String) {
+        let body = build(start_time, end_time);
+        let key = match &self.cache {
+            Some(cache) if req.time_range_type == "relative" => {
+                let (start, end) = cache.align_window_at(start_time, end_time, now);
+                QueryCache::key(operation, &build(start, end))
+            }
+            _ => QueryCache::key(operation, &body),
+        };
+        (body, key)
+    }
+
+    /// 带缓存的搜索请求：命中时不访问 Quickwit；`no_cache` 时跳过查找，结果仍写入缓存。
+    /// `key` 为 None 时不使用缓存
+    async fn cached_search(
+        &self,
+        operation: &str,
+        body: &Value,
+        key: Option<String>,
+        end_time: DateTime<Utc>,
+        no_cache: bool,
+    ) -> Result<(Arc<Value>, CacheStatus), AppError> {
+        let (cache, key) = match (&self.cache, key) {
+            (Some(cache), Some(key)) if !cache.ttl_for(end_time).is_zero() => (cache, key),
+            _ => {
+                let value = self.post_search(operation, body).await?;
+                return Ok((Arc::new(value), CacheStatus::Disabled));
+            }
+        };
+
+        if no_cache {
+            cache.observe_bypass();
+        } else if let Some(hit) = cache.get(&key) {
+            return Ok(hit);
+        }
+
+        let value = Arc::new(self.post_search(operation, body).await?);
+        // 按返回时的时间重新计算，查询期间窗口可能已经变为完全过去
+        let ttl = cache.ttl_for(end_time);
+        cache.insert(key, value.clone(), ttl);
+
+        let ttl_secs = ttl.as_secs();
+        let status = if no_cache {
+            CacheStatus::Bypass { ttl_secs }
+        } else {
+            CacheStatus::Miss { ttl_secs }
+        };
+        Ok((value, status))
+    }
+
+    /// 向 Quickwit 发送搜索请求，并记录调用耗时与失败次数。
+    ///
+    /// 连接失败或 5xx 时按指数退避加随机抖动重试；连续失败达到阈值后熔断，
+    /// 熔断期间直接返回 `QuickwitUnavailable`，不再访问 Quickwit
+    async fn post_search(&self, operation: &str, body: &Value) -> Result<Value, AppError> {
+        let url = format!("{}/api/v1/{}/search", self.base_url, self.index_id);
+        let mut attempt: u32 = 0;
+
+        loop {
+            if let Err(remaining) = self.breaker.allow() {
+                self.metrics.observe_quickwit_rejected(operation);
+                return Err(AppError::QuickwitUnavailable(format!(
+                    "circuit open after repeated failures, retry in {}s",
+                    remaining.as_secs().max(1)
+                )));
+            }
+
+            let start = std::time::Instant::now();
+            let result = self.send_search(&url, body).await;
+            self.metrics
+                .observe_quickwit(operation, start.elapsed(), result.is_ok());
+
+            let error = match result {
+                Ok(value) => {
+                    self.record_outcome(true);
+                    return Ok(value);
+                }
+                Err(Failure::Rejected(error)) => {
+                    self.record_outcome(true);
+                    return Err(error);
+                }
+                Err(Failure::Fatal(error)) => {
+                    self.record_outcome(false);
+                    return Err(error);
+                }
+                Err(Failure::Retryable(error)) => {
+                    self.record_outcome(false);
+                    error
+                }
+            };
+
+            if attempt >= self.max_retries || self.breaker.is_open() {
+                return Err(error);
+            }
+            attempt += 1;
+
+            let backoff = self.backoff(attempt);
+            warn!(
+                "Quickwit {} failed ({}), retrying in {:?} ({}/{})",
+                operation, error, backoff, attempt, self.max_retries
+            );
+            self.metrics.observe_quickwit_retry(operation);
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    async fn send_search(&self, url: &str, body: &Value) -> Result<Value, Failure> {
         let response = self
             .client
-            .post(&url)
-            .json(&query)
+            .post(url)
+            .timeout(self.timeout)
+            .json(body)
             .send()
             .await
-            .map_err(|e| AppError::QuickwitError(e.to_string()))?;
+            .map_err(Failure::send)?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AppError::QuickwitError(error_text));
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Failure::status(status, status_error(response).await));
         }
 
-        let qw_response: Value = response
-            .json()
-            .await
-            .map_err(|e| AppError::QuickwitError(e.to_string()))?;
+        response.json::<Value>().await.map_err(|e| {
+            if e.is_timeout() {
+                Failure::Fatal(send_error(e))
+            } else {
+                Failure::Fatal(AppError::QuickwitError(format!(
+                    "Failed to parse Quickwit response: {}",
+                    e
+                )))
+            }
+        })
+    }
 
-        let took_ms = start.elapsed().as_millis() as u64;
+    fn record_outcome(&self, success: bool) {
+        if success {
+            self.breaker.record_success();
+        } else {
+            self.breaker.record_failure();
+        }
+        self.metrics
+            .set_quickwit_circuit_open(self.breaker.is_open());
+    }
 
-        // 转换响应
-        self.convert_response(qw_response, req, took_ms)
+    /// 第 n 次重试的等待时长：`retry_backoff * 2^(n-1)`，取其一半加上随机的另一半
+    fn backoff(&self, attempt: u32) -> StdDuration {
+        let base = self
+            .retry_backoff
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_RETRY_BACKOFF);
+        let half = base / 2;
+        half + half.mul_f64(rand::rng().random::<f64>())
     }
 
     fn build_query(
@@ -170,48 +664,57 @@ impl QuickwitClient {
         // 构建查询字符串
         let mut query_parts = vec![req.query.clone()];
 
-        // 添加过滤条件
-        for (field, value) in &req.filters {
+        // 添加过滤条件（按字段名排序，相同的过滤条件生成相同的请求体，便于缓存）
+        let mut filters: Vec<_> = req.filters.iter().collect();
+        filters.sort();
+        for (field, value) in filters {
             query_parts.push(format!("{}:{}", field, value));
         }
 
-        let query_string = if query_parts.iter().all(|s| s.is_empty()) {
+        // 亚秒级时间边界：start_timestamp/end_timestamp 只能精确到秒，
+        // 此时用秒级窗口粗筛，再附加 timestamp 字段的精确范围条件
+        if let Some(range) = precise_time_range(start_time, end_time) {
+            query_parts.push(range);
+        }
+
+        // 每个部分加上括号，查询语句中的 OR 不会改变过滤条件与时间范围的作用范围
+        let query_parts: Vec<String> = query_parts
+            .iter()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty() && *s != "*")
+            .map(|s| format!("({})", s))
+            .collect();
+        let query_string = if query_parts.is_empty() {
             "*".to_string()
         } else {
-            query_parts
-                .into_iter()
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<_>>()
-                .join(" AND ")
+            query_parts.join(" AND ")
         };
 
         // 分页
         let offset = (req.page - 1) * req.page_size;
 
-        // 排序 - Quickwit的排序逻辑反了：
-        // 不带 "-" (如 "timestamp") 返回的是倒序（最新优先）
-        // 带 "-" (如 "-timestamp") 返回的是正序（最旧优先）
-        // 所以如果API要求降序（sort_desc=true），我们发送不带 "-" 的字段
-        // 如果API要求升序（sort_desc=false），我们发送带 "-" 的字段
-        let sort_field = if req.sort_desc {
-            req.sort_by.clone() // 不带 "-"，Quickwit会返回倒序
-        } else {
-            format!("-{}", req.sort_by) // 带 "-"，Quickwit会返回正序
-        };
+        // 排序
+        let sort_by = quickwit_sort_by(&req.sort_keys());
 
-        json!({
+        let mut query = json!({
             "query": query_string,
             "start_timestamp": start_time.timestamp(),
-            "end_timestamp": end_time.timestamp(),
+            "end_timestamp": ceil_timestamp(end_time),
             "max_hits": req.page_size,
             "start_offset": offset,
-            "sort_by": sort_field
-        })
+            "sort_by": sort_by
+        });
+
+        if req.highlight {
+            query["snippet_fields"] = json!(HIGHLIGHT_FIELDS);
+        }
+
+        query
     }
 
     fn convert_response(
         &self,
-        qw_response: Value,
+        qw_response: &Value,
         req: &SearchRequest,
         took_ms: u64,
     ) -> Result<SearchResponse, AppError> {
@@ -222,11 +725,20 @@ impl QuickwitClient {
         let total = qw_response["num_hits"].as_u64().unwrap_or(0);
 
         let mut hits: Vec<LogHit> = Vec::new();
-        let mut parse_errors = 0;
+        let mut parse_errors: u64 = 0;
+
+        let highlighter = req.highlight.then(|| Highlighter::from_query(&req.query));
+        let snippets = qw_response["snippets"].as_array();
 
-        for hit in hits_array {
+        for (i, hit) in hits_array.iter().enumerate() {
             match serde_json::from_value::<LogHit>(hit.clone()) {
-                Ok(log_hit) => hits.push(log_hit),
+                Ok(mut log_hit) => {
+                    if let Some(highlighter) = &highlighter {
+                        let hit_snippets = snippets.and_then(|s| s.get(i));
+                        log_hit.highlights = highlight_hit(highlighter, &log_hit, hit_snippets);
+                    }
+                    hits.push(log_hit);
+                }
                 Err(_e) => {
                     parse_errors += 1;
                     // 仅在调试模式下输出错误信息
@@ -236,6 +748,14 @@ impl QuickwitClient {
             }
         }
 
+        self.metrics.add_hit_parse_failures(parse_errors);
+
+        // 排序字段相同的日志在页内按固定规则排序，重复查询时页内顺序稳定。
+        // 跨页的并列由 Quickwit 按内部文档地址决定（最多两个排序字段，无法附加兜底字段），
+        // split 合并后可能变化，恰好落在页边界的并列日志可能重复或遗漏
+        let sort_keys = req.sort_keys();
+        hits.sort_by(|a, b| a.cmp_by_keys(b, &sort_keys));
+
         // 如果有显著的解析失败，记录警告
         if parse_errors > 0 && parse_errors as f64 / hits_array.len() as f64 > 0.1 {
             eprintln!(
@@ -251,14 +771,50 @@ impl QuickwitClient {
             page: req.page,
             page_size: req.page_size,
             took_ms,
+            cache: CacheStatus::Disabled,
         })
     }
 }
 
-fn extract_buckets(value: &Value) -> Option<Vec<Value>> {
+/// 发送请求失败时的错误：超时与连接失败分别映射为 504 与 503
+fn send_error(e: reqwest::Error) -> AppError {
+    if e.is_timeout() {
+        AppError::QuickwitTimeout(e.to_string())
+    } else if e.is_connect() {
+        AppError::QuickwitUnavailable(e.to_string())
+    } else {
+        AppError::QuickwitError(e.to_string())
+    }
+}
+
+async fn status_error(response: reqwest::Response) -> AppError {
+    let status = response.status();
+    let error_text = response.text().await.unwrap_or_default();
+    error_for_status(status, error_text)
+}
+
+/// 非 2xx 响应的错误：4xx 为查询本身有误，429 与 502/503/504 表示 Quickwit 暂不可用
+fn error_for_status(status: StatusCode, error_text: String) -> AppError {
+    let message = if error_text.trim().is_empty() {
+        status.to_string()
+    } else {
+        error_text
+    };
+
+    match status {
+        StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT
+        | StatusCode::TOO_MANY_REQUESTS => AppError::QuickwitUnavailable(message),
+        status if status.is_client_error() => AppError::BadQuery(message),
+        _ => AppError::QuickwitError(message),
+    }
+}
+
+fn extract_buckets(value: &Value, name: &str) -> Option<Vec<Value>> {
     let from_aggs = value
         .get("aggs")
-        .and_then(|aggs| aggs.get("services"))
+        .and_then(|aggs| aggs.get(name))
         .and_then(|agg| agg.get("buckets"))
         .and_then(|buckets| buckets.as_array().cloned());
 
@@ -268,7 +824,352 @@ fn extract_buckets(value: &Value) -> Option<Vec<Value>> {
 
     value
         .get("aggregations")
-        .and_then(|aggs| aggs.get("services"))
+        .and_then(|aggs| aggs.get(name))
         .and_then(|agg| agg.get("buckets"))
         .and_then(|buckets| buckets.as_array().cloned())
 }
+
+/// 为 message / stack_trace 生成高亮，没有任何匹配时返回 None
+fn highlight_hit(
+    highlighter: &Highlighter,
+    hit: &LogHit,
+    snippets: Option<&Value>,
+) -> Option<HashMap<String, FieldHighlight>> {
+    let fields = [
+        ("message", Some(hit.message.as_str())),
+        ("stack_trace", hit.stack_trace.as_deref()),
+    ];
+
+    let highlights: HashMap<String, FieldHighlight> = fields
+        .into_iter()
+        .filter_map(|(field, text)| {
+            let field_snippets = snippets.and_then(|s| s.get(field));
+            highlighter
+                .highlight(text?, field_snippets)
+                .map(|h| (field.to_string(), h))
+        })
+        .collect();
+
+    (!highlights.is_empty()).then_some(highlights)
+}
+
+/// 生成 Quickwit 的 sort_by 参数。
+///
+/// Quickwit 的排序符号与直觉相反：
+/// 不带 "-"（如 "timestamp"）返回的是倒序（最新优先），
+/// 带 "-"（如 "-timestamp"）返回的是正序（最旧优先）。
+/// 所有排序参数都应通过此函数生成，不要在其他地方处理符号
+fn quickwit_sort_by(keys: &[SortKey]) -> String {
+    keys.iter()
+        .map(|key| match key.order {
+            SortOrder::Desc => key.field.clone(),
+            SortOrder::Asc => format!("-{}", key.field),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 把时间窗口等分为 `count` 段，按时间顺序返回
+fn split_window(
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    count: i64,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let span = (end_time - start_time).num_microseconds().unwrap_or(i64::MAX);
+    let bound = |index: i64| {
+        if index == count {
+            end_time
+        } else {
+            start_time + ChronoDuration::microseconds(span / count * index)
+        }
+    };
+    (0..count).map(|index| (bound(index), bound(index + 1))).collect()
+}
+
+/// 按各段的匹配数分配采样数：每次分给 匹配数 / (已分配 + 1) 最大的段，
+/// 分配结果与匹配数成比例且不超过匹配数
+fn allocate_samples(sample_size: usize, totals: &[u64]) -> Vec<usize> {
+    let mut quotas = vec![0; totals.len()];
+    for _ in 0..sample_size {
+        let share = |index: usize| totals[index] as f64 / (quotas[index] + 1) as f64;
+        let next = (0..totals.len())
+            .filter(|&index| (quotas[index] as u64) < totals[index])
+            .max_by(|&a, &b| share(a).total_cmp(&share(b)));
+        match next {
+            Some(index) => quotas[index] += 1,
+            None => break,
+        }
+    }
+    quotas
+}
+
+/// Quickwit 的 end_timestamp 为开区间且精确到秒，有亚秒部分时需向上取整才能覆盖整个窗口
+fn ceil_timestamp(time: DateTime<Utc>) -> i64 {
+    if time.timestamp_subsec_nanos() > 0 {
+        time.timestamp() + 1
+    } else {
+        time.timestamp()
+    }
+}
+
+/// 起止时间任一带有亚秒部分时，生成 `timestamp:[start TO end}` 精确范围条件（左闭右开）
+fn precise_time_range(start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Option<String> {
+    if start_time.timestamp_subsec_nanos() == 0 && end_time.timestamp_subsec_nanos() == 0 {
+        return None;
+    }
+
+    Some(format!(
+        "timestamp:[{} TO {}}}",
+        start_time.to_rfc3339_opts(SecondsFormat::Micros, true),
+        end_time.to_rfc3339_opts(SecondsFormat::Micros, true)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::time_range::resolve_relative;
+
+    #[test]
+    fn sort_by_inverts_sign_for_quickwit() {
+        assert_eq!(
+            quickwit_sort_by(&[SortKey::new("timestamp", SortOrder::Desc)]),
+            "timestamp"
+        );
+        assert_eq!(
+            quickwit_sort_by(&[SortKey::new("timestamp", SortOrder::Asc)]),
+            "-timestamp"
+        );
+    }
+
+    #[test]
+    fn sort_by_joins_multiple_keys() {
+        let keys = [
+            SortKey::new("level", SortOrder::Asc),
+            SortKey::new("timestamp", SortOrder::Desc),
+        ];
+        assert_eq!(quickwit_sort_by(&keys), "-level,timestamp");
+    }
+
+    fn client() -> QuickwitClient {
+        let config: QuickwitConfig = serde_json::from_value(json!({
+            "base_url": "http://127.0.0.1:7280",
+            "index_id": "logs"
+        }))
+        .unwrap();
+        let metrics = Metrics::new();
+        let cache = QueryCache::from_config(&Default::default(), metrics.clone()).unwrap();
+        QuickwitClient::new(&config, metrics, Some(cache))
+    }
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn query_parts_are_parenthesized() {
+        let req: SearchRequest = serde_json::from_value(json!({
+            "query": "level:ERROR OR level:WARN",
+            "filters": {"service": "payment"},
+            "time_range_type": "relative",
+            "relative_time_key": "15m"
+        }))
+        .unwrap();
+        let body = client().build_query(
+            &req,
+            time("2026-10-18T10:00:00.250Z"),
+            time("2026-10-18T10:15:00Z"),
+        );
+        assert_eq!(
+            body["query"],
+            "(level:ERROR OR level:WARN) AND (service:payment) AND \
+             (timestamp:[2026-10-18T10:00:00.250000Z TO 2026-10-18T10:15:00.000000Z})"
+        );
+        assert_eq!(body["start_timestamp"], 1792317600);
+
+        let req = SearchRequest {
+            query: "*".to_string(),
+            filters: HashMap::new(),
+            ..req
+        };
+        let body = client().build_query(
+            &req,
+            time("2026-10-18T10:00:00Z"),
+            time("2026-10-18T10:15:00Z"),
+        );
+        assert_eq!(body["query"], "*");
+    }
+
+    #[test]
+    fn consecutive_relative_searches_hit_cache() {
+        let client = client();
+        let cache = client.cache.as_ref().unwrap();
+        let req: SearchRequest = serde_json::from_value(json!({
+            "query": "level:ERROR",
+            "time_range_type": "relative",
+            "relative_time_key": "15m"
+        }))
+        .unwrap();
+
+        // 两次查询相隔 1.5 秒，当前时间都带有亚秒部分
+        let first = time("2026-10-18T10:00:01.250Z");
+        let requests: Vec<(Value, String)> = [first, first + ChronoDuration::milliseconds(1500)]
+            .into_iter()
+            .map(|now| {
+                let start = resolve_relative("15m", now, Tz::UTC).unwrap();
+                client.request_with_key("search", &req, start, now, now, |start, end| {
+                    client.build_query(&req, start, end)
+                })
+            })
+            .collect();
+        assert_eq!(requests[0].1, requests[1].1);
+
+        // 发给 Quickwit 的请求保持请求的时间窗口
+        assert_eq!(requests[0].0["start_timestamp"], 1792316701);
+        assert_eq!(requests[0].0["end_timestamp"], 1792317602);
+        assert_eq!(requests[1].0["start_timestamp"], 1792316702);
+        assert_eq!(requests[1].0["end_timestamp"], 1792317603);
+        assert_eq!(
+            requests[1].0["query"],
+            "(level:ERROR) AND \
+             (timestamp:[2026-10-18T09:45:02.750000Z TO 2026-10-18T10:00:02.750000Z})"
+        );
+
+        let key = requests[0].1.clone();
+        assert!(cache.get(&key).is_none());
+        cache.insert(
+            key,
+            Arc::new(json!({"num_hits": 0})),
+            StdDuration::from_secs(10),
+        );
+        assert!(matches!(
+            cache.get(&requests[1].1),
+            Some((_, CacheStatus::Hit { .. }))
+        ));
+    }
+
+    #[test]
+    fn absolute_windows_are_keyed_by_the_exact_window() {
+        let client = client();
+        let req: SearchRequest = serde_json::from_value(json!({
+            "query": "level:ERROR",
+            "time_range_type": "absolute"
+        }))
+        .unwrap();
+        let now = time("2026-10-18T10:00:05Z");
+        let key = |start: &str| {
+            client
+                .request_with_key("search", &req, time(start), now, now, |start, end| {
+                    client.build_query(&req, start, end)
+                })
+                .1
+        };
+        assert_ne!(key("2026-10-18T09:45:01Z"), key("2026-10-18T09:45:02Z"));
+    }
+
+    #[test]
+    fn split_window_covers_the_window_without_gaps() {
+        let start = time("2024-05-01T08:00:00Z");
+        let end = time("2024-05-01T08:15:00.000007Z");
+        let slices = split_window(start, end, 10);
+        assert_eq!(slices.len(), 10);
+        assert_eq!(slices[0].0, start);
+        assert_eq!(slices[9].1, end);
+        for pair in slices.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+        assert_eq!(slices[1].0, time("2024-05-01T08:01:30Z"));
+    }
+
+    #[test]
+    fn samples_are_allocated_in_proportion_to_slice_totals() {
+        assert_eq!(allocate_samples(10, &[100, 0, 300, 600]), vec![1, 0, 3, 6]);
+        assert_eq!(allocate_samples(10, &[2, 1000]), vec![0, 10]);
+        // 不超过段内的匹配数
+        assert_eq!(allocate_samples(10, &[3, 4]), vec![3, 4]);
+        assert_eq!(allocate_samples(0, &[5]), vec![0]);
+    }
+
+    #[test]
+    fn estimates_use_the_weight_of_each_slice() {
+        let sample = LogSample {
+            hits: Vec::new(),
+            weights: vec![10.0, 10.0, 2.5, 2.5],
+            total: 25,
+        };
+        assert_eq!(sample.estimate(&[0, 2]), 13);
+        assert_eq!(sample.estimate(&[0, 1, 2, 3]), 25);
+    }
+
+    /// 失败的处理方式与对应的错误
+    fn outcome(failure: Failure) -> (&'static str, AppError) {
+        match failure {
+            Failure::Retryable(error) => ("retry", error),
+            Failure::Fatal(error) => ("fatal", error),
+            Failure::Rejected(error) => ("rejected", error),
+        }
+    }
+
+    #[test]
+    fn status_failures_are_classified_for_retry() {
+        let cases = [
+            (StatusCode::BAD_REQUEST, "rejected", "bad query"),
+            (StatusCode::NOT_FOUND, "rejected", "bad query"),
+            (StatusCode::TOO_MANY_REQUESTS, "retry", "unavailable"),
+            (StatusCode::INTERNAL_SERVER_ERROR, "retry", "error"),
+            (StatusCode::BAD_GATEWAY, "retry", "unavailable"),
+            (StatusCode::SERVICE_UNAVAILABLE, "retry", "unavailable"),
+            (StatusCode::GATEWAY_TIMEOUT, "retry", "unavailable"),
+        ];
+        for (status, expected_handling, expected_error) in cases {
+            let error = error_for_status(status, "boom".to_string());
+            let (handling, error) = outcome(Failure::status(status, error));
+            let kind = match &error {
+                AppError::BadQuery(message) if message == "boom" => "bad query",
+                AppError::QuickwitUnavailable(message) if message == "boom" => "unavailable",
+                AppError::QuickwitError(message) if message == "boom" => "error",
+                other => panic!("unexpected error for {}: {:?}", status, other),
+            };
+            assert_eq!(
+                (handling, kind),
+                (expected_handling, expected_error),
+                "{}",
+                status
+            );
+        }
+        // 空响应体时使用状态码作为错误信息
+        assert!(matches!(
+            error_for_status(StatusCode::BAD_GATEWAY, " ".to_string()),
+            AppError::QuickwitUnavailable(message) if message == "502 Bad Gateway"
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_failures_are_classified_for_retry() {
+        let client = Client::new();
+
+        // 连接被拒绝：可以重试
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let error = client.get(&refused).send().await.unwrap_err();
+        let (handling, error) = outcome(Failure::send(error));
+        assert_eq!(handling, "retry");
+        assert!(matches!(error, AppError::QuickwitUnavailable(_)));
+
+        // 建立连接后没有响应：超时不重试
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent = format!("http://{}", listener.local_addr().unwrap());
+        let error = client
+            .get(&silent)
+            .timeout(StdDuration::from_millis(100))
+            .send()
+            .await
+            .unwrap_err();
+        let (handling, error) = outcome(Failure::send(error));
+        assert_eq!(handling, "fatal");
+        assert!(matches!(error, AppError::QuickwitTimeout(_)));
+    }
+}
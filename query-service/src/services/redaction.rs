@@ -0,0 +1,546 @@
+use crate::config::{RedactionConfig, RedactionDetector};
+use crate::error::AppError;
+use crate::services::ai_analyzer::AnalysisStream;
+use futures_util::stream::{self, StreamExt};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::LazyLock;
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b")
+        .expect("valid regex")
+});
+static PHONE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\+86[ -]?|\b)1[3-9]\d{9}\b|\+\d{1,3}[ -]?\d{2,4}[ -]?\d{3,4}[ -]?\d{3,4}\b")
+        .expect("valid regex")
+});
+static IPV4: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d{1,3}(?:\.\d{1,3}){3}\b").expect("valid regex"));
+// 前一个字符不能是单词字符，避免把 Rust 路径（u8::MAX）之类误认为地址
+static IPV6: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:^|[^\w:])(?P<value>(?:[0-9a-f]{1,4})?(?::[0-9a-f]{0,4}){2,7})")
+        .expect("valid regex")
+});
+static JWT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\beyJ[A-Za-z0-9_-]{5,}\.[A-Za-z0-9_-]{5,}\.[A-Za-z0-9_-]*").expect("valid regex")
+});
+static BEARER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:bearer|basic)\s+(?P<value>[A-Za-z0-9._~+/=-]{8,})").expect("valid regex")
+});
+static API_KEY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b(?:sk-(?:ant-)?[A-Za-z0-9_-]{20,}|AKIA[0-9A-Z]{16}|gh[pousr]_[A-Za-z0-9]{36,}|xox[abprs]-[A-Za-z0-9-]{10,}|AIza[0-9A-Za-z_-]{35})",
+    )
+    .expect("valid regex")
+});
+static SECRET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?i)\b\w*(?:password|passwd|pwd|secret|token|api[_-]?key|access[_-]?key)\b["']?\s*[:=]\s*["']?(?P<value>[^\s"',;&}]+)"#,
+    )
+    .expect("valid regex")
+});
+static CREDIT_CARD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").expect("valid regex"));
+
+/// 占位符格式：`<EMAIL_1>`
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<[A-Z][A-Z0-9_]*_\d+>").expect("valid regex"));
+
+/// 占位符中的类别部分，如 `<EMAIL_1>` 中的 `EMAIL`
+static PLACEHOLDER_KIND: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<([A-Z][A-Z0-9_]*)_\d+>").expect("valid regex"));
+
+/// 占位符的最大长度，流式还原时据此判断末尾未闭合的 `<` 是否可能是占位符的开头
+const MAX_PLACEHOLDER_LEN: usize = 64;
+
+/// deny_fields 命中的标签值使用的占位符前缀
+const DENIED_FIELD_LABEL: &str = "SECRET";
+
+struct Rule {
+    /// 占位符前缀，如 EMAIL
+    label: String,
+    regex: Regex,
+    /// 对匹配值的额外校验，用于过滤误报
+    validate: Option<fn(&str) -> bool>,
+}
+
+impl Rule {
+    fn builtin(detector: RedactionDetector) -> Vec<Rule> {
+        let rule = |label: &str, regex: &Regex, validate: Option<fn(&str) -> bool>| Rule {
+            label: label.to_string(),
+            regex: regex.clone(),
+            validate,
+        };
+        match detector {
+            RedactionDetector::Email => vec![rule("EMAIL", &EMAIL, None)],
+            RedactionDetector::Phone => vec![rule("PHONE", &PHONE, None)],
+            RedactionDetector::Ip => vec![
+                rule("IP", &IPV4, Some(is_ipv4)),
+                rule("IP", &IPV6, Some(is_ipv6)),
+            ],
+            RedactionDetector::Jwt => vec![rule("TOKEN", &JWT, None)],
+            RedactionDetector::Bearer => vec![rule("TOKEN", &BEARER, None)],
+            RedactionDetector::ApiKey => vec![rule("API_KEY", &API_KEY, None)],
+            RedactionDetector::Secret => vec![rule("SECRET", &SECRET, None)],
+            RedactionDetector::CreditCard => vec![rule("CARD", &CREDIT_CARD, Some(is_card_number))],
+        }
+    }
+}
+
+/// 发送给 AI 前的敏感信息脱敏。
+///
+/// 检测到的值替换为 `<EMAIL_1>` 形式的占位符，并记录对应关系，
+/// AI 的回答中出现的占位符可以还原为原值后再展示
+pub struct Redactor {
+    enabled: bool,
+    rules: Vec<Rule>,
+    allow_fields: Vec<String>,
+    deny_fields: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for custom in &config.custom_patterns {
+            let regex = Regex::new(&custom.pattern)
+                .map_err(|e| format!("invalid redaction pattern '{}': {}", custom.name, e))?;
+            rules.push(Rule {
+                label: placeholder_label(&custom.name),
+                regex,
+                validate: None,
+            });
+        }
+
+        // 先替换结构明确的凭证，避免其中的片段被邮箱、号码等规则拆开
+        let order = [
+            RedactionDetector::Jwt,
+            RedactionDetector::Bearer,
+            RedactionDetector::ApiKey,
+            RedactionDetector::Secret,
+            RedactionDetector::Email,
+            RedactionDetector::CreditCard,
+            RedactionDetector::Phone,
+            RedactionDetector::Ip,
+        ];
+        for detector in order {
+            if config.detectors.contains(&detector) {
+                rules.extend(Rule::builtin(detector));
+            }
+        }
+
+        Ok(Self {
+            enabled: config.enabled,
+            rules,
+            allow_fields: config.allow_fields.clone(),
+            deny_fields: config
+                .deny_fields
+                .iter()
+                .map(|field| field.to_ascii_lowercase())
+                .collect(),
+        })
+    }
+
+    /// 开始一次脱敏，同一次分析内相同的值使用相同的占位符
+    pub fn session(&self) -> Redaction<'_> {
+        self.resume(Placeholders::default())
+    }
+
+    /// 在已有的占位符上继续脱敏，如多轮对话中的追问，已出现过的值沿用原占位符
+    pub fn resume(&self, placeholders: Placeholders) -> Redaction<'_> {
+        Redaction {
+            redactor: self,
+            placeholders,
+        }
+    }
+}
+
+/// 一次分析的脱敏过程
+pub struct Redaction<'a> {
+    redactor: &'a Redactor,
+    placeholders: Placeholders,
+}
+
+impl Redaction<'_> {
+    pub fn redact(&mut self, text: &str) -> String {
+        if !self.redactor.enabled {
+            return text.to_string();
+        }
+
+        let mut text = text.to_string();
+        for rule in &self.redactor.rules {
+            let placeholders = &mut self.placeholders;
+            let replaced = rule.regex.replace_all(&text, |caps: &Captures| {
+                let whole = caps.get(0).expect("group 0 always matches");
+                let target = caps.name("value").unwrap_or(whole);
+                let value = target.as_str();
+                let is_placeholder = PLACEHOLDER
+                    .find(value)
+                    .is_some_and(|m| m.start() == 0 && m.end() == value.len());
+                if value.is_empty()
+                    || is_placeholder
+                    || !rule.validate.is_none_or(|validate| validate(value))
+                {
+                    return whole.as_str().to_string();
+                }
+                format!(
+                    "{}{}{}",
+                    &whole.as_str()[..target.start() - whole.start()],
+                    placeholders.assign(&rule.label, value),
+                    &whole.as_str()[target.end() - whole.start()..]
+                )
+            });
+            text = replaced.into_owned();
+        }
+        text
+    }
+
+    /// 标签展开为 `路径=值`（嵌套对象的路径以 `.` 连接）。
+    /// deny_fields 命中的字段整个值替换为占位符，allow_fields 中的字段原样保留，其余按规则检测
+    pub fn redact_labels(&mut self, labels: &Value) -> Vec<String> {
+        let mut fields = Vec::new();
+        flatten_labels("", labels, &mut fields);
+
+        fields
+            .into_iter()
+            .map(|(path, value)| {
+                let value = if !self.redactor.enabled {
+                    value
+                } else if self.is_denied(&path) {
+                    self.placeholders.assign(DENIED_FIELD_LABEL, &value)
+                } else if self.redactor.allow_fields.contains(&path) {
+                    value
+                } else {
+                    self.redact(&value)
+                };
+                format!("{}={}", path, value)
+            })
+            .collect()
+    }
+
+    fn is_denied(&self, path: &str) -> bool {
+        let path = path.to_ascii_lowercase();
+        self.redactor
+            .deny_fields
+            .iter()
+            .any(|field| path.contains(field.as_str()))
+    }
+
+    /// 目前为止分配的占位符，用于在脱敏过程中还原文本
+    pub fn placeholders(&self) -> &Placeholders {
+        &self.placeholders
+    }
+
+    /// 结束脱敏，返回占位符与原值的对应关系
+    pub fn into_placeholders(self) -> Placeholders {
+        self.placeholders
+    }
+}
+
+/// 去掉占位符的序号（`<EMAIL_1>` → `<EMAIL>`），用于按消息模式归类时
+/// 把不同的值视为同一个变量
+pub fn strip_placeholder_ids(text: &str) -> Cow<'_, str> {
+    PLACEHOLDER_KIND.replace_all(text, "<$1>")
+}
+
+/// 占位符与原值的对应关系，用于还原 AI 的回答。
+/// 序列化为占位符到原值的映射，随多轮对话持久化
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "HashMap<String, String>", into = "HashMap<String, String>")]
+pub struct Placeholders {
+    by_value: HashMap<String, String>,
+    by_placeholder: HashMap<String, String>,
+    /// 每个前缀已分配的序号
+    counters: HashMap<String, usize>,
+}
+
+impl From<HashMap<String, String>> for Placeholders {
+    fn from(by_placeholder: HashMap<String, String>) -> Self {
+        let mut placeholders = Placeholders::default();
+        for (placeholder, value) in by_placeholder {
+            if let Some(caps) = PLACEHOLDER_KIND.captures(&placeholder) {
+                let serial = placeholder[caps[1].len() + 2..placeholder.len() - 1]
+                    .parse()
+                    .unwrap_or(0);
+                let counter = placeholders.counters.entry(caps[1].to_string()).or_insert(0);
+                *counter = (*counter).max(serial);
+            }
+            placeholders
+                .by_value
+                .insert(value.clone(), placeholder.clone());
+            placeholders.by_placeholder.insert(placeholder, value);
+        }
+        placeholders
+    }
+}
+
+impl From<Placeholders> for HashMap<String, String> {
+    fn from(placeholders: Placeholders) -> Self {
+        placeholders.by_placeholder
+    }
+}
+
+impl Placeholders {
+    fn assign(&mut self, label: &str, value: &str) -> String {
+        if let Some(placeholder) = self.by_value.get(value) {
+            return placeholder.clone();
+        }
+        let counter = self.counters.entry(label.to_string()).or_insert(0);
+        *counter += 1;
+        let placeholder = format!("<{}_{}>", label, counter);
+        self.by_value.insert(value.to_string(), placeholder.clone());
+        self.by_placeholder
+            .insert(placeholder.clone(), value.to_string());
+        placeholder
+    }
+
+    /// 脱敏的值的个数
+    pub fn len(&self) -> usize {
+        self.by_placeholder.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_placeholder.is_empty()
+    }
+
+    /// 将文本中的占位符还原为原值，未知的占位符保持不变
+    pub fn rehydrate(&self, text: &str) -> String {
+        if self.is_empty() {
+            return text.to_string();
+        }
+        PLACEHOLDER
+            .replace_all(text, |caps: &Captures| {
+                self.by_placeholder
+                    .get(&caps[0])
+                    .cloned()
+                    .unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned()
+    }
+
+    /// 还原流式输出。占位符可能被拆在两段增量中，末尾可能是占位符开头的部分暂存到下一段
+    pub fn rehydrate_stream(self, stream: AnalysisStream) -> AnalysisStream {
+        if self.is_empty() {
+            return stream;
+        }
+
+        let state = RehydrateState {
+            stream,
+            placeholders: self,
+            pending: String::new(),
+            error: None,
+            done: false,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            // 暂存的文本已输出，再返回上游的错误
+            if let Some(e) = state.error.take() {
+                state.done = true;
+                return Some((Err(e), state));
+            }
+            loop {
+                if state.done {
+                    return None;
+                }
+                match state.stream.next().await {
+                    Some(Ok(delta)) => {
+                        state.pending.push_str(&delta);
+                        if let Some(ready) = state.take_ready() {
+                            return Some((Ok(ready), state));
+                        }
+                    }
+                    Some(Err(e)) => {
+                        state.done = true;
+                        if state.pending.is_empty() {
+                            return Some((Err(e), state));
+                        }
+                        state.error = Some(e);
+                        return Some((Ok(state.take_rest()), state));
+                    }
+                    None => {
+                        state.done = true;
+                        if state.pending.is_empty() {
+                            return None;
+                        }
+                        return Some((Ok(state.take_rest()), state));
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+}
+
+struct RehydrateState {
+    stream: AnalysisStream,
+    placeholders: Placeholders,
+    /// 尚未输出的文本
+    pending: String,
+    /// 输出暂存文本后再返回的上游错误
+    error: Option<AppError>,
+    done: bool,
+}
+
+impl RehydrateState {
+    /// 取出可以输出的部分：末尾未闭合且不超过占位符长度的 `<...` 留到下一段
+    fn take_ready(&mut self) -> Option<String> {
+        let split = match self.pending.rfind('<') {
+            Some(start)
+                if !self.pending[start..].contains('>')
+                    && self.pending.len() - start < MAX_PLACEHOLDER_LEN =>
+            {
+                start
+            }
+            _ => self.pending.len(),
+        };
+        if split == 0 {
+            return None;
+        }
+        let ready: String = self.pending.drain(..split).collect();
+        Some(self.placeholders.rehydrate(&ready))
+    }
+
+    fn take_rest(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        self.placeholders.rehydrate(&rest)
+    }
+}
+
+/// 自定义规则名转为占位符前缀：order-id -> ORDER_ID
+fn placeholder_label(name: &str) -> String {
+    let label: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let label = label.trim_matches('_');
+    // 占位符必须以字母开头
+    if label.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        label.to_string()
+    } else {
+        format!("X_{}", label)
+    }
+}
+
+fn flatten_labels(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_labels(&path, value, out);
+            }
+        }
+        Value::Null => {}
+        Value::String(s) => out.push((prefix.to_string(), s.clone())),
+        other => out.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+fn is_ipv4(value: &str) -> bool {
+    value.parse::<Ipv4Addr>().is_ok()
+}
+
+/// 至少包含一个数字，排除 `std::io` 中的 `d::` 这类片段
+fn is_ipv6(value: &str) -> bool {
+    value.len() >= 3
+        && value.chars().any(|c| c.is_ascii_digit())
+        && value.parse::<Ipv6Addr>().is_ok()
+}
+
+/// 常见卡组织的卡号以 3-6 开头（排除以 1 开头的毫秒时间戳），并通过 Luhn 校验
+fn is_card_number(value: &str) -> bool {
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) || !(3..=6).contains(&digits[0]) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, &digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_consistently_and_rehydrates() {
+        let redactor = Redactor::new(&RedactionConfig::default()).unwrap();
+        let mut redaction = redactor.session();
+
+        let message = redaction.redact(
+            "login failed for alice@example.com from 10.0.0.12, token=abc123def; retry alice@example.com",
+        );
+        assert_eq!(
+            message,
+            "login failed for <EMAIL_1> from <IP_1>, token=<SECRET_1>; retry <EMAIL_1>"
+        );
+        // 时间、Rust 路径与非卡号的长数字不受影响
+        let untouched = "at 12:30:45 std::io::Error order 1760000000000";
+        assert_eq!(redaction.redact(untouched), untouched);
+
+        let labels = redaction.redact_labels(&serde_json::json!({
+            "user": { "phone": "13812345678" },
+            "db_password": "hunter2",
+        }));
+        assert!(labels.contains(&"user.phone=<PHONE_1>".to_string()));
+        assert!(labels.contains(&"db_password=<SECRET_2>".to_string()));
+
+        let placeholders = redaction.into_placeholders();
+        assert_eq!(
+            placeholders.rehydrate("请联系 <EMAIL_1>，检查 <IP_1> 与 <UNKNOWN_9>"),
+            "请联系 alice@example.com，检查 10.0.0.12 与 <UNKNOWN_9>"
+        );
+
+        // 持久化后继续脱敏：已有的值沿用原占位符，新值的序号接着分配
+        let json = serde_json::to_string(&placeholders).unwrap();
+        let mut resumed = redactor.resume(serde_json::from_str(&json).unwrap());
+        assert_eq!(
+            resumed.redact("bob@example.com 与 alice@example.com"),
+            "<EMAIL_2> 与 <EMAIL_1>"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn rehydrates_placeholders_split_across_deltas() {
+        let redactor = Redactor::new(&RedactionConfig::default()).unwrap();
+        let mut redaction = redactor.session();
+        redaction.redact("user bob@example.com");
+        let placeholders = redaction.into_placeholders();
+
+        let deltas = ["用户 <EM", "AIL_", "1> 登录失败，a < b"];
+        let stream = stream::iter(deltas.map(|delta| Ok(delta.to_string()))).boxed();
+        let output: Vec<String> = placeholders
+            .rehydrate_stream(stream)
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(output.concat(), "用户 bob@example.com 登录失败，a < b");
+    }
+}
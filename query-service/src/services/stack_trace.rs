@@ -0,0 +1,581 @@
+use crate::models::query::StackFrame;
+use regex::Regex;
+use std::sync::LazyLock;
+
+static JAVA_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^at (?P<func>[^\s(]+)\((?P<loc>[^)]*)\)(?:\s*~?\[.*\])?$").expect("valid regex")
+});
+static NODE_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^at (?:async )?(?P<func>.+?) \((?P<loc>.*)\)$").expect("valid regex")
+});
+static NODE_LOCATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<file>.+?):(?P<line>\d+)(?::(?P<col>\d+))?$").expect("valid regex")
+});
+static PYTHON_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^File "(?P<file>[^"]+)", line (?P<line>\d+)(?:, in (?P<func>.+))?$"#)
+        .expect("valid regex")
+});
+static GO_FUNCTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:created by )?(?P<func>[^\s()]+(?:\(\*?[^\s()]+\)\.[^\s()]+)?)(?:\(.*\))?(?: in goroutine \d+)?$",
+    )
+    .expect("valid regex")
+});
+static GO_LOCATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<file>\S+\.go):(?P<line>\d+)(?: \+0x[0-9a-fA-F]+)?$").expect("valid regex")
+});
+static RUST_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\d+:\s+(?:0x[0-9a-fA-F]+ - )?(?P<func>.+)$").expect("valid regex")
+});
+static RUST_LOCATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^at (?P<file>.+?):(?P<line>\d+)(?::(?P<col>\d+))?$").expect("valid regex")
+});
+static RUST_HASH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"::h[0-9a-f]{16}$").expect("valid regex"));
+
+/// 框架与标准库的模块前缀，未配置 in_app_prefixes 时这些帧不视为业务代码
+const LIBRARY_MODULES: &[&str] = &[
+    "java.",
+    "javax.",
+    "jdk.",
+    "sun.",
+    "com.sun.",
+    "kotlin.",
+    "kotlinx.",
+    "scala.",
+    "org.springframework.",
+    "org.apache.",
+    "org.hibernate.",
+    "io.netty.",
+    "reactor.",
+    "runtime",
+    "net/http",
+    "std::",
+    "core::",
+    "alloc::",
+    "tokio::",
+    "futures::",
+    "actix_",
+    "hyper::",
+    "rust_begin_unwind",
+    "__rust",
+];
+
+/// 第三方库与运行时的路径片段
+const LIBRARY_PATHS: &[&str] = &[
+    "node:",
+    "node_modules/",
+    "site-packages/",
+    "dist-packages/",
+    "/lib/python",
+    "/usr/local/go/",
+    "/pkg/mod/",
+    "/rustc/",
+    ".cargo/registry/",
+    "<frozen ",
+];
+
+/// 堆栈所属的运行时
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    Java,
+    Python,
+    Go,
+    Rust,
+    Node,
+    Unknown,
+}
+
+/// 解析后的堆栈
+#[derive(Debug, Clone)]
+pub struct ParsedStackTrace {
+    pub runtime: Runtime,
+
+    /// 异常行（如 `java.lang.IllegalStateException: order not found`、`panic: ...`）
+    pub header: Option<String>,
+
+    /// 调用帧，出错位置在前
+    pub frames: Vec<StackFrame>,
+}
+
+impl ParsedStackTrace {
+    /// 异常类型：异常行中冒号前不含空白的部分，Rust 的 panic 为 `panic`
+    pub fn error_type(&self) -> Option<&str> {
+        let header = self.header.as_deref()?;
+        if self.runtime == Runtime::Rust && header.contains(" panicked at ") {
+            return Some("panic");
+        }
+        let header = header.strip_prefix("Caused by: ").unwrap_or(header);
+        let (error_type, _) = header.split_once(':')?;
+        let error_type = error_type.trim();
+        (!error_type.is_empty() && !error_type.contains(char::is_whitespace)).then_some(error_type)
+    }
+
+    /// 紧凑的文本形式：异常行加上最多 `max_frames` 个业务帧，
+    /// 出错位置不在业务代码时额外保留最上层的一帧，省略的帧只记数量
+    pub fn summary(&self, max_frames: usize) -> String {
+        let mut lines: Vec<String> = self.header.iter().cloned().collect();
+
+        let mut shown = 0;
+        for (index, frame) in self.frames.iter().enumerate() {
+            if shown >= max_frames {
+                break;
+            }
+            if frame.in_app || index == 0 {
+                lines.push(format!("  at {}", frame));
+                shown += 1;
+            }
+        }
+        if self.frames.len() > shown {
+            lines.push(format!("  ...({} more frames)", self.frames.len() - shown));
+        }
+        lines.join("\n")
+    }
+}
+
+/// 堆栈解析器，支持 Java/Kotlin、Python、Go、Rust 与 Node.js 的默认堆栈格式。
+/// 无法识别的行会被跳过，不影响其余帧
+pub struct StackTraceParser {
+    in_app_prefixes: Vec<String>,
+}
+
+impl StackTraceParser {
+    /// `in_app_prefixes` 为业务代码的包名或路径前缀，为空时排除常见框架、标准库与第三方依赖的帧
+    pub fn new(in_app_prefixes: Vec<String>) -> Self {
+        Self { in_app_prefixes }
+    }
+
+    pub fn parse(&self, stack_trace: &str) -> ParsedStackTrace {
+        let lines: Vec<&str> = stack_trace
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let runtime = detect_runtime(&lines);
+
+        let mut header: Option<String> = None;
+        let mut frames: Vec<StackFrame> = Vec::new();
+        let mut index = 0;
+
+        while index < lines.len() {
+            let line = lines[index];
+            let trimmed = line.trim();
+            index += 1;
+
+            let frame = match runtime {
+                Runtime::Java => parse_java(trimmed),
+                Runtime::Node => parse_node(trimmed),
+                Runtime::Python => parse_python(trimmed).inspect(|_| {
+                    // 帧的下一行是缩进的源码
+                    if lines.get(index).is_some_and(|next| {
+                        next.starts_with(char::is_whitespace) && !is_python_frame(next)
+                    }) {
+                        index += 1;
+                    }
+                }),
+                Runtime::Go => lines
+                    .get(index)
+                    .and_then(|next| GO_LOCATION.captures(next.trim()))
+                    .and_then(|location| {
+                        let func = GO_FUNCTION.captures(trimmed)?;
+                        index += 1;
+                        Some(go_frame(
+                            &func["func"],
+                            &location["file"],
+                            &location["line"],
+                        ))
+                    }),
+                Runtime::Rust => RUST_FRAME.captures(trimmed).map(|caps| {
+                    let function = RUST_HASH.replace(&caps["func"], "").to_string();
+                    let location = lines
+                        .get(index)
+                        .and_then(|next| RUST_LOCATION.captures(next.trim()));
+                    if location.is_some() {
+                        index += 1;
+                    }
+                    StackFrame {
+                        module: function
+                            .rsplit_once("::")
+                            .map(|(module, _)| module.to_string()),
+                        file: location.as_ref().map(|caps| caps["file"].to_string()),
+                        line: location.as_ref().and_then(|caps| caps["line"].parse().ok()),
+                        column: location
+                            .as_ref()
+                            .and_then(|caps| caps.name("col"))
+                            .and_then(|col| col.as_str().parse().ok()),
+                        function: Some(function),
+                        in_app: false,
+                    }
+                }),
+                Runtime::Unknown => None,
+            };
+
+            match frame {
+                Some(mut frame) => {
+                    frame.in_app = self.is_in_app(runtime, &frame);
+                    frames.push(frame);
+                }
+                None => {
+                    if is_header_candidate(runtime, line, trimmed) {
+                        // 取最外层的异常；Python 的异常行在最后
+                        if header.is_none() || runtime == Runtime::Python {
+                            header = Some(trimmed.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Python 的帧按调用顺序排列，最后一帧才是出错位置
+        if runtime == Runtime::Python {
+            frames.reverse();
+        }
+
+        ParsedStackTrace {
+            runtime,
+            header,
+            frames,
+        }
+    }
+
+    fn is_in_app(&self, runtime: Runtime, frame: &StackFrame) -> bool {
+        let fields = [&frame.module, &frame.function, &frame.file];
+        if !self.in_app_prefixes.is_empty() {
+            return fields
+                .iter()
+                .filter_map(|field| field.as_deref())
+                .any(|value| {
+                    self.in_app_prefixes
+                        .iter()
+                        .any(|prefix| value.contains(prefix.as_str()))
+                });
+        }
+
+        // 没有源码位置的帧（Native Method、<anonymous> 等）来自运行时；Rust 无调试信息时没有位置，不在此列
+        if frame.file.is_none() && runtime != Runtime::Rust {
+            return false;
+        }
+
+        let symbol = frame
+            .module
+            .as_deref()
+            .or(frame.function.as_deref())
+            .unwrap_or_default();
+        let file = frame.file.as_deref().unwrap_or_default();
+        !LIBRARY_MODULES
+            .iter()
+            .any(|prefix| symbol.starts_with(prefix))
+            && !LIBRARY_PATHS.iter().any(|path| file.contains(path))
+    }
+}
+
+fn detect_runtime(lines: &[&str]) -> Runtime {
+    let trimmed = || lines.iter().map(|line| line.trim());
+
+    if trimmed().any(|line| line.starts_with("Traceback (most recent call last)"))
+        || trimmed().any(|line| PYTHON_FRAME.is_match(line))
+    {
+        Runtime::Python
+    } else if trimmed().any(|line| line.starts_with("goroutine ") || GO_LOCATION.is_match(line)) {
+        Runtime::Go
+    } else if trimmed().any(|line| RUST_FRAME.is_match(line)) {
+        Runtime::Rust
+    } else if trimmed().any(|line| parse_node(line).is_some()) {
+        Runtime::Node
+    } else if trimmed().any(|line| JAVA_FRAME.is_match(line)) {
+        Runtime::Java
+    } else {
+        Runtime::Unknown
+    }
+}
+
+fn is_python_frame(line: &str) -> bool {
+    PYTHON_FRAME.is_match(line.trim())
+}
+
+/// 非帧行中可作为异常行的行：顶格、不是提示信息
+fn is_header_candidate(runtime: Runtime, line: &str, trimmed: &str) -> bool {
+    if line.starts_with(char::is_whitespace) && runtime != Runtime::Unknown {
+        return false;
+    }
+    !(trimmed.starts_with("Traceback")
+        || trimmed.starts_with("goroutine ")
+        || trimmed.starts_with("...")
+        || trimmed.starts_with("stack backtrace:")
+        || trimmed.starts_with("note: ")
+        || trimmed.starts_with("Caused by:")
+        || trimmed.starts_with("During handling of")
+        || trimmed.starts_with("The above exception"))
+}
+
+/// `at com.acme.OrderService.find(OrderService.java:42)`
+fn parse_java(line: &str) -> Option<StackFrame> {
+    let caps = JAVA_FRAME.captures(line)?;
+    // Java 9+ 的模块前缀：java.base/java.lang.Thread.run
+    let func = caps["func"]
+        .rsplit_once('/')
+        .map_or(&caps["func"], |(_, func)| func);
+    let (file, line) = match caps["loc"].split_once(':') {
+        Some((file, line)) => (Some(file.to_string()), line.parse().ok()),
+        None if caps["loc"].contains('.') => (Some(caps["loc"].to_string()), None),
+        // Native Method / Unknown Source
+        None => (None, None),
+    };
+
+    Some(StackFrame {
+        module: func.rsplit_once('.').map(|(class, _)| class.to_string()),
+        function: Some(func.to_string()),
+        file,
+        line,
+        column: None,
+        in_app: false,
+    })
+}
+
+/// `at OrderService.find (/app/src/order.js:42:7)` 或 `at /app/src/order.js:42:7`
+fn parse_node(line: &str) -> Option<StackFrame> {
+    let (function, location) = match NODE_FRAME.captures(line) {
+        Some(caps) => (
+            Some(caps["func"].to_string()),
+            caps.name("loc").map_or("", |loc| loc.as_str()).to_string(),
+        ),
+        None => (None, line.strip_prefix("at ")?.to_string()),
+    };
+    let location = location.strip_prefix("file://").unwrap_or(&location);
+
+    let caps = NODE_LOCATION.captures(location);
+    // 没有行号的帧只接受 `<anonymous>`、`native` 等形式，避免把 Java 帧误判为 Node
+    if caps.is_none() && function.is_none() {
+        return None;
+    }
+    if caps.is_none()
+        && !(location.starts_with('<') || location == "native" || location.starts_with("index "))
+    {
+        return None;
+    }
+
+    Some(StackFrame {
+        module: None,
+        function,
+        file: caps.as_ref().map(|caps| caps["file"].to_string()),
+        line: caps.as_ref().and_then(|caps| caps["line"].parse().ok()),
+        column: caps
+            .as_ref()
+            .and_then(|caps| caps.name("col"))
+            .and_then(|col| col.as_str().parse().ok()),
+        in_app: false,
+    })
+}
+
+/// `File "/app/views.py", line 12, in handler`
+fn parse_python(line: &str) -> Option<StackFrame> {
+    let caps = PYTHON_FRAME.captures(line)?;
+    let file = caps["file"].to_string();
+    let module = file
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.strip_suffix(".py"))
+        .map(str::to_string);
+
+    Some(StackFrame {
+        function: caps.name("func").map(|func| func.as_str().to_string()),
+        module,
+        line: caps["line"].parse().ok(),
+        file: Some(file),
+        column: None,
+        in_app: false,
+    })
+}
+
+/// `main.(*Server).handle(0xc000010000)` + `\t/app/server.go:42 +0x1d`
+fn go_frame(function: &str, file: &str, line: &str) -> StackFrame {
+    // 包路径：最后一个 `/` 之后的第一个 `.` 之前
+    let package_end = function.rfind('/').map_or(0, |slash| slash + 1);
+    let module = function[package_end..]
+        .find('.')
+        .map(|dot| function[..package_end + dot].to_string());
+
+    StackFrame {
+        function: Some(function.to_string()),
+        module,
+        file: Some(file.to_string()),
+        line: line.parse().ok(),
+        column: None,
+        in_app: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (函数, 行号, 是否业务代码)
+    fn frames(parsed: &ParsedStackTrace) -> Vec<(&str, Option<u32>, bool)> {
+        parsed
+            .frames
+            .iter()
+            .map(|frame| {
+                (
+                    frame.function.as_deref().unwrap_or_default(),
+                    frame.line,
+                    frame.in_app,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_java_with_causes() {
+        let trace = "java.lang.IllegalStateException: order not found
+\tat com.acme.order.OrderService.find(OrderService.java:42)
+\tat org.springframework.aop.framework.ReflectiveMethodInvocation.proceed(ReflectiveMethodInvocation.java:186)
+\tat java.base/java.lang.Thread.run(Thread.java:833)
+Caused by: java.sql.SQLTimeoutException: query timed out
+\tat com.mysql.cj.jdbc.ClientPreparedStatement.executeQuery(ClientPreparedStatement.java:1003)
+\tat com.acme.order.OrderRepository.load(OrderRepository.java)
+\t... 2 more";
+        let parsed = StackTraceParser::new(vec!["com.acme.".to_string()]).parse(trace);
+
+        assert_eq!(parsed.runtime, Runtime::Java);
+        assert_eq!(parsed.error_type(), Some("java.lang.IllegalStateException"));
+        assert_eq!(
+            frames(&parsed),
+            vec![
+                ("com.acme.order.OrderService.find", Some(42), true),
+                (
+                    "org.springframework.aop.framework.ReflectiveMethodInvocation.proceed",
+                    Some(186),
+                    false
+                ),
+                ("java.lang.Thread.run", Some(833), false),
+                (
+                    "com.mysql.cj.jdbc.ClientPreparedStatement.executeQuery",
+                    Some(1003),
+                    false
+                ),
+                ("com.acme.order.OrderRepository.load", None, true),
+            ]
+        );
+        assert_eq!(
+            parsed.frames[0].module.as_deref(),
+            Some("com.acme.order.OrderService")
+        );
+        assert_eq!(
+            parsed.frames[4].file.as_deref(),
+            Some("OrderRepository.java")
+        );
+        assert_eq!(
+            parsed.summary(2),
+            "java.lang.IllegalStateException: order not found
+  at com.acme.order.OrderService.find (OrderService.java:42)
+  at com.acme.order.OrderRepository.load (OrderRepository.java)
+  ...(3 more frames)"
+        );
+    }
+
+    #[test]
+    fn parses_python_innermost_first() {
+        let trace = r#"Traceback (most recent call last):
+  File "/usr/lib/python3.11/site-packages/flask/app.py", line 1484, in full_dispatch_request
+    rv = self.dispatch_request()
+  File "/app/views.py", line 12, in handler
+    order = load(order_id)
+KeyError: 'order_id'"#;
+        let parsed = StackTraceParser::new(Vec::new()).parse(trace);
+
+        assert_eq!(parsed.runtime, Runtime::Python);
+        assert_eq!(parsed.error_type(), Some("KeyError"));
+        assert_eq!(
+            frames(&parsed),
+            vec![
+                ("handler", Some(12), true),
+                ("full_dispatch_request", Some(1484), false),
+            ]
+        );
+        assert_eq!(parsed.frames[0].module.as_deref(), Some("views"));
+    }
+
+    #[test]
+    fn parses_go_goroutine() {
+        let trace = "panic: runtime error: invalid memory address or nil pointer dereference
+[signal SIGSEGV: segmentation violation code=0x1 addr=0x0 pc=0x4a1b2c]
+
+goroutine 1 [running]:
+main.(*Server).handle(0xc000010000, 0x0)
+\t/app/server.go:42 +0x1d
+net/http.HandlerFunc.ServeHTTP(...)
+\t/usr/local/go/src/net/http/server.go:2136
+main.main()
+\t/app/main.go:10 +0x25
+exit status 2";
+        let parsed = StackTraceParser::new(Vec::new()).parse(trace);
+
+        assert_eq!(parsed.runtime, Runtime::Go);
+        assert_eq!(parsed.error_type(), Some("panic"));
+        assert_eq!(
+            frames(&parsed),
+            vec![
+                ("main.(*Server).handle", Some(42), true),
+                ("net/http.HandlerFunc.ServeHTTP", Some(2136), false),
+                ("main.main", Some(10), true),
+            ]
+        );
+        assert_eq!(parsed.frames[1].module.as_deref(), Some("net/http"));
+    }
+
+    #[test]
+    fn parses_rust_backtrace() {
+        let trace = "thread 'main' panicked at src/order.rs:42:9:
+called `Option::unwrap()` on a `None` value
+stack backtrace:
+   0: rust_begin_unwind
+             at /rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/std/src/panicking.rs:597:5
+   1: core::panicking::panic
+             at /rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/core/src/panicking.rs:127:5
+   2: order_service::order::load::h0123456789abcdef
+             at ./src/order.rs:42:9
+   3: order_service::main
+             at ./src/main.rs:7:5
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.";
+        let parsed = StackTraceParser::new(Vec::new()).parse(trace);
+
+        assert_eq!(parsed.runtime, Runtime::Rust);
+        assert_eq!(parsed.error_type(), Some("panic"));
+        assert_eq!(
+            frames(&parsed),
+            vec![
+                ("rust_begin_unwind", Some(597), false),
+                ("core::panicking::panic", Some(127), false),
+                ("order_service::order::load", Some(42), true),
+                ("order_service::main", Some(7), true),
+            ]
+        );
+        assert_eq!(
+            parsed.frames[2].module.as_deref(),
+            Some("order_service::order")
+        );
+        assert_eq!(parsed.frames[2].column, Some(9));
+    }
+
+    #[test]
+    fn parses_node_stack() {
+        let trace = "TypeError: Cannot read properties of undefined (reading 'id')
+    at OrderService.find (/app/src/order.js:42:7)
+    at async Router.handle (/app/node_modules/express/lib/router.js:10:3)
+    at /app/src/index.js:5:1
+    at process.processTicksAndRejections (node:internal/process/task_queues:95:5)";
+        let parsed = StackTraceParser::new(Vec::new()).parse(trace);
+
+        assert_eq!(parsed.runtime, Runtime::Node);
+        assert_eq!(parsed.error_type(), Some("TypeError"));
+        assert_eq!(
+            frames(&parsed),
+            vec![
+                ("OrderService.find", Some(42), true),
+                ("Router.handle", Some(10), false),
+                ("", Some(5), true),
+                ("process.processTicksAndRejections", Some(95), false),
+            ]
+        );
+        assert_eq!(parsed.frames[0].column, Some(7));
+    }
+}
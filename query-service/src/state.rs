@@ -0,0 +1,146 @@
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::services::{
+    ai_agent::AiAgent, ai_analyzer::AiAnalyzerClient, ai_cache::AiAnalysisCache,
+    ai_sessions::AiSessionStore, cache::QueryCache,
+    issues::IssueTracker, quickwit::QuickwitClient, redaction::Redactor,
+    stack_trace::StackTraceParser,
+};
+use std::sync::{Arc, RwLock};
+
+/// 依赖配置创建的客户端，配置重载时整体替换
+struct Clients {
+    quickwit: Arc<QuickwitClient>,
+    ai_analyzer: Arc<AiAnalyzerClient>,
+    ai_agent: Arc<AiAgent>,
+    stack_trace_parser: Arc<StackTraceParser>,
+    redactor: Arc<Redactor>,
+}
+
+impl Clients {
+    /// 提示词模板无效时返回错误
+    fn from_config(
+        config: &Config,
+        metrics: &Metrics,
+        ai_cache: Option<Arc<AiAnalysisCache>>,
+        quickwit: Arc<QuickwitClient>,
+    ) -> Result<Self, String> {
+        // 创建 AI 分析器客户端，分析结果缓存跨配置重载保留
+        let ai_analyzer = AiAnalyzerClient::new(&config.ai_analyzer, metrics.clone(), ai_cache)?;
+
+        // AI 调查的步数与 token 限制
+        let ai_agent = AiAgent::new(&config.ai_agent);
+
+        // 创建堆栈解析器
+        let stack_trace_parser =
+            StackTraceParser::new(config.stack_trace.in_app_prefixes.clone());
+
+        // 创建发送给 AI 前的脱敏规则（自定义正则已在配置校验时检查）
+        let redactor = Redactor::new(&config.redaction)
+            .expect("redaction patterns are validated with the config");
+
+        Ok(Self {
+            quickwit,
+            ai_analyzer: Arc::new(ai_analyzer),
+            ai_agent: Arc::new(ai_agent),
+            stack_trace_parser: Arc::new(stack_trace_parser),
+            redactor: Arc::new(redactor),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    clients: Arc<RwLock<Arc<Clients>>>,
+    pub metrics: Metrics,
+    pub issues: Arc<IssueTracker>,
+    pub ai_sessions: Arc<AiSessionStore>,
+    ai_cache: Option<Arc<AiAnalysisCache>>,
+}
+
+impl AppState {
+    pub fn new(
+        config: &Config,
+        metrics: Metrics,
+        issues: IssueTracker,
+        ai_sessions: AiSessionStore,
+        ai_cache: Option<AiAnalysisCache>,
+    ) -> Result<Self, String> {
+        let ai_cache = ai_cache.map(Arc::new);
+        let quickwit = Arc::new(new_quickwit(config, &metrics));
+        let clients = Clients::from_config(config, &metrics, ai_cache.clone(), quickwit)?;
+        Ok(Self {
+            clients: Arc::new(RwLock::new(Arc::new(clients))),
+            metrics,
+            issues: Arc::new(issues),
+            ai_sessions: Arc::new(ai_sessions),
+            ai_cache,
+        })
+    }
+
+    fn clients(&self) -> Arc<Clients> {
+        self.clients.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn quickwit(&self) -> Arc<QuickwitClient> {
+        self.clients().quickwit.clone()
+    }
+
+    pub fn ai_analyzer(&self) -> Arc<AiAnalyzerClient> {
+        self.clients().ai_analyzer.clone()
+    }
+
+    pub fn ai_agent(&self) -> Arc<AiAgent> {
+        self.clients().ai_agent.clone()
+    }
+
+    pub fn stack_trace_parser(&self) -> Arc<StackTraceParser> {
+        self.clients().stack_trace_parser.clone()
+    }
+
+    pub fn redactor(&self) -> Arc<Redactor> {
+        self.clients().redactor.clone()
+    }
+
+    /// 使用新配置重建客户端并原子替换；重建失败时保留原有客户端。
+    ///
+    /// `quickwit` 配置变化时先检查新地址上的索引，检查失败则拒绝重载；
+    /// 未变化时沿用原客户端，查询缓存与熔断状态不受重载影响
+    pub async fn reload(&self, config: &Config, previous: &Config) -> Result<(), String> {
+        let current = self.clients().quickwit.clone();
+        let quickwit = if config.quickwit != previous.quickwit {
+            let quickwit = new_quickwit(config, &self.metrics);
+            quickwit
+                .check_index()
+                .await
+                .map_err(|e| format!("Quickwit index check failed: {}", e))?;
+            Arc::new(quickwit)
+        } else if config.cache != previous.cache {
+            Arc::new(current.with_cache(QueryCache::from_config(
+                &config.cache,
+                self.metrics.clone(),
+            )))
+        } else {
+            current
+        };
+
+        let clients = Arc::new(Clients::from_config(
+            config,
+            &self.metrics,
+            self.ai_cache.clone(),
+            quickwit,
+        )?);
+        *self.clients.write().unwrap_or_else(|e| e.into_inner()) = clients;
+        config.log_ai_config();
+        Ok(())
+    }
+}
+
+/// 创建 Quickwit 客户端及其查询缓存
+fn new_quickwit(config: &Config, metrics: &Metrics) -> QuickwitClient {
+    QuickwitClient::new(
+        &config.quickwit,
+        metrics.clone(),
+        QueryCache::from_config(&config.cache, metrics.clone()),
+    )
+}
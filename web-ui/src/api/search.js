@@ -67,10 +67,72 @@ export const healthCheck = () => {
 /**
  * AI分析错误日志
  * @param {string} traceId - Trace ID
+ * @param {boolean} forceRefresh - 忽略缓存的分析结果，重新分析
  * @returns {Promise}
  */
-export const aiAnalyzeError = (traceId) => {
-  return apiClient.post('/ai/analyze', { trace_id: traceId })
+// options: { forceRefresh, template, language }，template/language 为空时使用服务端默认值
+export const aiAnalyzeError = (traceId, { forceRefresh = false, template, language } = {}) => {
+  return apiClient.post('/ai/analyze', {
+    trace_id: traceId,
+    force_refresh: forceRefresh,
+    template: template || undefined,
+    language: language || undefined,
+  })
+}
+
+// AI 总结搜索结果：服务端采样最近的日志并按消息模式归类
+export const aiAnalyzeSearch = (searchParams, { forceRefresh = false, template, language } = {}) => {
+  return apiClient.post('/ai/analyze/search', {
+    ...searchParams,
+    force_refresh: forceRefresh,
+    template: template || undefined,
+    language: language || undefined,
+  })
+}
+
+// 把自然语言问题转换为搜索条件，返回的 search 可直接作为搜索参数
+export const aiTranslateQuery = (question, { timeZone, language } = {}) => {
+  return apiClient.post('/ai/query', {
+    question,
+    time_zone: timeZone || undefined,
+    language: language || undefined,
+  })
+}
+
+// AI 调查：模型自行搜索日志后给出结论，同时返回执行过的查询
+export const aiInvestigate = (question, { traceId, timeZone, language } = {}) => {
+  return apiClient.post('/ai/agent', {
+    question,
+    trace_id: traceId || undefined,
+    time_zone: timeZone || undefined,
+    language: language || undefined,
+  }, {
+    // 多轮工具调用，耗时远长于普通请求
+    timeout: 300000,
+  })
+}
+
+// 创建 AI 分析会话：target 为 { traceId } 或 { search }，首轮为普通分析
+export const createAiSession = (target, { template, language } = {}) => {
+  return apiClient.post('/ai/sessions', {
+    trace_id: target.traceId || undefined,
+    search: target.search || undefined,
+    template: template || undefined,
+    language: language || undefined,
+  })
+}
+
+// 在会话中追问，返回更新后的会话
+export const askAiSession = (sessionId, question) => {
+  return apiClient.post(`/ai/sessions/${sessionId}/messages`, { question })
+}
+
+export const getAiSession = (sessionId) => {
+  return apiClient.get(`/ai/sessions/${sessionId}`)
+}
+
+export const getAiTemplates = () => {
+  return apiClient.get('/ai/templates')
 }
 
 export default apiClient
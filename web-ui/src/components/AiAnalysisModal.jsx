@@ -1,10 +1,60 @@
 import React from 'react'
-import { Modal, Spin, Alert, Typography } from 'antd'
-import { BulbOutlined } from '@ant-design/icons'
+import { Modal, Spin, Alert, Typography, Select, Space, Input, Button } from 'antd'
+import { BulbOutlined, SendOutlined } from '@ant-design/icons'
+import dayjs from 'dayjs'
 
 const { Text, Paragraph } = Typography
 
-const AiAnalysisModal = ({ visible, onClose, loading, analysis, traceId }) => {
+// 回答语言选项，value 为发送给服务端的语言代码
+const LANGUAGE_OPTIONS = [
+  { value: 'zh', label: '中文' },
+  { value: 'en', label: 'English' },
+  { value: 'ja', label: '日本語' },
+]
+
+// 分析与回答内容的样式
+const ANSWER_STYLE = {
+  whiteSpace: 'pre-wrap',
+  wordBreak: 'break-word',
+  margin: 0,
+  fontSize: '14px',
+  lineHeight: '1.8',
+  fontFamily: '-apple-system, BlinkMacSystemFont, "Segoe UI", "PingFang SC", "Hiragino Sans GB", "Microsoft YaHei", sans-serif',
+}
+
+const AiAnalysisModal = ({
+  visible,
+  onClose,
+  loading,
+  analysis,
+  subject,
+  emptyDescription = '该 trace_id 没有找到相关的错误日志可供分析。',
+  cachedAt,
+  onRefresh,
+  templates = [],
+  template,
+  language,
+  onOptionsChange,
+  followUps = [],
+  onAsk,
+  asking = false,
+  sessionLink,
+}) => {
+  const [question, setQuestion] = React.useState('')
+
+  const handleAsk = async () => {
+    if (!question.trim() || asking) {
+      return
+    }
+    if (await onAsk(question.trim())) {
+      setQuestion('')
+    }
+  }
+
+  const languageOptions = LANGUAGE_OPTIONS.some((option) => option.value === language) || !language
+    ? LANGUAGE_OPTIONS
+    : [...LANGUAGE_OPTIONS, { value: language, label: language }]
+
   return (
     <Modal
       title={
@@ -17,8 +67,8 @@ const AiAnalysisModal = ({ visible, onClose, loading, analysis, traceId }) => {
       onCancel={onClose}
       width={800}
       footer={[
-        <Text key="trace" type="secondary" style={{ float: 'left' }}>
-          Trace ID: {traceId}
+        <Text key="subject" type="secondary" style={{ float: 'left' }}>
+          {subject}
         </Text>,
         <button key="close" onClick={onClose} style={{ padding: '6px 16px' }}>
           关闭
@@ -26,24 +76,60 @@ const AiAnalysisModal = ({ visible, onClose, loading, analysis, traceId }) => {
       ]}
       destroyOnClose
     >
+      {templates.length > 0 && (
+        <Space style={{ marginBottom: '12px' }}>
+          <Select
+            style={{ width: 200 }}
+            value={template}
+            disabled={loading}
+            onChange={(value) => onOptionsChange({ template: value, language })}
+            options={templates.map((item) => ({
+              value: item.name,
+              label: <span title={item.description}>{item.name}</span>,
+            }))}
+          />
+          <Select
+            style={{ width: 120 }}
+            value={language}
+            disabled={loading}
+            onChange={(value) => onOptionsChange({ template, language: value })}
+            options={languageOptions}
+          />
+        </Space>
+      )}
+
       {loading && (
         <div style={{ textAlign: 'center', padding: '40px 0' }}>
           <Spin size="large" />
           <div style={{ marginTop: '16px', color: '#666' }}>
-            AI 正在分析错误日志，请稍候...
+            AI 正在分析日志，请稍候...
           </div>
         </div>
       )}
 
       {!loading && !analysis && (
         <Alert
-          message="未找到错误日志"
-          description="该 trace_id 没有找到相关的错误日志可供分析。"
+          message="未找到可分析的日志"
+          description={emptyDescription}
           type="warning"
           showIcon
         />
       )}
 
+      {!loading && analysis && cachedAt && (
+        <Alert
+          style={{ marginBottom: '12px' }}
+          message={`缓存的分析结果（分析于 ${dayjs(cachedAt).format('YYYY-MM-DD HH:mm:ss')}）`}
+          type="info"
+          showIcon
+          action={
+            <button onClick={onRefresh} style={{ padding: '2px 12px' }}>
+              重新分析
+            </button>
+          }
+        />
+      )}
+
       {!loading && analysis && (
         <div
           style={{
@@ -54,18 +140,47 @@ const AiAnalysisModal = ({ visible, onClose, loading, analysis, traceId }) => {
             overflow: 'auto',
           }}
         >
-          <Paragraph
-            style={{
-              whiteSpace: 'pre-wrap',
-              wordBreak: 'break-word',
-              margin: 0,
-              fontSize: '14px',
-              lineHeight: '1.8',
-              fontFamily: '-apple-system, BlinkMacSystemFont, "Segoe UI", "PingFang SC", "Hiragino Sans GB", "Microsoft YaHei", sans-serif',
-            }}
-          >
+          <Paragraph style={ANSWER_STYLE}>
             {analysis}
           </Paragraph>
+
+          {/* 追问与回答 */}
+          {followUps.map((turn) => (
+            <div key={turn.created_at} style={{ marginTop: '20px' }}>
+              <Text strong>问：{turn.question}</Text>
+              <Paragraph style={{ ...ANSWER_STYLE, marginTop: '8px' }}>
+                {turn.answer}
+              </Paragraph>
+            </div>
+          ))}
+          {asking && (
+            <div style={{ marginTop: '16px', color: '#666' }}>
+              <Spin size="small" /> AI 正在回答...
+            </div>
+          )}
+        </div>
+      )}
+
+      {!loading && analysis && onAsk && (
+        <Space.Compact style={{ width: '100%', marginTop: '12px' }}>
+          <Input
+            placeholder="继续追问，如：这个错误是从什么时候开始的？"
+            value={question}
+            onChange={(e) => setQuestion(e.target.value)}
+            onPressEnter={handleAsk}
+            disabled={asking}
+            maxLength={2000}
+          />
+          <Button type="primary" icon={<SendOutlined />} onClick={handleAsk} loading={asking}>
+            追问
+          </Button>
+        </Space.Compact>
+      )}
+
+      {sessionLink && (
+        <div style={{ marginTop: '8px' }}>
+          <Text type="secondary">会话链接：</Text>
+          <Text copyable>{sessionLink}</Text>
         </div>
       )}
     </Modal>
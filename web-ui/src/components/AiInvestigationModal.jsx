@@ -0,0 +1,96 @@
+import React from 'react'
+import { Modal, Spin, Alert, Typography, List, Tag } from 'antd'
+import { SearchOutlined } from '@ant-design/icons'
+
+const { Text, Paragraph } = Typography
+
+// 调查提前结束的说明
+const STOP_REASONS = {
+  step_limit: '已达到工具调用次数上限，结论基于已有的查询结果',
+  token_limit: '已达到 token 预算，结论基于已有的查询结果',
+}
+
+// AI 调查结果：结论以及 AI 执行过的查询
+const AiInvestigationModal = ({ visible, onClose, loading, question, result }) => {
+  return (
+    <Modal
+      title={
+        <div style={{ display: 'flex', alignItems: 'center', gap: '8px' }}>
+          <SearchOutlined style={{ fontSize: '20px', color: '#1677ff' }} />
+          <span>AI 调查</span>
+        </div>
+      }
+      open={visible}
+      onCancel={onClose}
+      width={900}
+      footer={null}
+      destroyOnClose
+    >
+      <Text type="secondary">{question}</Text>
+
+      {loading && (
+        <div style={{ textAlign: 'center', padding: '40px 0' }}>
+          <Spin size="large" />
+          <div style={{ marginTop: '16px', color: '#666' }}>
+            AI 正在查询日志并分析，可能需要几分钟...
+          </div>
+        </div>
+      )}
+
+      {!loading && result && (
+        <>
+          {STOP_REASONS[result.stop_reason] && (
+            <Alert
+              style={{ marginTop: '12px' }}
+              message={STOP_REASONS[result.stop_reason]}
+              type="warning"
+              showIcon
+            />
+          )}
+
+          <div
+            style={{
+              background: '#f5f5f5',
+              padding: '24px',
+              borderRadius: '8px',
+              marginTop: '12px',
+              maxHeight: '400px',
+              overflow: 'auto',
+            }}
+          >
+            <Paragraph style={{ whiteSpace: 'pre-wrap', wordBreak: 'break-word', margin: 0, lineHeight: '1.8' }}>
+              {result.analysis}
+            </Paragraph>
+          </div>
+
+          <List
+            style={{ marginTop: '12px' }}
+            size="small"
+            header={
+              <Text strong>
+                执行过的查询（{result.steps.length} 次，调用 AI {result.ai_calls} 次，
+                {result.usage.prompt_tokens + result.usage.completion_tokens} tokens）
+              </Text>
+            }
+            dataSource={result.steps}
+            renderItem={(step, index) => (
+              <List.Item key={index}>
+                <div style={{ width: '100%' }}>
+                  <Tag color="blue">{step.tool}</Tag>
+                  <Text code style={{ wordBreak: 'break-all' }}>{JSON.stringify(step.arguments)}</Text>
+                  <div style={{ marginTop: '4px' }}>
+                    {step.error
+                      ? <Text type="danger">{step.error}</Text>
+                      : <Text type="secondary">{step.summary}（{step.took_ms} ms）</Text>}
+                  </div>
+                </div>
+              </List.Item>
+            )}
+          />
+        </>
+      )}
+    </Modal>
+  )
+}
+
+export default AiInvestigationModal
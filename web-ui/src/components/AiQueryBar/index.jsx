@@ -0,0 +1,198 @@
+import React from 'react'
+import { Input, Button, Space, Alert, Tag, Popover, message } from 'antd'
+import { RobotOutlined, PlayCircleOutlined, SearchOutlined, ClockCircleOutlined } from '@ant-design/icons'
+import dayjs from 'dayjs'
+import { aiTranslateQuery, aiInvestigate } from '../../api/search'
+import AiInvestigationModal from '../AiInvestigationModal'
+import TimeRangePicker from '../TimeRangePicker'
+
+// 生成的时间范围的显示文字
+const formatTimeRange = (search) => {
+  if (search.time_range_type === 'relative') {
+    return `${search.relative_time_key} ~ ${search.relative_end_key || 'now'}`
+  }
+  const start = dayjs(search.start_time).format('YYYY-MM-DD HH:mm:ss')
+  const end = dayjs(search.end_time).format('YYYY-MM-DD HH:mm:ss')
+  return `${start} ~ ${end}`
+}
+
+// 用 TimeRangePicker 选择的时间范围替换生成的时间范围
+const withTimeRange = (search, timeRange) => {
+  const rest = { ...search }
+  delete rest.relative_time_key
+  delete rest.relative_end_key
+  delete rest.start_time
+  delete rest.end_time
+  if (timeRange.type === 'relative') {
+    return { ...rest, time_range_type: 'relative', relative_time_key: timeRange.key }
+  }
+  return {
+    ...rest,
+    time_range_type: 'absolute',
+    start_time: timeRange.dates[0].toISOString(),
+    end_time: timeRange.dates[1].toISOString(),
+  }
+}
+
+// 用自然语言生成查询条件，用户确认或修改查询语句后再执行；
+// 也可以让 AI 自行查询日志调查问题
+const AiQueryBar = ({ onApply, loading }) => {
+  const [question, setQuestion] = React.useState('')
+  const [translating, setTranslating] = React.useState(false)
+  const [result, setResult] = React.useState(null)
+  const [query, setQuery] = React.useState('')
+  // 生成的查询条件，执行前可以删除过滤条件、修改时间范围
+  const [search, setSearch] = React.useState(null)
+  const [timePickerOpen, setTimePickerOpen] = React.useState(false)
+  const [investigating, setInvestigating] = React.useState(false)
+  const [investigation, setInvestigation] = React.useState(null)
+  const [investigationVisible, setInvestigationVisible] = React.useState(false)
+
+  const handleTranslate = async () => {
+    if (!question.trim()) {
+      return
+    }
+    setTranslating(true)
+    try {
+      const response = await aiTranslateQuery(question, {
+        timeZone: Intl.DateTimeFormat().resolvedOptions().timeZone,
+      })
+      setResult(response)
+      setSearch(response.search)
+      setQuery(response.search.query)
+    } catch (error) {
+      message.error(`生成查询失败: ${error.message}`)
+    } finally {
+      setTranslating(false)
+    }
+  }
+
+  const handleInvestigate = async () => {
+    if (!question.trim()) {
+      return
+    }
+    setInvestigationVisible(true)
+    setInvestigating(true)
+    setInvestigation(null)
+    try {
+      const response = await aiInvestigate(question, {
+        timeZone: Intl.DateTimeFormat().resolvedOptions().timeZone,
+      })
+      setInvestigation(response)
+    } catch (error) {
+      message.error(`AI 调查失败: ${error.message}`)
+      setInvestigationVisible(false)
+    } finally {
+      setInvestigating(false)
+    }
+  }
+
+  const handleApply = () => {
+    onApply({ ...search, query: query || '*', page: 1 })
+  }
+
+  const handleRemoveFilter = (field) => {
+    const filters = { ...search.filters }
+    delete filters[field]
+    setSearch({ ...search, filters })
+  }
+
+  const handleTimeRangeChange = (timeRange) => {
+    setSearch(withTimeRange(search, timeRange))
+    setTimePickerOpen(false)
+  }
+
+  return (
+    <Space direction="vertical" style={{ width: '100%' }} size="small">
+      <Space.Compact style={{ width: '100%' }}>
+        <Input
+          placeholder="用自然语言描述要查找的日志，如：prod 环境 payment 服务今天 10 点以来的超时"
+          value={question}
+          onChange={(e) => setQuestion(e.target.value)}
+          onPressEnter={handleTranslate}
+          prefix={<RobotOutlined />}
+          maxLength={500}
+        />
+        <Button onClick={handleTranslate} loading={translating}>
+          生成查询
+        </Button>
+        <Button icon={<SearchOutlined />} onClick={handleInvestigate} loading={investigating}>
+          AI 调查
+        </Button>
+      </Space.Compact>
+
+      <AiInvestigationModal
+        visible={investigationVisible}
+        onClose={() => setInvestigationVisible(false)}
+        loading={investigating}
+        question={question}
+        result={investigation}
+      />
+
+      {result && search && (
+        <Alert
+          type="info"
+          message={result.explanation || '已生成查询条件，确认或修改后执行'}
+          description={
+            <Space direction="vertical" style={{ width: '100%' }} size="small">
+              <Input
+                value={query}
+                onChange={(e) => setQuery(e.target.value)}
+                onPressEnter={handleApply}
+                addonBefore="查询"
+              />
+              <div>
+                {Object.entries(search.filters || {}).map(([field, value]) => (
+                  <Tag
+                    key={field}
+                    color="blue"
+                    closable
+                    onClose={(e) => {
+                      e.preventDefault()
+                      handleRemoveFilter(field)
+                    }}
+                  >
+                    {field}={value}
+                  </Tag>
+                ))}
+                <Popover
+                  content={<TimeRangePicker onChange={handleTimeRangeChange} />}
+                  title="修改时间范围"
+                  trigger="click"
+                  placement="bottomLeft"
+                  open={timePickerOpen}
+                  onOpenChange={setTimePickerOpen}
+                >
+                  <Tag icon={<ClockCircleOutlined />} style={{ cursor: 'pointer' }}>
+                    {formatTimeRange(search)}
+                  </Tag>
+                </Popover>
+              </div>
+              {(result.warnings || []).map((warning) => (
+                <div key={warning} style={{ color: '#d48806' }}>⚠ {warning}</div>
+              ))}
+            </Space>
+          }
+          action={
+            <Button
+              type="primary"
+              size="small"
+              icon={<PlayCircleOutlined />}
+              onClick={handleApply}
+              loading={loading}
+            >
+              执行查询
+            </Button>
+          }
+          closable
+          onClose={() => {
+            setResult(null)
+            setSearch(null)
+          }}
+        />
+      )}
+    </Space>
+  )
+}
+
+export default AiQueryBar
@@ -1,11 +1,23 @@
-import React, { useState, useMemo } from 'react'
+import React, { useState, useMemo, useEffect } from 'react'
 import { Card, Space, message, Statistic, Row, Col, Button, Tag } from 'antd'
 import { ClockCircleOutlined, FileTextOutlined, BulbOutlined } from '@ant-design/icons'
 import SearchBar from '../../components/SearchBar'
 import FilterPanel from '../../components/FilterPanel'
 import LogTable from '../../components/LogTable'
 import AiAnalysisModal from '../../components/AiAnalysisModal'
-import { searchLogs, aiAnalyzeError } from '../../api/search'
+import AiQueryBar from '../../components/AiQueryBar'
+import {
+  searchLogs,
+  aiAnalyzeError,
+  aiAnalyzeSearch,
+  getAiTemplates,
+  createAiSession,
+  askAiSession,
+  getAiSession,
+} from '../../api/search'
+
+// 会话的分享链接，打开后加载会话并可以继续追问
+const sessionLink = (sessionId) => `${window.location.origin}${window.location.pathname}?session=${sessionId}`
 
 const SearchPage = () => {
   const [loading, setLoading] = useState(false)
@@ -22,7 +34,46 @@ const SearchPage = () => {
   const [aiModalVisible, setAiModalVisible] = useState(false)
   const [aiLoading, setAiLoading] = useState(false)
   const [aiAnalysis, setAiAnalysis] = useState('')
-  const [aiTraceId, setAiTraceId] = useState('')
+  // trace：分析一个 trace 的错误；search：总结当前搜索结果
+  const [aiMode, setAiMode] = useState('trace')
+  const [aiSubject, setAiSubject] = useState('')
+  const [aiCachedAt, setAiCachedAt] = useState(null)
+  const [aiTemplates, setAiTemplates] = useState([])
+  const [aiTemplate, setAiTemplate] = useState(null)
+  const [aiLanguage, setAiLanguage] = useState(null)
+  const [aiTraceId, setAiTraceId] = useState(null)
+  // 第一次追问时创建的会话，之后的追问都在该会话中进行
+  const [aiSession, setAiSession] = useState(null)
+  const [aiAsking, setAiAsking] = useState(false)
+
+  // 加载提示词模板，默认选中服务端配置的模板与语言
+  useEffect(() => {
+    getAiTemplates()
+      .then((result) => {
+        setAiTemplates(result.templates || [])
+        setAiTemplate(result.default_template)
+        setAiLanguage(result.default_language)
+      })
+      .catch(() => {})
+  }, [])
+
+  // 通过链接打开已有的会话
+  useEffect(() => {
+    const sessionId = new URLSearchParams(window.location.search).get('session')
+    if (!sessionId) {
+      return
+    }
+    setAiModalVisible(true)
+    setAiLoading(true)
+    getAiSession(sessionId)
+      .then((session) => {
+        setAiSession(session)
+        setAiSubject(session.title)
+        setAiAnalysis(session.turns[0]?.answer || '')
+      })
+      .catch((error) => message.error(`加载 AI 会话失败: ${error.message}`))
+      .finally(() => setAiLoading(false))
+  }, [])
 
   // 检查是否有 trace_id 可用于 AI 分析
   const hasTraceId = useMemo(() => {
@@ -30,21 +81,32 @@ const SearchPage = () => {
   }, [data])
 
   // 处理 AI 分析
-  const handleAiAnalyze = async () => {
+  const handleAiAnalyze = async ({ mode = aiMode, forceRefresh = false, template = aiTemplate, language = aiLanguage } = {}) => {
     const traceId = data.find(log => log.trace_id)?.trace_id
-    if (!traceId) {
+    if (mode === 'trace' && !traceId) {
       message.warning('未找到可分析的 trace_id')
       return
     }
 
+    setAiMode(mode)
     setAiTraceId(traceId)
+    setAiSession(null)
+    setAiSubject(mode === 'trace' ? `Trace ID: ${traceId}` : `查询: ${searchParams.query}`)
     setAiModalVisible(true)
     setAiLoading(true)
     setAiAnalysis('')
+    setAiCachedAt(null)
 
     try {
-      const result = await aiAnalyzeError(traceId)
+      const options = { forceRefresh, template, language }
+      const result = mode === 'trace'
+        ? await aiAnalyzeError(traceId, options)
+        : await aiAnalyzeSearch(searchParams, options)
       setAiAnalysis(result.analysis)
+      setAiCachedAt(result.cached_at || null)
+      if (mode === 'search' && result.sampled) {
+        setAiSubject(`查询: ${searchParams.query}（共 ${result.total} 条，采样 ${result.sampled} 条，${result.patterns} 种模式）`)
+      }
     } catch (error) {
       message.error(`AI 分析失败: ${error.message}`)
       setAiAnalysis('分析失败，请检查日志或稍后重试。')
@@ -53,6 +115,26 @@ const SearchPage = () => {
     }
   }
 
+  // 追问：第一次追问时以当前分析对象创建会话（首轮分析命中缓存），成功时返回 true
+  const handleAiAsk = async (question) => {
+    setAiAsking(true)
+    try {
+      let session = aiSession
+      if (!session) {
+        const target = aiMode === 'trace' ? { traceId: aiTraceId } : { search: searchParams }
+        session = await createAiSession(target, { template: aiTemplate, language: aiLanguage })
+        setAiSession(session)
+      }
+      setAiSession(await askAiSession(session.id, question))
+      return true
+    } catch (error) {
+      message.error(`追问失败: ${error.message}`)
+      return false
+    } finally {
+      setAiAsking(false)
+    }
+  }
+
   // 搜索参数状态
   const [searchParams, setSearchParams] = useState({
     query: '*',
@@ -80,6 +162,9 @@ const SearchPage = () => {
         // 相对时间：传 key 给后端
         apiParams.time_range_type = 'relative'
         apiParams.relative_time_key = params.relative_time_key
+        // AI 生成的查询可能带相对终点与时区（如 "今天 10 点以来"）
+        apiParams.relative_end_key = params.relative_end_key || undefined
+        apiParams.time_zone = params.time_zone || undefined
         console.log('API request with relative time:', apiParams) // 调试
       } else if (params.time_range_type === 'absolute') {
         // 绝对时间：传时间戳给后端
@@ -116,6 +201,8 @@ const SearchPage = () => {
       if (params.time_range_type === 'relative') {
         newSearchParams.time_range_type = 'relative'
         newSearchParams.relative_time_key = params.relative_time_key
+        newSearchParams.relative_end_key = params.relative_end_key
+        newSearchParams.time_zone = params.time_zone
       } else {
         newSearchParams.time_range_type = 'absolute'
         newSearchParams.start_time = params.start_time || searchParams.start_time
@@ -166,6 +253,8 @@ const SearchPage = () => {
       // 保留当前的时间范围参数
       time_range_type: searchParams.time_range_type,
       relative_time_key: searchParams.relative_time_key,
+      relative_end_key: searchParams.relative_end_key,
+      time_zone: searchParams.time_zone,
       start_time: searchParams.start_time,
       end_time: searchParams.end_time,
     })
@@ -179,6 +268,8 @@ const SearchPage = () => {
       // 保留当前的时间范围参数
       time_range_type: searchParams.time_range_type,
       relative_time_key: searchParams.relative_time_key,
+      relative_end_key: searchParams.relative_end_key,
+      time_zone: searchParams.time_zone,
       start_time: searchParams.start_time,
       end_time: searchParams.end_time,
     })
@@ -192,6 +283,8 @@ const SearchPage = () => {
       // 保留当前的时间范围参数
       time_range_type: searchParams.time_range_type,
       relative_time_key: searchParams.relative_time_key,
+      relative_end_key: searchParams.relative_end_key,
+      time_zone: searchParams.time_zone,
       start_time: searchParams.start_time,
       end_time: searchParams.end_time,
     })
@@ -201,7 +294,10 @@ const SearchPage = () => {
     <Space direction="vertical" style={{ width: '100%' }} size="large">
       {/* 搜索栏 */}
       <Card>
-        <SearchBar onSearch={handleSearchBarSearch} loading={loading} />
+        <Space direction="vertical" style={{ width: '100%' }} size="middle">
+          <SearchBar onSearch={handleSearchBarSearch} loading={loading} />
+          <AiQueryBar onApply={handleSearch} loading={loading} />
+        </Space>
       </Card>
 
       {/* 过滤面板 */}
@@ -239,19 +335,29 @@ const SearchPage = () => {
                 <span style={{ color: 'rgba(0, 0, 0, 0.65)', fontSize: '14px' }}>
                   AI 分析
                 </span>
-                {hasTraceId ? (
+                <Space wrap>
+                  {hasTraceId ? (
+                    <Button
+                      type="primary"
+                      icon={<BulbOutlined />}
+                      onClick={() => handleAiAnalyze({ mode: 'trace' })}
+                      disabled={loading}
+                      loading={aiLoading && aiMode === 'trace'}
+                    >
+                      AI 分析错误原因
+                    </Button>
+                  ) : (
+                    <Tag color="default">暂无可分析的错误</Tag>
+                  )}
                   <Button
-                    type="primary"
                     icon={<BulbOutlined />}
-                    onClick={handleAiAnalyze}
-                    disabled={loading}
-                    loading={aiLoading}
+                    onClick={() => handleAiAnalyze({ mode: 'search' })}
+                    disabled={loading || total === 0}
+                    loading={aiLoading && aiMode === 'search'}
                   >
-                    AI 分析错误原因
+                    AI 总结搜索结果
                   </Button>
-                ) : (
-                  <Tag color="default">暂无可分析的错误</Tag>
-                )}
+                </Space>
               </div>
             </Card>
           </Col>
@@ -274,7 +380,22 @@ const SearchPage = () => {
         onClose={() => setAiModalVisible(false)}
         loading={aiLoading}
         analysis={aiAnalysis}
-        traceId={aiTraceId}
+        subject={aiSubject}
+        emptyDescription={aiMode === 'trace' ? '该 trace_id 没有找到相关的错误日志可供分析。' : '当前查询没有匹配的日志可供分析。'}
+        cachedAt={aiCachedAt}
+        onRefresh={() => handleAiAnalyze({ forceRefresh: true })}
+        templates={aiTemplates}
+        template={aiTemplate}
+        language={aiLanguage}
+        onOptionsChange={({ template, language }) => {
+          setAiTemplate(template)
+          setAiLanguage(language)
+          handleAiAnalyze({ template, language })
+        }}
+        followUps={aiSession ? aiSession.turns.slice(1) : []}
+        onAsk={handleAiAsk}
+        asking={aiAsking}
+        sessionLink={aiSession && sessionLink(aiSession.id)}
       />
     </Space>
   )